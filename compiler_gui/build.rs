@@ -1,5 +1,7 @@
 #[cfg(windows)]
 fn main() {
+    embed_default_stubs();
+
     // This tells Rust to build the application as a Windows GUI app (no console window)
     // Only needed on Windows
     if std::env::var("CARGO_CFG_TARGET_OS").unwrap() == "windows" {
@@ -8,6 +10,7 @@ fn main() {
             
             // Check if icon file exists, otherwise skip it (don't fail the build)
             let icon_path = "assets/app_icon.ico";
+            println!("cargo:rerun-if-changed={}", icon_path);
             if std::path::Path::new(icon_path).exists() {
                 res.set_icon(icon_path);
             } else {
@@ -26,5 +29,24 @@ fn main() {
 
 #[cfg(not(windows))]
 fn main() {
-    // Nothing to do for non-Windows platforms
+    embed_default_stubs();
+}
+
+// Copy prebuilt stubs into OUT_DIR so main.rs can include_bytes! them as the
+// built-in default stub. Build the stub first (or point RC_DEFAULT_STUB /
+// RC_DEFAULT_STUB_X86 at it); a missing stub is embedded as an empty file and
+// the compiler then falls back to reading stub.exe at runtime.
+fn embed_default_stubs() {
+    println!("cargo:rerun-if-changed=build.rs");
+    let out_dir = std::path::PathBuf::from(std::env::var("OUT_DIR").unwrap());
+    for (env_var, default_path, name) in [
+        ("RC_DEFAULT_STUB", "../stub.exe", "stub.exe"),
+        ("RC_DEFAULT_STUB_X86", "../stub_x86.exe", "stub_x86.exe"),
+    ] {
+        println!("cargo:rerun-if-env-changed={}", env_var);
+        let source = std::env::var(env_var).unwrap_or_else(|_| default_path.to_string());
+        println!("cargo:rerun-if-changed={}", source);
+        let stub = std::fs::read(&source).unwrap_or_default();
+        std::fs::write(out_dir.join(name), stub).expect("Failed to write the default stub to OUT_DIR");
+    }
 }
@@ -1,47 +1,306 @@
 #![cfg_attr(windows, windows_subsystem = "windows")]
+#![recursion_limit = "256"]
 
 use eframe::{egui};
 use serde::{Serialize, Deserialize};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
 use egui::Vec2;
+use resource_archive::{
+    archive_layout, check_stub, finish_resource_data, gzip_compress, EntryCompression, pack_archive_aligned, pe_signature_range, read_u32_le, sandboxed_path, strip_signature, unpack_archive, unpack_header, ArchiveHeader, Dependency,
+    LayoutRegion, RegionKind, ResourceEntry, ResourcePacker,
+    ServiceOptions, ShortcutOptions, StubMessages, COMPRESSION_BLOCK_SIZE, FOOTER_SIZE, MAX_ARCHIVE_SIZE,
+    SPLIT_MARKER, SPLIT_TRAILER_SIZE,
+};
 
-// Footer constants: our appended archive is terminated with a footer
-const FOOTER_MARKER: &[u8; 16] = b"RSCARCHIVE_V1___";
+// How much of a resource the preview pane reads, and how much of that is shown as hex for binary files
+const PREVIEW_BYTES: usize = 16 * 1024;
+const HEX_PREVIEW_BYTES: usize = 256;
 
-// Each resource is recorded with its filename and size.
-#[derive(Serialize, Deserialize)]
-struct ResourceEntry {
-    filename: String,
-    size: u32,
+// Stubs embed "RSC_CAPABILITIES:<feature>,<feature>,...;" listing the header
+// options they understand. Stubs built before the tag have none.
+const CAPABILITIES_MARKER: &[u8] = b"RSC_CAPABILITIES:";
+
+// Stubs built into the compiler by build.rs, used when no stub path is set.
+// Empty if no stub was available when the compiler was built.
+const EMBEDDED_STUB_X64: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/stub.exe"));
+const EMBEDDED_STUB_X86: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/stub_x86.exe"));
+
+// PE optional header Subsystem values for a windowed and a console program
+const IMAGE_SUBSYSTEM_WINDOWS_GUI: u16 = 2;
+const IMAGE_SUBSYSTEM_WINDOWS_CUI: u16 = 3;
+
+// Upper bounds of the MB settings: 1 TB of extracted files, and volumes no
+// larger than a whole archive can be
+const MAX_EXTRACT_SIZE_MB: u64 = 1024 * 1024;
+const MAX_SPLIT_SIZE_MB: u64 = (MAX_ARCHIVE_SIZE / (1024 * 1024)) as u64;
+
+// Error returned by compile_exe when the user cancels a build
+const BUILD_CANCELLED: &str = "Build cancelled";
+
+// Timestamp server signtool is pointed at unless the project names another
+const DEFAULT_TIMESTAMP_URL: &str = "http://timestamp.digicert.com";
+
+// A glob pattern (e.g. `assets/**/*.png`) evaluated relative to a base folder.
+// Patterns are stored in the project so re-opening it picks up newly matching files.
+#[derive(Serialize, Deserialize, Clone)]
+struct ResourceGlob {
+    base: PathBuf,
+    pattern: String,
 }
 
-// The archive header now also includes execution_style, run_as_admin, and is_compressed
-#[derive(Serialize, Deserialize)]
-struct ArchiveHeader {
-    extraction_path: String,
-    main_file: String,
-    resources: Vec<ResourceEntry>,
-    execution_style: String,
-    run_as_admin: bool,
-    is_compressed: bool,  // Added this field to indicate if resources are compressed
+// One entry of the resource list. The same file may be listed more than once
+// (see "Clone"), so the settings of an entry live here rather than in maps
+// keyed by its path. Empty strings mean "not set".
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq)]
+struct ResourceItem {
+    path: PathBuf,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    tag: String, // groups the list
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    subdir: String, // subfolder of the extraction path
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    name: String, // name it is extracted as, instead of its filename
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    compression: String, // "store" or "compress", empty = auto; see uses_entry_compression
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    profile: String, // profile it belongs to, empty = shared by every build
+}
+
+impl ResourceItem {
+    fn new(path: PathBuf) -> Self {
+        ResourceItem { path, ..Default::default() }
+    }
+
+    // Name the resource is stored under in the header: its filename (or target name),
+    // prefixed with the target subfolder (using '/') when one is set.
+    fn archive_filename(&self) -> Result<String, String> {
+        let filename = if self.name.is_empty() {
+            resource_filename(&self.path)?
+        } else {
+            check_target_name(&self.name)?;
+            self.name.clone()
+        };
+        let mut parts = Vec::new();
+        for part in self.subdir.split(['/', '\\']).filter(|p| !p.is_empty() && *p != ".") {
+            if part == ".." || part.contains(':') {
+                return Err(format!("Target folder {:?} of {} must stay inside the extraction path", self.subdir, filename));
+            }
+            parts.push(part);
+        }
+        parts.push(&filename);
+        Ok(parts.join("/"))
+    }
+}
+
+// A .zip whose files are packed as resources, keeping their paths inside the
+// zip as archive names. Entries are read straight from the zip when compiling.
+#[derive(Clone)]
+struct ResourceZip {
+    path: PathBuf,
+    entries: Vec<String>, // archive names of the files in the zip, listed when it was added
+}
+
+impl ResourceZip {
+    fn open(path: PathBuf) -> Result<Self, String> {
+        let file = fs::File::open(&path).map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+        let mut archive = zip::ZipArchive::new(file)
+            .map_err(|e| format!("{:?} is not a valid zip file: {}", path, e))?;
+        let mut entries = Vec::new();
+        for i in 0..archive.len() {
+            let entry = archive.by_index_raw(i)
+                .map_err(|e| format!("Failed to read entry {} of {:?}: {}", i, path, e))?;
+            if let Some(name) = zip_entry_name(&entry) {
+                entries.push(name);
+            }
+        }
+        Ok(Self { path, entries })
+    }
+}
+
+// Capabilities read from the stub a build would use, cached per file
+#[derive(Clone)]
+struct StubCapabilities {
+    path: PathBuf,
+    modified: Option<std::time::SystemTime>,
+    features: Option<Vec<String>>, // None = the stub has no capabilities tag
+    signed: bool, // carries an Authenticode signature, which packing invalidates
+}
+
+// An archive parsed back out of a packed EXE or pack file.
+struct ParsedArchive {
+    header: ArchiveHeader,
+    data: Vec<u8>, // decompressed resource data
+    archive_start: usize, // where the archive begins, i.e. the length of the stub in an EXE
+}
+
+// A compile running on a background thread. The GUI polls `result` each frame
+// and sets `cancel` when the user presses Cancel.
+struct CompileJob {
+    cancel: AtomicBool,
+    result: Mutex<Option<Result<String, String>>>,
+    close_when_done: bool, // started by "Compile and Close", independent of the saved setting
+}
+
+// A compare_compression run on a background thread, polled like CompileJob
+struct ComparisonJob {
+    cancel: AtomicBool,
+    result: Mutex<Option<Result<CompressionComparison, String>>>,
+}
+
+// Lowercased text of a resource and the modified time it was read at; None = binary/unreadable
+type ResourceText = (Option<std::time::SystemTime>, Option<String>);
+
+// Resource texts for the "Contents" search. A background thread reads the files
+// and matches the query, the resource list only looks up `matches`; see
+// refresh_content_search.
+#[derive(Default)]
+struct ContentSearch {
+    texts: Mutex<HashMap<PathBuf, ResourceText>>,
+    matches: Mutex<HashSet<PathBuf>>, // resources whose text contains the last scanned query
+    scanning: AtomicBool,
+}
+
+impl ContentSearch {
+    // Re-read resources whose modified time changed, forget removed ones and
+    // match `query_lower`. Binaries and files over 4 MB never match.
+    fn scan(&self, resources: &[PathBuf], query_lower: &str) {
+        const MAX_SEARCH_SIZE: u64 = 4 * 1024 * 1024;
+        let mut texts = self.texts.lock().unwrap();
+        let current: HashSet<&PathBuf> = resources.iter().collect();
+        texts.retain(|path, _| current.contains(path));
+        for path in resources {
+            let Ok(metadata) = fs::metadata(path) else {
+                texts.remove(path);
+                continue;
+            };
+            let modified = metadata.modified().ok();
+            if texts.get(path).is_some_and(|(read_at, _)| *read_at == modified) {
+                continue;
+            }
+            let text = (metadata.len() <= MAX_SEARCH_SIZE)
+                .then(|| fs::read(path).ok())
+                .flatten()
+                .filter(|bytes| looks_like_text(&bytes[..bytes.len().min(512)]))
+                .map(|bytes| String::from_utf8_lossy(&bytes).to_lowercase());
+            texts.insert(path.clone(), (modified, text));
+        }
+        let matches = texts.iter()
+            .filter(|(_, (_, text))| text.as_deref().is_some_and(|text| text.contains(query_lower)))
+            .map(|(path, _)| path.clone())
+            .collect();
+        *self.matches.lock().unwrap() = matches;
+    }
 }
 
 // The GUI app state now holds additional fields including theme selection and project management
+#[derive(Clone)]
 struct AppState {
     extraction_path: String,
     main_file: String,      // resource filename that should be launched
-    resources: Vec<PathBuf>, // list of resource file paths
+    main_is_external: bool, // main_file is a path/command on the target machine rather than a resource
+    resources: Vec<ResourceItem>, // resource files in extraction order, with their settings
     output_exe: String,
     execution_style: String, // one of "no-window", "minimized", "normal", "maximized"
+    launch_verb: String, // one of "open", "runas", "print", "edit", "explore"
+    launch_args: String, // arguments passed to the main file
+    dependencies: Vec<Dependency>, // prerequisites checked on the target before extracting
+    post_extract_command: String, // setup command run after extracting, e.g. regsvr32 /s lib.dll
+    post_extract_style: String, // window state of the setup command
+    post_extract_abort_on_failure: bool, // skip launching when the setup command fails
+    working_dir: String, // start folder of the main file, relative to the extraction path
+    package_version: String, // recorded in the header, e.g. "1.2.0"
+    stamp_build_time: bool, // record when the EXE was built, which makes every build's bytes differ
+    capture_output: bool, // show the main file's console output in a window
+    main_file_last: bool, // extract the main file after its dependencies
     run_as_admin: bool,
+    admin_title: String, // shown by the stub when admin rights are missing, empty = default
+    admin_message: String,
+    eula_title: String, // title of the first-run license dialog, empty = stub default
+    eula_text: String, // license shown on first run, empty = none
+    stub_messages: StubMessages, // custom/translated error dialogs of the packed EXE
+    clean_before_extract: bool, // delete the extraction folder before extracting (clean install)
+    overwrite_policy: String, // "ask", "refuse" or "allow" for an extraction folder with files the stub didn't create
+    update_mode: String, // "always", "newer" or "skip" for each file already on disk
+    launch_delay_ms: u32, // delay between extraction and launching the main file
+    allow_uninstall: bool, // packed EXE supports --uninstall
+    single_instance: bool, // only one copy of the packed app runs at a time
+    wait_for_exit: bool, // packed EXE waits for the main file and returns its exit code
+    shortcut_desktop: bool, // create a Desktop shortcut on first run
+    shortcut_start_menu: bool, // create a Start Menu shortcut on first run
+    shortcut_name: String,
+    shortcut_args: String,
+    shortcut_icon: String,
+    install_service: bool, // install the main file as a Windows service instead of launching it
+    service_name: String,
+    service_display_name: String,
+    service_description: String,
+    service_start_type: String, // "auto", "manual" or "disabled"
+    service_args: String,
+    service_start_now: bool, // start the service right after installing it
     message: String,
     dark_mode: bool,
     selected_resource: Option<usize>, // track the selected resource
     compress_resources: bool, // option to compress resources
+    seekable_compression: bool, // compress in independent blocks the stub can inflate one at a time
+    cache_compression: bool, // reuse the last build's compressed data when nothing changed
+    sidecar_pack: bool, // write resources to a .rscpack file next to the EXE instead of appending
+    pe_resource_archive: bool, // store resources as a PE RCDATA resource (fewer antivirus false positives)
+    preserve_attributes: bool, // restore read-only/hidden/system attributes on extraction
+    verify_after_build: bool, // re-read and check the output after compiling
+    show_progress: bool, // show extraction progress in the packed EXE
+    target_arch: String, // one of "x64", "x86"; selects which stub to pack with
+    stub_path_x64: String, // stub binary used for 64-bit builds, empty = built-in stub
+    stub_path_x86: String, // stub binary used for 32-bit builds, empty = built-in stub
+    stub_console: bool, // pack the console build of the stub (stub_console.exe) for debugging
+    output_subsystem: String, // "stub" (keep the stub's), "console" or "windows"; patched into the output PE
+    compress_stub: bool, // run UPX over the stub part of the output
+    strip_stub_signature: bool, // remove a signed stub's certificate table instead of leaving it broken
+    sign_output: bool, // Authenticode-sign the finished EXE with signtool
+    sign_certificate: String, // .pfx file or SHA-1 thumbprint of a certificate in the user's store
+    sign_password: String, // .pfx password; never saved to the project
+    sign_timestamp_url: String, // RFC 3161 timestamp server, empty = no timestamp
+    log_level: String, // one of "off", "error", "info", "debug"
+    max_extract_size_mb: u64, // extraction size limit in MB, 0 = stub default
+    io_retries: u32, // how often the packed EXE retries creating folders and the first write
+    split_size_mb: u64, // split the output into volumes of this size, 0 = single file
+    resource_alignment: u32, // start each resource on a multiple of this many bytes, 0 = no padding
     show_settings: bool, // toggle for settings panel
     icon_path: Option<PathBuf>, // custom icon for the output executable
     search_query: String, // for resource searching
+    search_contents: bool, // also match the query against the text inside resources
+    content_search: Arc<ContentSearch>, // file texts and matches for search_contents
+    content_search_query: String, // lowercased query the last content scan matched
+    content_search_at: Option<std::time::Instant>, // when the last content scan started
+    glob_base: Option<PathBuf>, // base folder for the glob input
+    glob_input: String, // glob pattern being typed
+    resource_globs: Vec<ResourceGlob>, // patterns added to the project
+    resource_zips: Vec<ResourceZip>, // zip files whose contents are packed
+    ignore_patterns: String, // one per line, files/folders skipped when expanding globs
+    profiles: Vec<String>, // environment variants (dev, stage, prod, ...) the project can build
+    active_profile: String, // profile the next build packs, empty = shared resources only; see profile_variant
+    new_profile_name: String, // profile name being typed
+    preview: Option<(PathBuf, String)>, // cached preview of the selected resource
+    resource_types: HashMap<PathBuf, (&'static str, &'static str)>, // icon and type label per resource, see detect_file_type
+    compression_stats: Option<Vec<CompressionStat>>, // last per-extension compression analysis
+    compression_comparison: Option<CompressionComparison>, // last compressed vs uncompressed build comparison
+    comparison_job: Option<Arc<ComparisonJob>>, // comparison in progress, if any
+    output_layout: Option<Vec<LayoutRegion>>, // byte layout of the output EXE when last inspected
+    base_exe: Option<PathBuf>, // existing packed EXE that new resources are added to
+    embedded_resources: Vec<String>, // filenames already inside base_exe
+    compile_job: Option<Arc<CompileJob>>, // build in progress, if any
+    close_after_compile: bool, // exit the app once a build succeeds
+    stub_capabilities: Option<StubCapabilities>, // features of the current stub, see refresh_stub_capabilities
+    saved_project: Option<serde_json::Value>, // project as last saved/loaded, None = new and untouched
+    close_confirmed: bool, // closing was requested by Compile and Close, skip the unsaved changes prompt
+    project_path: Option<PathBuf>, // .rcproj file the project was last saved to or loaded from
+    relative_paths: bool, // save source paths relative to the .rcproj file so the project can move, see portable_project_json
+    compact_project: bool, // write the .rcproj as single-line JSON instead of indented
+    sort_resources: bool, // sort the resource list by path when saving, for minimal version control diffs
+    extraction_path_template: String, // app setting: extraction path of new projects, see resolve_extraction_template
 }
 
 impl Default for AppState {
@@ -49,591 +308,4559 @@ impl Default for AppState {
         Self {
             extraction_path: "rc_extracted".to_string(),
             main_file: String::new(),
+            main_is_external: false,
             resources: Vec::new(),
             output_exe: "packed.exe".to_string(),
             execution_style: "normal".to_string(),
+            launch_verb: "open".to_string(),
+            launch_args: String::new(),
+            dependencies: Vec::new(),
+            post_extract_command: String::new(),
+            post_extract_style: "no-window".to_string(),
+            post_extract_abort_on_failure: true,
+            working_dir: String::new(),
+            package_version: String::new(),
+            stamp_build_time: false,
+            capture_output: false,
+            main_file_last: false,
             run_as_admin: false,
+            admin_title: String::new(),
+            admin_message: String::new(),
+            eula_title: String::new(),
+            eula_text: String::new(),
+            stub_messages: StubMessages::default(),
+            clean_before_extract: false,
+            overwrite_policy: "ask".to_string(),
+            update_mode: "always".to_string(),
+            launch_delay_ms: 0,
+            allow_uninstall: false,
+            single_instance: false,
+            wait_for_exit: false,
+            shortcut_desktop: false,
+            shortcut_start_menu: false,
+            shortcut_name: String::new(),
+            shortcut_args: String::new(),
+            shortcut_icon: String::new(),
+            install_service: false,
+            service_name: String::new(),
+            service_display_name: String::new(),
+            service_description: String::new(),
+            service_start_type: "auto".to_string(),
+            service_args: String::new(),
+            service_start_now: true,
             message: String::new(),
             dark_mode: true, // default to dark mode
             selected_resource: None,
             compress_resources: false,
+            seekable_compression: false,
+            cache_compression: true,
+            sidecar_pack: false,
+            pe_resource_archive: false,
+            preserve_attributes: false,
+            verify_after_build: true,
+            show_progress: false,
+            target_arch: "x64".to_string(),
+            stub_path_x64: String::new(),
+            stub_path_x86: String::new(),
+            stub_console: false,
+            output_subsystem: "stub".to_string(),
+            compress_stub: false,
+            strip_stub_signature: true,
+            sign_output: false,
+            sign_certificate: String::new(),
+            sign_password: String::new(),
+            sign_timestamp_url: DEFAULT_TIMESTAMP_URL.to_string(),
+            log_level: "off".to_string(),
+            max_extract_size_mb: 0,
+            io_retries: 3,
+            split_size_mb: 0,
+            resource_alignment: 0,
             show_settings: false,
             icon_path: None,
             search_query: String::new(),
+            search_contents: false,
+            content_search: Arc::default(),
+            content_search_query: String::new(),
+            content_search_at: None,
+            glob_base: None,
+            glob_input: String::new(),
+            resource_globs: Vec::new(),
+            resource_zips: Vec::new(),
+            ignore_patterns: String::new(),
+            profiles: Vec::new(),
+            active_profile: String::new(),
+            new_profile_name: String::new(),
+            preview: None,
+            resource_types: HashMap::new(),
+            compression_stats: None,
+            compression_comparison: None,
+            comparison_job: None,
+            output_layout: None,
+            base_exe: None,
+            embedded_resources: Vec::new(),
+            compile_job: None,
+            close_after_compile: false,
+            stub_capabilities: None,
+            saved_project: None,
+            close_confirmed: false,
+            project_path: None,
+            relative_paths: true,
+            compact_project: false,
+            sort_resources: false,
+            extraction_path_template: String::new(),
         }
     }
 }
 
-impl eframe::App for AppState {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        // set the theme based on dark_mode
-        if self.dark_mode {
-            ctx.set_visuals(egui::Visuals::dark());
+impl AppState {
+    // Run compile_exe on a background thread against a snapshot of the current
+    // settings, so the UI stays responsive and the build can be cancelled.
+    // `close_when_done` exits the app once this build succeeds.
+    fn start_compile(&mut self, ctx: &egui::Context, close_when_done: bool) {
+        if self.compile_job.is_some() {
+            return;
+        }
+        let job = Arc::new(CompileJob {
+            cancel: AtomicBool::new(false),
+            result: Mutex::new(None),
+            close_when_done,
+        });
+        let mut snapshot = self.clone();
+        snapshot.compile_job = None;
+        snapshot.comparison_job = None;
+        let thread_job = Arc::clone(&job);
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let result = compile_exe(&snapshot, &thread_job.cancel);
+            *thread_job.result.lock().unwrap() = Some(result);
+            ctx.request_repaint();
+        });
+        self.compile_job = Some(job);
+        self.message = "Compiling...".to_string();
+    }
+
+    // Run compare_compression on a background thread, like start_compile
+    fn start_comparison(&mut self, ctx: &egui::Context) {
+        if self.comparison_job.is_some() {
+            return;
+        }
+        let job = Arc::new(ComparisonJob {
+            cancel: AtomicBool::new(false),
+            result: Mutex::new(None),
+        });
+        let mut snapshot = self.clone();
+        snapshot.compile_job = None;
+        snapshot.comparison_job = None;
+        let thread_job = Arc::clone(&job);
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let result = compare_compression(&snapshot, &thread_job.cancel);
+            *thread_job.result.lock().unwrap() = Some(result);
+            ctx.request_repaint();
+        });
+        self.comparison_job = Some(job);
+    }
+
+    // Clear the current project
+    fn new_project(&mut self) {
+        self.resources.clear();
+        self.resource_globs.clear();
+        self.resource_zips.clear();
+        self.profiles.clear();
+        self.active_profile.clear();
+        self.base_exe = None;
+        self.embedded_resources.clear();
+        self.main_file.clear();
+        self.extraction_path = "rc_extracted".to_string();
+        self.output_exe = "packed.exe".to_string();
+        self.project_path = None;
+        if !self.extraction_path_template.trim().is_empty() {
+            self.extraction_path = self.resolve_extraction_template();
+        }
+        self.message = "Started new project".to_string();
+        self.saved_project = Some(self.project_json());
+    }
+
+    // The default extraction path template with its placeholders filled in:
+    // {projectname} (project file name, or the output EXE name before the first
+    // save), {exename} (output EXE file name) and {date} (YYYY-MM-DD)
+    fn resolve_extraction_template(&self) -> String {
+        let output = Path::new(&self.output_exe);
+        let exe_name = output.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let project_name = self.project_path.as_deref()
+            .and_then(Path::file_stem)
+            .or_else(|| output.file_stem())
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        self.extraction_path_template.trim()
+            .replace("{projectname}", &project_name)
+            .replace("{exename}", &exe_name)
+            .replace("{date}", &chrono::Local::now().format("%Y-%m-%d").to_string())
+    }
+
+    // Settings that belong to the user rather than a project, kept in
+    // settings.json in the user's config folder
+    fn load_app_settings(&mut self) {
+        let Some(settings) = app_settings_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
+        else {
+            return;
+        };
+        self.extraction_path_template = settings["extraction_path_template"].as_str().unwrap_or("").to_string();
+    }
+
+    fn save_app_settings(&self) -> Result<(), String> {
+        let path = app_settings_path().ok_or("No config folder to store settings in")?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+        }
+        let settings = serde_json::json!({
+            "extraction_path_template": self.extraction_path_template,
+        });
+        let json = serde_json::to_string_pretty(&settings).map_err(|e| format!("Failed to serialize settings: {}", e))?;
+        fs::write(&path, json).map_err(|e| format!("Failed to save settings to {:?}: {}", path, e))
+    }
+
+    // Use an existing packed EXE as the starting point: its stub and embedded
+    // resources are kept and newly added resources are appended on compile.
+    fn load_packed_exe(&mut self, path: PathBuf) -> Result<String, String> {
+        let bytes = read_packed_file(&path)?;
+        let parsed = parse_archive(&bytes)
+            .map_err(|e| format!("{:?} does not contain an appended archive: {}", path, e))?;
+        let resources = self.apply_header_settings(parsed.header);
+        self.embedded_resources = resources.into_iter().map(|r| r.filename).collect();
+        self.resources.clear();
+        self.selected_resource = None;
+
+        let msg = format!(
+            "Loaded {} embedded resource(s) from {}. Added resources will be appended.",
+            self.embedded_resources.len(),
+            path.to_string_lossy()
+        );
+        self.base_exe = Some(path);
+        Ok(msg)
+    }
+
+    // Recover an editable project from a packed EXE, e.g. after losing the
+    // .rcproj: its resources are extracted into `folder` and become the
+    // project's resources, and its settings are restored from the header.
+    // The custom icon can't be recovered.
+    fn import_packed_exe(&mut self, path: &Path, folder: &Path) -> Result<String, String> {
+        let bytes = read_packed_file(path)?;
+        let (header, contents) = unpack_archive(&bytes, u64::MAX)
+            .map_err(|e| format!("{:?} does not contain an appended archive: {}", path, e))?;
+        let packed_with_entry_compression = header.resources.iter().any(|r| r.stored_size.is_some());
+
+        let mut imported = Vec::new();
+        for (entry, data) in header.resources.iter().zip(&contents) {
+            let target = sandboxed_path(folder, &entry.filename)?;
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent).map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+            }
+            fs::write(&target, data).map_err(|e| format!("Failed to write {:?}: {}", target, e))?;
+            if let Some(modified) = entry.modified {
+                // Keeps "Only if bundled file is newer" updates working after a rebuild
+                let _ = fs::File::options().write(true).open(&target)
+                    .and_then(|file| file.set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_millis(modified)));
+            }
+            imported.push((target, entry.filename.clone(), entry.stored_size.is_some()));
+        }
+
+        self.new_project();
+        self.apply_header_settings(header);
+        for (target, filename, compressed) in imported {
+            let mut item = ResourceItem::new(target);
+            // Resources in subfolders keep their place through a target folder
+            if let Some((subdir, _)) = filename.rsplit_once('/') {
+                item.subdir = subdir.to_string();
+            }
+            if packed_with_entry_compression && compressed {
+                item.compression = "compress".to_string();
+            }
+            if !self.has_resource(&item.path) {
+                self.resources.push(item);
+            }
+        }
+        self.output_exe = path.file_name().map_or_else(|| "packed.exe".to_string(), |name| name.to_string_lossy().to_string());
+
+        Ok(format!(
+            "Imported {} resource(s) from {} into {}. Save the project to keep it; the icon has to be set again.",
+            self.resources.len(),
+            path.display(),
+            folder.display()
+        ))
+    }
+
+    // Take over every setting stored in a packed EXE's header, returning its
+    // resource entries for the caller to use
+    fn apply_header_settings(&mut self, header: ArchiveHeader) -> Vec<ResourceEntry> {
+        self.extraction_path = header.extraction_path;
+        self.main_file = header.main_file;
+        self.main_is_external = header.main_is_external;
+        self.execution_style = header.execution_style;
+        self.package_version = header.package_version;
+        self.stamp_build_time = !header.build_time.is_empty();
+        self.capture_output = header.capture_output;
+        self.main_file_last = header.main_file_last;
+        self.launch_verb = if header.launch_verb.is_empty() { "open".to_string() } else { header.launch_verb };
+        self.launch_args = header.launch_args;
+        self.dependencies = header.dependencies;
+        self.post_extract_command = header.post_extract_command;
+        self.post_extract_style = if header.post_extract_style.is_empty() { "no-window".to_string() } else { header.post_extract_style };
+        self.post_extract_abort_on_failure = header.post_extract_abort_on_failure;
+        self.working_dir = header.working_dir;
+        self.run_as_admin = header.run_as_admin;
+        self.admin_title = header.admin_title;
+        self.admin_message = header.admin_message;
+        self.eula_title = header.eula_title;
+        self.eula_text = header.eula_text;
+        self.stub_messages = header.messages;
+        self.compress_resources = header.is_compressed;
+        self.seekable_compression = !header.compressed_blocks.is_empty();
+        self.show_progress = header.show_progress;
+        if !header.target_arch.is_empty() {
+            self.target_arch = header.target_arch;
+        }
+        if !header.log_level.is_empty() {
+            self.log_level = header.log_level;
+        }
+        self.max_extract_size_mb = (header.max_extract_size / (1024 * 1024)).min(MAX_EXTRACT_SIZE_MB);
+        self.io_retries = header.io_retries;
+        self.clean_before_extract = header.clean_before_extract;
+        // Archives from before the setting always overwrote
+        self.overwrite_policy = if header.overwrite_policy.is_empty() { "allow".to_string() } else { header.overwrite_policy };
+        self.update_mode = if header.update_mode.is_empty() { "always".to_string() } else { header.update_mode };
+        self.launch_delay_ms = header.launch_delay_ms;
+        self.allow_uninstall = header.allow_uninstall;
+        self.single_instance = header.single_instance;
+        self.wait_for_exit = header.wait_for_exit;
+        let shortcut = header.shortcut.unwrap_or_default();
+        self.shortcut_desktop = shortcut.desktop;
+        self.shortcut_start_menu = shortcut.start_menu;
+        self.shortcut_name = shortcut.name;
+        self.shortcut_args = shortcut.arguments;
+        self.shortcut_icon = shortcut.icon;
+        self.install_service = header.service.is_some();
+        let service = header.service.unwrap_or_default();
+        self.service_name = service.name;
+        self.service_display_name = service.display_name;
+        self.service_description = service.description;
+        self.service_start_type = if service.start_type.is_empty() { "auto".to_string() } else { service.start_type };
+        self.service_args = service.arguments;
+        self.service_start_now = service.start_now;
+        header.resources
+    }
+
+    // Project settings as saved to a .rcproj file
+    fn project_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "extraction_path": self.extraction_path,
+            "main_file": self.main_file,
+            "main_is_external": self.main_is_external,
+            "resources": self.resources,
+            "output_exe": self.output_exe,
+            "execution_style": self.execution_style,
+            "launch_verb": self.launch_verb,
+            "launch_args": self.launch_args,
+            "dependencies": self.dependencies,
+            "post_extract_command": self.post_extract_command,
+            "post_extract_style": self.post_extract_style,
+            "post_extract_abort_on_failure": self.post_extract_abort_on_failure,
+            "working_dir": self.working_dir,
+            "package_version": self.package_version,
+            "stamp_build_time": self.stamp_build_time,
+            "capture_output": self.capture_output,
+            "main_file_last": self.main_file_last,
+            "run_as_admin": self.run_as_admin,
+            "admin_title": self.admin_title,
+            "admin_message": self.admin_message,
+            "eula_title": self.eula_title,
+            "eula_text": self.eula_text,
+            "stub_messages": self.stub_messages,
+            "clean_before_extract": self.clean_before_extract,
+            "overwrite_policy": self.overwrite_policy,
+            "update_mode": self.update_mode,
+            "launch_delay_ms": self.launch_delay_ms,
+            "allow_uninstall": self.allow_uninstall,
+            "single_instance": self.single_instance,
+            "wait_for_exit": self.wait_for_exit,
+            "shortcut_desktop": self.shortcut_desktop,
+            "shortcut_start_menu": self.shortcut_start_menu,
+            "shortcut_name": self.shortcut_name,
+            "shortcut_args": self.shortcut_args,
+            "shortcut_icon": self.shortcut_icon,
+            "install_service": self.install_service,
+            "service_name": self.service_name,
+            "service_display_name": self.service_display_name,
+            "service_description": self.service_description,
+            "service_start_type": self.service_start_type,
+            "service_args": self.service_args,
+            "service_start_now": self.service_start_now,
+            "compress_resources": self.compress_resources,
+            "seekable_compression": self.seekable_compression,
+            "cache_compression": self.cache_compression,
+            "sidecar_pack": self.sidecar_pack,
+            "pe_resource_archive": self.pe_resource_archive,
+            "preserve_attributes": self.preserve_attributes,
+            "verify_after_build": self.verify_after_build,
+            "close_after_compile": self.close_after_compile,
+            "show_progress": self.show_progress,
+            "target_arch": self.target_arch,
+            "stub_path_x64": self.stub_path_x64,
+            "stub_path_x86": self.stub_path_x86,
+            "stub_console": self.stub_console,
+            "output_subsystem": self.output_subsystem,
+            "compress_stub": self.compress_stub,
+            "strip_stub_signature": self.strip_stub_signature,
+            "sign_output": self.sign_output,
+            "sign_certificate": self.sign_certificate,
+            "sign_timestamp_url": self.sign_timestamp_url,
+            "log_level": self.log_level,
+            "max_extract_size_mb": self.max_extract_size_mb,
+            "io_retries": self.io_retries,
+            "split_size_mb": self.split_size_mb,
+            "resource_alignment": self.resource_alignment,
+            "icon_path": self.icon_path.as_ref().map(|p| p.to_string_lossy().to_string()),
+            "base_exe": self.base_exe.as_ref().map(|p| p.to_string_lossy().to_string()),
+            "resource_globs": self.resource_globs,
+            "resource_zips": self.resource_zips.iter().map(|z| z.path.to_string_lossy().to_string()).collect::<Vec<_>>(),
+            "ignore_patterns": self.ignore_patterns.lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .collect::<Vec<_>>(),
+            "profiles": self.profiles,
+            "active_profile": self.active_profile,
+            "relative_paths": self.relative_paths,
+            "compact_project": self.compact_project,
+            "sort_resources": self.sort_resources,
+        })
+    }
+
+    // The project as written to `project_file`: with relative_paths set, source
+    // paths (resources, globs, zips, icon, base EXE) are stored relative to the
+    // project file's folder where they share a drive, and load_project resolves
+    // them again. Everything else matches project_json.
+    fn portable_project_json(&self, project_file: &Path) -> serde_json::Value {
+        let mut project = self.project_json();
+        let project_dir = std::path::absolute(project_file).ok().and_then(|p| p.parent().map(Path::to_path_buf));
+        let Some(project_dir) = project_dir.filter(|_| self.relative_paths) else {
+            return project;
+        };
+        let relative = |stored: &str| relative_path(Path::new(stored), &project_dir)
+            .map_or_else(|| stored.to_string(), |p| p.to_string_lossy().to_string());
+        let relative_value = |value: &mut serde_json::Value| {
+            if let Some(stored) = value.as_str() {
+                *value = serde_json::Value::String(relative(stored));
+            }
+        };
+        if let Some(resources) = project["resources"].as_array_mut() {
+            resources.iter_mut().for_each(|resource| relative_value(&mut resource["path"]));
+        }
+        if let Some(paths) = project["resource_zips"].as_array_mut() {
+            paths.iter_mut().for_each(relative_value);
+        }
+        if let Some(globs) = project["resource_globs"].as_array_mut() {
+            globs.iter_mut().for_each(|resource_glob| relative_value(&mut resource_glob["base"]));
+        }
+        relative_value(&mut project["icon_path"]);
+        relative_value(&mut project["base_exe"]);
+        project
+    }
+
+    // Whether the project differs from when it was last saved, loaded or started
+    fn has_unsaved_changes(&self) -> bool {
+        match &self.saved_project {
+            Some(saved) => *saved != self.project_json(),
+            None => self.project_json() != AppState::default().project_json(),
+        }
+    }
+
+    // Ask before an action that throws away unsaved changes; true to go ahead
+    fn confirm_discard_changes(&self, action: &str) -> bool {
+        !self.has_unsaved_changes() || rfd::MessageDialog::new()
+            .set_title("Unsaved Changes")
+            .set_description(format!("The current project has unsaved changes. Discard them and {}?", action))
+            .set_buttons(rfd::MessageButtons::YesNo)
+            .show() == rfd::MessageDialogResult::Yes
+    }
+
+    // `--build` invocation that packs the same thing as Compile EXE, for scripts
+    // and CI. Only common options have flags, so a saved project is passed with
+    // --project to carry the rest; the flags after it match the current state.
+    fn cli_command(&self) -> String {
+        let program = std::env::current_exe().ok()
+            .and_then(|exe| exe.file_name().map(|name| name.to_string_lossy().to_string()))
+            .unwrap_or_else(|| "main_gui.exe".to_string());
+        let mut args = vec![program, "--build".to_string()];
+        if let Some(project_path) = &self.project_path {
+            args.extend(["--project".to_string(), project_path.to_string_lossy().to_string()]);
+        }
+        args.extend(["--output".to_string(), self.output_exe.clone()]);
+        args.extend(["--extract-to".to_string(), self.extraction_path.clone()]);
+        if !self.main_file.is_empty() {
+            args.extend(["--main".to_string(), self.main_file.clone()]);
+        }
+        if !self.active_profile.is_empty() {
+            args.extend(["--profile".to_string(), self.active_profile.clone()]);
+        }
+        args.extend(["--style".to_string(), self.execution_style.clone()]);
+        if self.compress_resources {
+            args.push(if self.seekable_compression { "--seekable" } else { "--compress" }.to_string());
+        }
+        if self.run_as_admin {
+            args.push("--admin".to_string());
+        }
+        if self.launch_verb == "runas" {
+            args.push("--launch-admin".to_string());
+        }
+        if let Some(icon_path) = &self.icon_path {
+            args.extend(["--icon".to_string(), icon_path.to_string_lossy().to_string()]);
+        }
+        // The .pfx password is left out, it comes from RC_SIGN_PASSWORD or --sign-password-stdin
+        if self.sign_output {
+            args.extend(["--sign".to_string(), self.sign_certificate.clone()]);
+            if self.sign_timestamp_url != DEFAULT_TIMESTAMP_URL {
+                args.extend(["--timestamp-url".to_string(), self.sign_timestamp_url.clone()]);
+            }
+        }
+        if self.target_arch != "x64" {
+            args.extend(["--arch".to_string(), self.target_arch.clone()]);
+        }
+        args.extend(self.resources.iter().map(|r| r.path.to_string_lossy().to_string()));
+        args.iter().map(|arg| quote_arg(arg)).collect::<Vec<_>>().join(" ")
+    }
+
+    // Write the project to a .rcproj file and make it the one future saves go to.
+    fn save_project(&mut self, path: &Path) -> Result<(), String> {
+        self.write_project_file(path)?;
+        self.project_path = Some(path.to_path_buf());
+        self.saved_project = Some(self.project_json());
+        Ok(())
+    }
+
+    // Write the project to a .rcproj file without switching to it, e.g. to start a
+    // variant. The open project keeps its path and unsaved-changes state.
+    fn save_project_copy(&mut self, path: &Path) -> Result<(), String> {
+        self.write_project_file(path)
+    }
+
+    // Save to the current .rcproj, or ask where to save if there is none yet
+    fn save_project_or_ask(&mut self) {
+        match self.project_path.clone() {
+            Some(path) => match self.save_project(&path) {
+                Ok(()) => self.message = format!("Project saved to {}", path.display()),
+                Err(e) => self.message = format!("❌ {}", e),
+            },
+            None => self.save_project_dialog(false),
+        }
+    }
+
+    // Ask for a .rcproj path, then save there (Save As) or write a copy
+    fn save_project_dialog(&mut self, copy: bool) {
+        let mut dialog = rfd::FileDialog::new().add_filter("Resource Compiler Project", &["rcproj"]);
+        if let Some(dir) = self.project_path.as_deref().and_then(Path::parent) {
+            dialog = dialog.set_directory(dir);
+        }
+        let Some(path) = dialog.save_file() else {
+            return;
+        };
+        self.message = if copy {
+            match self.save_project_copy(&path) {
+                Ok(()) => format!("Saved a copy to {}", path.display()),
+                Err(e) => format!("❌ {}", e),
+            }
         } else {
-            ctx.set_visuals(egui::Visuals::light());
+            match self.save_project(&path) {
+                Ok(()) => "Project saved successfully".to_string(),
+                Err(e) => format!("❌ {}", e),
+            }
+        };
+    }
+
+    // Object keys are always sorted (serde_json maps are ordered), so with
+    // sort_resources the file only changes where the project does.
+    fn write_project_file(&mut self, path: &Path) -> Result<(), String> {
+        if self.sort_resources {
+            // The list order is the extraction order, so this reorders extraction too
+            self.resources.sort_by_key(|r| r.path.to_string_lossy().to_lowercase());
+            self.resource_zips.sort_by_key(|z| z.path.to_string_lossy().to_lowercase());
         }
-        
-        // egui drag and drop
-        if !ctx.input(|i| i.raw.dropped_files.clone()).is_empty() {
-            for file in &ctx.input(|i| i.raw.dropped_files.clone()) {
-                if let Some(path) = &file.path {
-                    if !self.resources.contains(path) {
-                        self.resources.push(path.clone());
+        let portable = self.portable_project_json(path);
+        let mut json = if self.compact_project {
+            serde_json::to_string(&portable)
+        } else {
+            serde_json::to_string_pretty(&portable)
+        }.map_err(|e| format!("Failed to serialize project: {}", e))?;
+        json.push('\n');
+        fs::write(path, json).map_err(|e| format!("Failed to save project {:?}: {}", path, e))
+    }
+
+    // Load a saved .rcproj file into the current state. Returns what couldn't be
+    // restored (e.g. a packed EXE that is gone), the rest of the project still loads.
+    fn load_project(&mut self, path: &Path) -> Result<Vec<String>, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read project {:?}: {}", path, e))?;
+        let project = serde_json::from_str::<serde_json::Value>(&content)
+            .map_err(|e| format!("Invalid project file {:?}: {}", path, e))?;
+        // Relative source paths (see portable_project_json) start at the project's folder
+        let project_dir = std::path::absolute(path).ok()
+            .and_then(|p| p.parent().map(Path::to_path_buf))
+            .unwrap_or_default();
+        let resolve = |stored: &str| resolve_project_path(&project_dir, stored);
+        let mut problems = Vec::new();
+
+        // Load project data
+        self.extraction_path = project["extraction_path"].as_str().unwrap_or("rc_extracted").to_string();
+        self.main_file = project["main_file"].as_str().unwrap_or("").to_string();
+        self.main_is_external = project["main_is_external"].as_bool().unwrap_or(false);
+        self.output_exe = project["output_exe"].as_str().unwrap_or("packed.exe").to_string();
+        self.execution_style = project["execution_style"].as_str().unwrap_or("normal").to_string();
+        self.launch_verb = project["launch_verb"].as_str().unwrap_or("open").to_string();
+        self.launch_args = project["launch_args"].as_str().unwrap_or("").to_string();
+        self.dependencies = serde_json::from_value(project["dependencies"].clone())
+            .unwrap_or_default();
+        self.post_extract_command = project["post_extract_command"].as_str().unwrap_or("").to_string();
+        self.post_extract_style = project["post_extract_style"].as_str().unwrap_or("no-window").to_string();
+        self.post_extract_abort_on_failure = project["post_extract_abort_on_failure"].as_bool().unwrap_or(true);
+        self.working_dir = project["working_dir"].as_str().unwrap_or("").to_string();
+        self.package_version = project["package_version"].as_str().unwrap_or("").to_string();
+        self.stamp_build_time = project["stamp_build_time"].as_bool().unwrap_or(false);
+        self.capture_output = project["capture_output"].as_bool().unwrap_or(false);
+        self.main_file_last = project["main_file_last"].as_bool().unwrap_or(false);
+        self.run_as_admin = project["run_as_admin"].as_bool().unwrap_or(false);
+        self.admin_title = project["admin_title"].as_str().unwrap_or("").to_string();
+        self.admin_message = project["admin_message"].as_str().unwrap_or("").to_string();
+        self.eula_title = project["eula_title"].as_str().unwrap_or("").to_string();
+        self.eula_text = project["eula_text"].as_str().unwrap_or("").to_string();
+        self.stub_messages = serde_json::from_value(project["stub_messages"].clone())
+            .unwrap_or_default();
+        self.clean_before_extract = project["clean_before_extract"].as_bool().unwrap_or(false);
+        self.overwrite_policy = project["overwrite_policy"].as_str().unwrap_or("ask").to_string();
+        self.update_mode = project["update_mode"].as_str().unwrap_or("always").to_string();
+        self.launch_delay_ms = project["launch_delay_ms"].as_u64().unwrap_or(0) as u32;
+        self.allow_uninstall = project["allow_uninstall"].as_bool().unwrap_or(false);
+        self.single_instance = project["single_instance"].as_bool().unwrap_or(false);
+        self.wait_for_exit = project["wait_for_exit"].as_bool().unwrap_or(false);
+        self.shortcut_desktop = project["shortcut_desktop"].as_bool().unwrap_or(false);
+        self.shortcut_start_menu = project["shortcut_start_menu"].as_bool().unwrap_or(false);
+        self.shortcut_name = project["shortcut_name"].as_str().unwrap_or("").to_string();
+        self.shortcut_args = project["shortcut_args"].as_str().unwrap_or("").to_string();
+        self.shortcut_icon = project["shortcut_icon"].as_str().unwrap_or("").to_string();
+        self.install_service = project["install_service"].as_bool().unwrap_or(false);
+        self.service_name = project["service_name"].as_str().unwrap_or("").to_string();
+        self.service_display_name = project["service_display_name"].as_str().unwrap_or("").to_string();
+        self.service_description = project["service_description"].as_str().unwrap_or("").to_string();
+        self.service_start_type = project["service_start_type"].as_str().unwrap_or("auto").to_string();
+        self.service_args = project["service_args"].as_str().unwrap_or("").to_string();
+        self.service_start_now = project["service_start_now"].as_bool().unwrap_or(true);
+        self.compress_resources = project["compress_resources"].as_bool().unwrap_or(false);
+        self.seekable_compression = project["seekable_compression"].as_bool().unwrap_or(false);
+        self.cache_compression = project["cache_compression"].as_bool().unwrap_or(true);
+        self.sidecar_pack = project["sidecar_pack"].as_bool().unwrap_or(false);
+        self.pe_resource_archive = project["pe_resource_archive"].as_bool().unwrap_or(false);
+        self.preserve_attributes = project["preserve_attributes"].as_bool().unwrap_or(false);
+        self.verify_after_build = project["verify_after_build"].as_bool().unwrap_or(true);
+        self.close_after_compile = project["close_after_compile"].as_bool().unwrap_or(false);
+        self.show_progress = project["show_progress"].as_bool().unwrap_or(false);
+        self.target_arch = project["target_arch"].as_str().unwrap_or("x64").to_string();
+        self.stub_path_x64 = project["stub_path_x64"].as_str().unwrap_or("").to_string();
+        self.stub_path_x86 = project["stub_path_x86"].as_str().unwrap_or("").to_string();
+        self.stub_console = project["stub_console"].as_bool().unwrap_or(false);
+        self.output_subsystem = project["output_subsystem"].as_str().unwrap_or("stub").to_string();
+        self.compress_stub = project["compress_stub"].as_bool().unwrap_or(false);
+        self.strip_stub_signature = project["strip_stub_signature"].as_bool().unwrap_or(true);
+        self.sign_output = project["sign_output"].as_bool().unwrap_or(false);
+        self.sign_certificate = project["sign_certificate"].as_str().unwrap_or("").to_string();
+        self.sign_timestamp_url = project["sign_timestamp_url"].as_str().unwrap_or(DEFAULT_TIMESTAMP_URL).to_string();
+        self.log_level = project["log_level"].as_str().unwrap_or("off").to_string();
+        self.max_extract_size_mb = project["max_extract_size_mb"].as_u64().unwrap_or(0).min(MAX_EXTRACT_SIZE_MB);
+        self.io_retries = project["io_retries"].as_u64().unwrap_or(3) as u32;
+        self.split_size_mb = project["split_size_mb"].as_u64().unwrap_or(0).min(MAX_SPLIT_SIZE_MB);
+        self.resource_alignment = project["resource_alignment"].as_u64().unwrap_or(0) as u32;
+        // Older projects only stored absolute paths, and are saved relative from now on
+        self.relative_paths = project["relative_paths"].as_bool().unwrap_or(true);
+        self.compact_project = project["compact_project"].as_bool().unwrap_or(false);
+        self.sort_resources = project["sort_resources"].as_bool().unwrap_or(false);
+
+        // Load resources. Older projects list plain paths and keep the settings
+        // in maps keyed by path, picked up further down.
+        self.resources.clear();
+        if let Some(resources) = project["resources"].as_array() {
+            for res in resources {
+                let item = match res.as_str() {
+                    Some(path_str) => Some(ResourceItem::new(PathBuf::from(path_str))),
+                    None => serde_json::from_value::<ResourceItem>(res.clone()).ok(),
+                };
+                if let Some(mut item) = item {
+                    item.path = resolve(&item.path.to_string_lossy());
+                    if item.path.exists() {
+                        self.resources.push(item);
                     }
                 }
             }
         }
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            // Top bar with title and menu
-            ui.horizontal(|ui| {
-                // Use a styled heading for the title instead of an image
-                ui.heading(egui::RichText::new("Resource Compiler")
-                    .size(28.0)
-                    .strong()
-                    .color(if self.dark_mode {
-                        egui::Color32::from_rgb(120, 80, 200)
-                    } else {
-                        egui::Color32::from_rgb(60, 40,100)
-                    })
-                );
-                
-                // Menu bar
-                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                    let theme_text = if self.dark_mode { "☀ Light Mode" } else { "🌙 Dark Mode" };
-                    if ui.button(theme_text).clicked() {
-                        self.dark_mode = !self.dark_mode;
-                    }
-                    
-                    if ui.button("⚙ Settings").clicked() {
-                        self.show_settings = !self.show_settings;
+        // Load glob patterns and pick up any files that match them now
+        self.ignore_patterns = project["ignore_patterns"].as_array()
+            .map(|patterns| patterns.iter()
+                .filter_map(|p| p.as_str())
+                .collect::<Vec<_>>()
+                .join("\n"))
+            .unwrap_or_default();
+        self.resource_globs = serde_json::from_value(project["resource_globs"].clone())
+            .unwrap_or_default();
+        for resource_glob in &mut self.resource_globs {
+            resource_glob.base = resolve(&resource_glob.base.to_string_lossy());
+        }
+        for resource_glob in &self.resource_globs {
+            if let Ok((matches, _)) = expand_glob(&resource_glob.base, &resource_glob.pattern, &self.ignore_patterns) {
+                for path in matches {
+                    if !self.has_resource(&path) {
+                        self.resources.push(ResourceItem::new(path));
                     }
-                    
-                    // File menu dropdown
-                    egui::menu::menu_button(ui, "📁 File", |ui| {
-                        if ui.button("New Project").clicked() {
-                            // Clear current project
-                            self.resources.clear();
-                            self.main_file.clear();
-                            self.extraction_path = "rc_extracted".to_string();
-                            self.output_exe = "packed.exe".to_string();
-                            self.message = "Started new project".to_string();
-                            ui.close_menu();
-                        }
-                        
-                        if ui.button("Save Project").clicked() {
-                            if let Some(path) = rfd::FileDialog::new()
-                                .add_filter("Resource Compiler Project", &["rcproj"])
-                                .save_file() {
-                                let project = serde_json::json!({
-                                    "extraction_path": self.extraction_path,
-                                    "main_file": self.main_file,
-                                    "resources": self.resources.iter().map(|p| p.to_string_lossy().to_string()).collect::<Vec<_>>(),
-                                    "output_exe": self.output_exe,
-                                    "execution_style": self.execution_style,
-                                    "run_as_admin": self.run_as_admin,
-                                    "compress_resources": self.compress_resources,
-                                    "icon_path": self.icon_path.as_ref().map(|p| p.to_string_lossy().to_string()),
-                                });
-                                
-                                if let Ok(json) = serde_json::to_string_pretty(&project) {
-                                    if fs::write(&path, json).is_ok() {
-                                        self.message = "Project saved successfully".to_string();
-                                    } else {
-                                        self.message = "❌ Failed to save project".to_string();
-                                    }
-                                }
-                            }
-                            ui.close_menu();
-                        }
-                        
-                        if ui.button("Load Project").clicked() {
-                            if let Some(path) = rfd::FileDialog::new()
-                                .add_filter("Resource Compiler Project", &["rcproj"])
-                                .pick_file() {
-                                if let Ok(content) = fs::read_to_string(&path) {
-                                    if let Ok(project) = serde_json::from_str::<serde_json::Value>(&content) {
-                                        // Load project data
-                                        self.extraction_path = project["extraction_path"].as_str().unwrap_or("rc_extracted").to_string();
-                                        self.main_file = project["main_file"].as_str().unwrap_or("").to_string();
-                                        self.output_exe = project["output_exe"].as_str().unwrap_or("packed.exe").to_string();
-                                        self.execution_style = project["execution_style"].as_str().unwrap_or("normal").to_string();
-                                        self.run_as_admin = project["run_as_admin"].as_bool().unwrap_or(false);
-                                        self.compress_resources = project["compress_resources"].as_bool().unwrap_or(false);
-                                        
-                                        // Load resources
-                                        self.resources.clear();
-                                        if let Some(resources) = project["resources"].as_array() {
-                                            for res in resources {
-                                                if let Some(path_str) = res.as_str() {
-                                                    let path = PathBuf::from(path_str);
-                                                    if path.exists() {
-                                                        self.resources.push(path);
-                                                    }
-                                                }
-                                            }
-                                        }
-                                        
-                                        // Load icon path
-                                        if let Some(icon_path) = project["icon_path"].as_str() {
-                                            let path = PathBuf::from(icon_path);
-                                            if path.exists() {
-                                                self.icon_path = Some(path);
-                                            } else {
-                                                self.icon_path = None;
-                                            }
-                                        }
-                                        
-                                        self.message = "Project loaded successfully".to_string();
-                                    }
-                                }
-                            }
-                            ui.close_menu();
-                        }
-                    });
-                });
-            });
+                }
+            }
+        }
 
-            ui.add_space(10.0);
-
-            // Container with rounded corners and padding for the main content
-            egui::Frame::default()
-                .fill(ui.style().visuals.faint_bg_color)
-                .rounding(10.0)
-                .inner_margin(10.0)
-                .show(ui, |ui| {
-                    ui.heading("Project Settings");
-                    ui.add_space(5.0);
-
-                    // --- Extraction Path (supports env variables) ---
-                    ui.horizontal(|ui| {
-                        ui.label("Extraction Path:");
-                        ui.text_edit_singleline(&mut self.extraction_path);
-                        ui.label(" (%USERPROFILE%\\MyApp | C:\\folder | cool_folder)");
-                    });
+        // Load zip files, listing their current contents
+        self.resource_zips.clear();
+        if let Some(zips) = project["resource_zips"].as_array() {
+            for zip_path in zips.iter().filter_map(|z| z.as_str()) {
+                match ResourceZip::open(resolve(zip_path)) {
+                    Ok(resource_zip) => self.resource_zips.push(resource_zip),
+                    Err(e) => problems.push(format!("Zip {} could not be reopened: {}", zip_path, e)),
+                }
+            }
+        }
+        
+        // Per-resource settings of older projects, keyed by path
+        for (map, field) in [
+            ("resource_tags", (|item| &mut item.tag) as fn(&mut ResourceItem) -> &mut String),
+            ("resource_subdirs", |item| &mut item.subdir),
+            ("resource_names", |item| &mut item.name),
+            ("resource_compression", |item| &mut item.compression),
+            ("resource_profiles", |item| &mut item.profile),
+        ] {
+            let Some(values) = project[map].as_object() else {
+                continue;
+            };
+            for (path_str, value) in values {
+                let path = resolve(path_str);
+                for item in self.resources.iter_mut().filter(|item| item.path == path) {
+                    *field(item) = value.as_str().unwrap_or("").to_string();
+                }
+            }
+        }
+
+        // Load profiles; resources of a profile the project doesn't define are shared
+        self.profiles = project["profiles"].as_array()
+            .map(|names| names.iter().filter_map(|name| name.as_str().map(String::from)).collect())
+            .unwrap_or_default();
+        for item in &mut self.resources {
+            if !matches!(item.compression.as_str(), "" | "store" | "compress") {
+                item.compression.clear();
+            }
+            if !self.profiles.contains(&item.profile) {
+                item.profile.clear();
+            }
+        }
+        self.active_profile = project["active_profile"].as_str()
+            .filter(|profile| self.profiles.iter().any(|p| p == profile))
+            .unwrap_or("")
+            .to_string();
+
+        // Reopen the packed EXE this project adds to, if any
+        self.base_exe = None;
+        self.embedded_resources.clear();
+        if let Some(base_exe) = project["base_exe"].as_str() {
+            let base_path = resolve(base_exe);
+            let reopened = fs::read(&base_path)
+                .map_err(|e| format!("Failed to read {:?}: {}", base_path, e))
+                .and_then(|bytes| parse_archive(&bytes));
+            match reopened {
+                Ok(parsed) => {
+                    self.embedded_resources = parsed.header.resources.into_iter().map(|r| r.filename).collect();
+                    self.base_exe = Some(base_path);
+                }
+                Err(e) => problems.push(format!("Packed EXE {} could not be reopened: {}", base_exe, e)),
+            }
+        }
 
-                    // --- Output EXE Name ---
-                    ui.horizontal(|ui| {
-                        ui.label("Output EXE Name:");
-                        ui.text_edit_singleline(&mut self.output_exe);
+        // Load icon path
+        if let Some(icon_path) = project["icon_path"].as_str() {
+            let path = resolve(icon_path);
+            if path.exists() {
+                self.icon_path = Some(path);
+            } else {
+                self.icon_path = None;
+            }
+        }
+
+        self.project_path = Some(path.to_path_buf());
+        self.saved_project = Some(self.project_json());
+        Ok(problems)
+    }
+
+    // Stub binary used for a fresh build: per architecture, console variant if selected.
+    // Without a configured path this is the file next to the compiler, which is
+    // only read when there is no built-in stub (see embedded_stub).
+    fn stub_path(&self) -> String {
+        let stub_path = match self.target_arch.as_str() {
+            "x86" if self.stub_path_x86.trim().is_empty() => "stub_x86.exe",
+            "x86" => &self.stub_path_x86,
+            _ if self.stub_path_x64.trim().is_empty() => "stub.exe",
+            _ => &self.stub_path_x64,
+        };
+        if self.stub_console {
+            console_stub_path(stub_path)
+        } else {
+            stub_path.to_string()
+        }
+    }
+
+    // The built-in stub for the selected architecture, unless a stub path is set
+    // or the console stub is wanted (only the regular stub is built in)
+    fn embedded_stub(&self) -> Option<&'static [u8]> {
+        let (configured, embedded) = match self.target_arch.as_str() {
+            "x86" => (&self.stub_path_x86, EMBEDDED_STUB_X86),
+            _ => (&self.stub_path_x64, EMBEDDED_STUB_X64),
+        };
+        (configured.trim().is_empty() && !self.stub_console && !embedded.is_empty()).then_some(embedded)
+    }
+
+    // Bytes of the stub a fresh build starts from
+    fn read_stub(&self) -> Result<Vec<u8>, String> {
+        if let Some(stub) = self.embedded_stub() {
+            return Ok(stub.to_vec());
+        }
+        let stub_path = self.stub_path();
+        fs::read(&stub_path).map_err(|e| format!("Failed to read {}: {}", stub_path, e))
+    }
+
+    // Whether `path` is in the resource list, so adding it again is skipped
+    fn has_resource(&self, path: &Path) -> bool {
+        self.resources.iter().any(|r| r.path == path)
+    }
+
+    // Whether the main file is one of the resources (added or already embedded)
+    fn main_file_in_resources(&self) -> bool {
+        self.resources.iter().any(|r| r.archive_filename().is_ok_and(|name| name == self.main_file))
+            || self.resource_zips.iter().any(|z| z.entries.contains(&self.main_file))
+            || self.embedded_resources.contains(&self.main_file)
+    }
+
+    // Stub features the current settings rely on: (capability, option name)
+    fn required_capabilities(&self) -> Vec<(&'static str, &'static str)> {
+        let mut required = Vec::new();
+        if self.compress_resources {
+            required.push(("compress", "compression"));
+            if self.seekable_compression {
+                required.push(("seekable_compression", "seekable compression"));
+            }
+        }
+        if self.main_file_last {
+            required.push(("main_file_last", "extract main file last"));
+        }
+        if self.capture_output {
+            required.push(("capture_output", "console output window"));
+        }
+        if self.launch_verb != "open" {
+            required.push(("launch_verb", "launch verb"));
+        }
+        if !self.dependencies.is_empty() {
+            required.push(("dependencies", "required dependencies"));
+        }
+        if !self.post_extract_command.trim().is_empty() {
+            required.push(("post_extract", "setup command after extraction"));
+        }
+        if self.extraction_path.contains("{reg:") {
+            required.push(("registry_path", "registry value in the extraction path"));
+        }
+        if !self.launch_args.trim().is_empty() || !self.working_dir.trim().is_empty() {
+            required.push(("launch_options", "launch arguments / working directory"));
+        }
+        if self.shortcut_desktop || self.shortcut_start_menu {
+            required.push(("shortcuts", "shortcuts"));
+        }
+        if self.install_service {
+            required.push(("service", "Windows service"));
+        }
+        if !self.eula_text.trim().is_empty() {
+            required.push(("eula", "license agreement"));
+        }
+        if self.single_instance {
+            required.push(("single_instance", "single instance"));
+        }
+        if self.wait_for_exit {
+            required.push(("wait_for_exit", "wait for main app to exit"));
+        }
+        if self.uses_entry_compression() {
+            required.push(("entry_compression", "per-resource compression"));
+        }
+        if self.overwrite_policy != "allow" {
+            required.push(("overwrite_policy", "existing files check"));
+        }
+        if self.update_mode != "always" {
+            required.push(("update_mode", "update only newer / keep existing files"));
+        }
+        if self.allow_uninstall {
+            required.push(("uninstall", "uninstaller"));
+        }
+        if self.split_size_mb > 0 {
+            required.push(("split_volumes", "split output"));
+        }
+        if self.pe_resource_archive {
+            required.push(("pe_resource", "PE resource storage"));
+        }
+        if self.sidecar_pack {
+            required.push(("sidecar", "separate .rscpack file"));
+        }
+        if self.main_is_external {
+            required.push(("external_main", "external command"));
+        }
+        if self.resources.iter().any(|r| !r.subdir.is_empty()) || !self.resource_zips.is_empty() {
+            required.push(("subfolders", "resources in subfolders"));
+        }
+        if self.show_progress {
+            required.push(("progress", "extraction progress"));
+        }
+        if self.log_level != "off" {
+            required.push(("log", "stub log"));
+        }
+        required
+    }
+
+    // Re-read the capabilities tag when the stub (or packed EXE) a build would use
+    // changes. Cheap when nothing changed, so it runs every frame.
+    fn refresh_stub_capabilities(&mut self) {
+        // The built-in stub never changes; an empty path with the epoch as its time stands for it
+        let embedded = self.embedded_stub().filter(|_| self.base_exe.is_none());
+        let (path, modified) = match embedded {
+            Some(_) => (PathBuf::new(), Some(std::time::UNIX_EPOCH)),
+            None => {
+                let path = self.base_exe.clone().unwrap_or_else(|| PathBuf::from(self.stub_path()));
+                let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+                (path, modified)
+            }
+        };
+        if self.stub_capabilities.as_ref().is_some_and(|c| c.path == path && c.modified == modified) {
+            return;
+        }
+        let bytes = match embedded {
+            Some(stub) => stub.to_vec(),
+            None => fs::read(&path).unwrap_or_default(),
+        };
+        let features = read_stub_capabilities(&bytes);
+        let signed = pe_signature_range(&bytes).is_some();
+        self.stub_capabilities = Some(StubCapabilities { path, modified, features, signed });
+    }
+
+    // Whether a signed stub stays valid in the output: only a sidecar build
+    // writes it out untouched, anything else appends to it or rewrites it
+    fn stub_signature_survives(&self) -> bool {
+        self.sidecar_pack && self.base_exe.is_none() && self.icon_path.is_none()
+            && self.output_subsystem == "stub" && !self.compress_stub
+    }
+
+    // Prerequisites for a build, each with whether it is currently met
+    fn readiness_checks(&self) -> Vec<(String, bool)> {
+        let mut checks = vec![(
+            "At least one resource added".to_string(),
+            !self.resources.is_empty() || !self.resource_zips.is_empty() || !self.embedded_resources.is_empty(),
+        )];
+        if self.main_is_external {
+            checks.push(("External command set".to_string(), !self.main_file.trim().is_empty()));
+        } else {
+            checks.push((
+                "Main file set and among the resources".to_string(),
+                !self.main_file.is_empty() && self.main_file_in_resources(),
+            ));
+        }
+        checks.push((
+            "All resources exist on disk".to_string(),
+            self.resources.iter().all(|r| r.path.exists()) && self.resource_zips.iter().all(|z| z.path.exists()),
+        ));
+        let output_name = std::path::Path::new(&self.output_exe).file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        checks.push((
+            "Output name is a valid .exe name".to_string(),
+            output_name.to_lowercase().ends_with(".exe")
+                && output_name.len() > 4
+                && !output_name.contains(['<', '>', ':', '"', '|', '?', '*']),
+        ));
+        match &self.base_exe {
+            Some(base) => checks.push((format!("Packed EXE {} found", base.to_string_lossy()), base.exists())),
+            // The stub is copied into every packed EXE, so its size is fixed overhead
+            None => match self.embedded_stub() {
+                Some(stub) => checks.push((format!("Using the built-in stub ({})", format_size(stub.len() as u64)), true)),
+                None => {
+                    let stub_path = self.stub_path();
+                    match fs::metadata(&stub_path) {
+                        Ok(metadata) => checks.push((format!("Stub {} found ({})", stub_path, format_size(metadata.len())), true)),
+                        Err(_) => checks.push((format!("Stub {} found", stub_path), false)),
+                    }
+                }
+            },
+        }
+        if let Some(capabilities) = self.stub_capabilities.as_ref().filter(|c| c.modified.is_some()) {
+            let required = self.required_capabilities();
+            match &capabilities.features {
+                Some(features) => {
+                    let unsupported: Vec<&str> = required.iter()
+                        .filter(|(feature, _)| !features.iter().any(|f| f == feature))
+                        .map(|(_, option)| *option)
+                        .collect();
+                    checks.push(if unsupported.is_empty() {
+                        ("Stub supports the selected options".to_string(), true)
+                    } else {
+                        (format!("Stub does not support: {}", unsupported.join(", ")), false)
                     });
+                }
+                // Untagged stubs predate everything but compression
+                None => {
+                    let unknown: Vec<&str> = required.iter()
+                        .filter(|(feature, _)| *feature != "compress")
+                        .map(|(_, option)| *option)
+                        .collect();
+                    if !unknown.is_empty() {
+                        checks.push((format!("Stub is too old to report its features and may ignore: {}", unknown.join(", ")), false));
+                    }
+                }
+            }
+        }
+        // Appending after the certificate table leaves Windows reporting a corrupt signature
+        if self.stub_capabilities.as_ref().is_some_and(|c| c.signed) && !self.stub_signature_survives() {
+            let stub = if self.base_exe.is_some() { "Packed EXE" } else { "Stub" };
+            checks.push(if self.strip_stub_signature || self.sign_output {
+                (format!("{} is signed; its signature is removed before packing", stub), true)
+            } else {
+                (format!("{} is signed and packing will invalidate its signature (turn on \"Remove the stub's signature\" in Settings)", stub), false)
+            });
+        }
+        if let Some(icon) = &self.icon_path {
+            checks.push((
+                "Custom icon is an existing .ico file".to_string(),
+                icon.exists() && icon.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("ico")),
+            ));
+        }
+        checks
+    }
+
+    // Add the resources listed in a CSV or JSON manifest. Relative sources are
+    // relative to the manifest's folder. Nothing is changed unless every listed
+    // file exists and every target is valid.
+    fn import_manifest(&mut self, path: &Path) -> Result<String, String> {
+        let entries = read_manifest(path)?;
+        let base = path.parent().unwrap_or(Path::new(""));
+
+        let mut missing = Vec::new();
+        let mut resolved = Vec::new();
+        for entry in entries {
+            let source = base.join(&entry.source);
+            if !source.is_file() {
+                missing.push(entry.source.to_string_lossy().to_string());
+                continue;
+            }
+            let target = entry.target.as_deref().map(split_manifest_target).transpose()?;
+            resolved.push((source, target, entry.compress, entry.main));
+        }
+        if !missing.is_empty() {
+            return Err(format!("Manifest lists missing file(s): {}", missing.join(", ")));
+        }
+
+        let count = resolved.len();
+        let mut wants_compression = false;
+        for (source, target, compress, main) in resolved {
+            let index = match self.resources.iter().position(|r| r.path == source) {
+                Some(index) => index,
+                None => {
+                    self.resources.push(ResourceItem::new(source.clone()));
+                    self.resources.len() - 1
+                }
+            };
+            let item = &mut self.resources[index];
+            if let Some((subdir, name)) = target {
+                item.subdir = subdir.unwrap_or_default();
+                if source.file_name().is_some_and(|n| n.to_string_lossy() == name) {
+                    item.name.clear();
+                } else {
+                    item.name = name;
+                }
+            }
+            if main {
+                self.main_file = item.archive_filename()?;
+                self.main_is_external = false;
+            }
+            wants_compression |= compress;
+        }
+
+        let mut message = format!("Imported {} resource(s) from {}", count, path.to_string_lossy());
+        // Compression applies to the whole archive, so any flagged resource turns it on
+        if wants_compression && !self.compress_resources {
+            self.compress_resources = true;
+            message.push_str(", compression enabled for the whole archive");
+        }
+        Ok(message)
+    }
+
+    // One-line description of what the packed EXE does once it has extracted
+    fn launch_summary(&self) -> String {
+        if self.main_file.trim().is_empty() {
+            return "Nothing is launched until a main file is set".to_string();
+        }
+        let mut target = if self.main_is_external {
+            format!("the command {}", self.main_file.trim())
+        } else {
+            format!("{} from the extraction folder", self.main_file.trim())
+        };
+        if !self.launch_args.trim().is_empty() {
+            target.push_str(&format!(" with arguments {}", self.launch_args.trim()));
+        }
+        if !self.working_dir.trim().is_empty() {
+            target.push_str(&format!(" in {}", self.working_dir.trim()));
+        }
+
+        let mut steps = Vec::new();
+        if self.run_as_admin {
+            steps.push("asks for administrator rights".to_string());
+        }
+        if !self.post_extract_command.trim().is_empty() {
+            steps.push(format!("runs {}", self.post_extract_command.trim()));
+        }
+        if self.launch_delay_ms > 0 {
+            steps.push(format!("waits {} ms", self.launch_delay_ms));
+        }
+        steps.push(if self.capture_output {
+            format!("runs {} and shows its console output in a window", target)
+        } else {
+            let verb = match self.launch_verb.as_str() {
+                "runas" => "runs elevated",
+                "print" => "prints",
+                "edit" => "opens for editing",
+                "explore" => "explores",
+                _ => "opens",
+            };
+            let window = match self.execution_style.as_str() {
+                "no-window" => "hidden",
+                "minimized" => "minimized",
+                "maximized" => "maximized",
+                _ => "normal window",
+            };
+            format!("{} {} ({})", verb, target, window)
+        });
+        if self.wait_for_exit {
+            steps.push("waits for it to exit and returns its exit code".to_string());
+        }
+        format!("On run the packed EXE {}", steps.join(", then "))
+    }
+
+    // Output file of a profile build: {profile} in the output name is replaced,
+    // otherwise the profile goes before the extension (app.exe -> app_prod.exe)
+    // so building each profile never overwrites another's EXE
+    fn profile_output(&self) -> String {
+        if self.output_exe.contains("{profile}") {
+            return self.output_exe.replace("{profile}", &self.active_profile);
+        }
+        let output = Path::new(&self.output_exe);
+        let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+        let name = match output.extension() {
+            Some(ext) => format!("{}_{}.{}", stem, self.active_profile, ext.to_string_lossy()),
+            None => format!("{}_{}", stem, self.active_profile),
+        };
+        output.with_file_name(name).to_string_lossy().to_string()
+    }
+
+    // The state a build packs: resources assigned to a profile other than the
+    // active one are left out, and a profile build gets its own output file.
+    // Without profiles this is the state as-is.
+    fn profile_variant(&self) -> Result<AppState, String> {
+        if !self.active_profile.is_empty() && !self.profiles.contains(&self.active_profile) {
+            return Err(format!("Unknown profile {:?}, the project defines: {}", self.active_profile, self.profiles.join(", ")));
+        }
+        let mut variant = self.clone();
+        if self.profiles.is_empty() {
+            return Ok(variant);
+        }
+        variant.resources.retain(|r| r.profile.is_empty() || r.profile == self.active_profile);
+        if !self.active_profile.is_empty() {
+            variant.output_exe = self.profile_output();
+        }
+        Ok(variant)
+    }
+
+    // A store/compress override on any resource switches the build to compressing
+    // each resource on its own instead of the data as a whole
+    fn uses_entry_compression(&self) -> bool {
+        self.resources.iter().any(|r| !r.compression.is_empty())
+    }
+
+    // How a resource is packed when uses_entry_compression: its override, or with
+    // compression on, whichever of stored and gzipped is smaller
+    fn entry_compression(&self, item: &ResourceItem) -> EntryCompression {
+        match item.compression.as_str() {
+            "store" => EntryCompression::Store,
+            "compress" => EntryCompression::Gzip,
+            _ if self.compress_resources => EntryCompression::Auto,
+            _ => EntryCompression::Store,
+        }
+    }
+
+    // Start a background content scan when the query changed, or every few
+    // seconds so edited files are picked up. Only one scan runs at a time; a
+    // query typed meanwhile is scanned once it finishes.
+    fn refresh_content_search(&mut self, ctx: &egui::Context) {
+        const RESCAN_INTERVAL: std::time::Duration = std::time::Duration::from_secs(3);
+        if !self.search_contents || self.search_query.is_empty() {
+            return;
+        }
+        ctx.request_repaint_after(RESCAN_INTERVAL);
+        let query = self.search_query.to_lowercase();
+        let due = query != self.content_search_query
+            || self.content_search_at.is_none_or(|at| at.elapsed() >= RESCAN_INTERVAL);
+        if !due || self.content_search.scanning.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        self.content_search_query = query.clone();
+        self.content_search_at = Some(std::time::Instant::now());
+        let search = Arc::clone(&self.content_search);
+        let resources: Vec<PathBuf> = self.resources.iter().map(|r| r.path.clone()).collect();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            search.scan(&resources, &query);
+            search.scanning.store(false, Ordering::SeqCst);
+            ctx.request_repaint();
+        });
+    }
+
+    // Draw one row of the resource list. Removing and cloning are only noted in
+    // `resources_to_remove` and `resource_to_clone`, the caller applies them after the list.
+    fn resource_row(&mut self, ui: &mut egui::Ui, i: usize, resources_to_remove: &mut Vec<usize>, resource_to_clone: &mut Option<usize>) {
+        let resource_name = self.resources[i].path.file_name()
+            .map_or_else(|| "Unknown".to_string(), |n| n.to_string_lossy().to_string());
+        
+        let resource_path = self.resources[i].path.to_string_lossy().to_string();
+        
+        let archive_name = self.resources[i].archive_filename().unwrap_or_else(|_| resource_name.clone());
+        let is_selected = Some(i) == self.selected_resource;
+        let is_missing = !self.resources[i].path.exists();
+        
+        // Create a frame for each resource with conditional highlighting
+        let mut frame = egui::Frame::default()
+            .inner_margin(egui::style::Margin::same(8.0))
+            .rounding(egui::Rounding::same(4.0));
+        
+        if is_selected {
+            frame = frame.fill(ui.style().visuals.selection.bg_fill);
+        }
+        
+        frame.show(ui, |ui| {
+            ui.horizontal(|ui| {
+                if is_missing {
+                    ui.colored_label(egui::Color32::from_rgb(230, 160, 0), "⚠")
+                        .on_hover_text("File no longer exists on disk");
+                } else {
+                    // Detected once per path, the magic bytes fallback reads the file
+                    let (icon, kind) = *self.resource_types.entry(self.resources[i].path.clone())
+                        .or_insert_with_key(|path| detect_file_type(path));
+                    ui.label(icon).on_hover_text(kind);
+                }
+
+                let name_response = ui.selectable_label(is_selected, &resource_name);
+                if name_response.clicked() {
+                    // Single click selects the resource
+                    if Some(i) == self.selected_resource {
+                        // If already selected, set as main file
+                        self.main_file = archive_name.clone();
+                    }
+                    self.selected_resource = Some(i);
+                }
+                // Right click: open the source file to tweak it before recompiling, or clone the entry
+                name_response.context_menu(|ui| {
+                    if ui.add_enabled(!is_missing, egui::Button::new("Open in Default App")).clicked() {
+                        if let Err(e) = open_with_default_app(&self.resources[i].path) {
+                            self.message = format!("❌ Error: {}", e);
+                        }
+                        ui.close_menu();
+                    }
+                    if ui.add_enabled(!is_missing, egui::Button::new("Show in Folder")).clicked() {
+                        if let Err(e) = show_in_folder(&self.resources[i].path) {
+                            self.message = format!("❌ Error: {}", e);
+                        }
+                        ui.close_menu();
+                    }
+                    if ui.button("Clone").on_hover_text("Add this file again right below, e.g. to extract it under a second name or folder").clicked() {
+                        *resource_to_clone = Some(i);
+                        ui.close_menu();
+                    }
+                });
+                
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if ui.button("✖").clicked() {
+                        if self.selected_resource == Some(i) {
+                            self.selected_resource = None;
+                        }
+                        resources_to_remove.push(i);
+                    }
+                    
+                    if ui.button("Set as Main").on_hover_text("Launch this resource after extraction").clicked() {
+                        self.main_file = archive_name.clone();
+                    }
+
+                    if !self.profiles.is_empty() {
+                        let profile = &mut self.resources[i].profile;
+                        egui::ComboBox::from_id_source(("resource_profile", i))
+                            .width(80.0)
+                            .selected_text(if profile.is_empty() { "Shared" } else { profile.as_str() })
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(profile, String::new(), "Shared");
+                                for name in &self.profiles {
+                                    ui.selectable_value(profile, name.clone(), name);
+                                }
+                            })
+                            .response
+                            .on_hover_text("Shared resources go into every build; a profile's resources only into that profile's build");
+                    }
+
+                    let mode = &mut self.resources[i].compression;
+                    egui::ComboBox::from_id_source(("resource_compression", i))
+                        .width(80.0)
+                        .selected_text(match mode.as_str() {
+                            "store" => "Store",
+                            "compress" => "Compress",
+                            _ => "Auto",
+                        })
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(mode, String::new(), "Auto");
+                            ui.selectable_value(mode, "store".to_string(), "Store");
+                            ui.selectable_value(mode, "compress".to_string(), "Compress");
+                        })
+                        .response
+                        .on_hover_text("Auto follows \"Compress resources\" and keeps whichever is smaller. Store or Compress on any resource makes the build compress each resource separately.");
+                });
+            });
+            
+            ui.add_space(2.0);
+            ui.label(format!("Path: {}", resource_path));
+            if archive_name != resource_name {
+                ui.label(format!("Extracts to: {}", archive_name));
+            }
+        });
+        
+        ui.add_space(4.0);
+    }
+}
+
+impl eframe::App for AppState {
+    // Closing the window loses anything not saved to a project file
+    fn on_close_event(&mut self) -> bool {
+        self.close_confirmed || self.confirm_discard_changes("exit")
+    }
+
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.refresh_stub_capabilities();
+        self.refresh_content_search(ctx);
+
+        // Pick up the result of a finished background compile
+        let finished = self.compile_job.as_ref()
+            .and_then(|job| job.result.lock().unwrap().take().map(|result| (result, job.close_when_done)));
+        if let Some((result, close_when_done)) = finished {
+            let succeeded = result.is_ok();
+            self.message = match result {
+                Ok(msg) => msg,
+                Err(e) if e == BUILD_CANCELLED => e,
+                Err(e) => format!("❌ Error: {}", e),
+            };
+            self.compile_job = None;
+            if succeeded && (self.close_after_compile || close_when_done) {
+                self.close_confirmed = true;
+                _frame.close();
+            }
+        }
+
+        // Pick up the result of a finished compression comparison
+        let compared = self.comparison_job.as_ref().and_then(|job| job.result.lock().unwrap().take());
+        if let Some(result) = compared {
+            match result {
+                Ok(comparison) => self.compression_comparison = Some(comparison),
+                Err(e) if e == BUILD_CANCELLED => {}
+                Err(e) => self.message = format!("❌ Error: {}", e),
+            }
+            self.comparison_job = None;
+        }
+
+        // set the theme based on dark_mode
+        if self.dark_mode {
+            ctx.set_visuals(egui::Visuals::dark());
+        } else {
+            ctx.set_visuals(egui::Visuals::light());
+        }
+        
+        // egui drag and drop: project files are opened, anything else becomes a resource
+        if !ctx.input(|i| i.raw.dropped_files.clone()).is_empty() {
+            for file in &ctx.input(|i| i.raw.dropped_files.clone()) {
+                if let Some(path) = &file.path {
+                    if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("rcproj")) {
+                        if self.confirm_discard_changes(&format!("open {}", path.to_string_lossy())) {
+                            match self.load_project(path) {
+                                Ok(problems) => self.message = with_load_problems("Project loaded successfully", &problems),
+                                Err(e) => self.message = format!("❌ {}", e),
+                            }
+                        }
+                    } else if !self.has_resource(path) {
+                        self.resources.push(ResourceItem::new(path.clone()));
+                    }
+                }
+            }
+        }
+
+        // Keyboard shortcuts help, pinned below the scrollable content
+        egui::TopBottomPanel::bottom("shortcuts_footer").show(ctx, |ui| {
+            ui.horizontal_wrapped(|ui| {
+                ui.spacing_mut().item_spacing.x = 10.0;
+                ui.label("Keyboard Shortcuts:");
+                ui.label("Ctrl+N: New Project");
+                ui.label("Ctrl+S: Save Project");
+                ui.label("Ctrl+O: Open Project");
+                ui.label("Ctrl+B: Compile EXE");
+                ui.label("Delete: Remove Selected Resource");
+            });
+        });
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            // Everything scrolls so the Compile button and messages stay reachable on small screens
+            egui::ScrollArea::vertical().auto_shrink([false, false]).show(ui, |ui| {
+                // Top bar with title and menu
+                ui.horizontal(|ui| {
+                    // Use a styled heading for the title instead of an image
+                    ui.heading(egui::RichText::new("Resource Compiler")
+                        .size(28.0)
+                        .strong()
+                        .color(if self.dark_mode {
+                            egui::Color32::from_rgb(120, 80, 200)
+                        } else {
+                            egui::Color32::from_rgb(60, 40,100)
+                        })
+                    );
+                
+                    // Menu bar
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        let theme_text = if self.dark_mode { "☀ Light Mode" } else { "🌙 Dark Mode" };
+                        if ui.button(theme_text).clicked() {
+                            self.dark_mode = !self.dark_mode;
+                        }
+                    
+                        if ui.button("⚙ Settings").clicked() {
+                            self.show_settings = !self.show_settings;
+                        }
+                    
+                        // File menu dropdown
+                        egui::menu::menu_button(ui, "📁 File", |ui| {
+                            if ui.button("New Project").clicked() {
+                                ui.close_menu();
+                                if self.confirm_discard_changes("start a new project") {
+                                    self.new_project();
+                                }
+                            }
+                        
+                            if ui.button("Save Project").clicked() {
+                                ui.close_menu();
+                                self.save_project_or_ask();
+                            }
+                            if ui.button("Save Project As...").on_hover_text("Saves to a new .rcproj, which later saves then go to").clicked() {
+                                ui.close_menu();
+                                self.save_project_dialog(false);
+                            }
+                            if ui.button("Save a Copy...").on_hover_text("Writes the current settings to another .rcproj but keeps working on this one, e.g. to start a variant with a different icon or main file").clicked() {
+                                ui.close_menu();
+                                self.save_project_dialog(true);
+                            }
+                        
+                            ui.checkbox(&mut self.relative_paths, "Save paths relative to the project")
+                                .on_hover_text("Stores resource, zip and icon paths relative to the .rcproj file, so the project still works after moving its folder or checking it out on another machine. Paths on another drive stay absolute.");
+                            ui.checkbox(&mut self.sort_resources, "Sort resources when saving")
+                                .on_hover_text("Keeps the resource list in path order so version control diffs only show files that were added or removed. Resources are extracted in list order, so this changes the extraction order too (\"Extract main file last\" still applies).");
+                            ui.checkbox(&mut self.compact_project, "Save compact JSON")
+                                .on_hover_text("Writes the project on a single line instead of indented. Smaller, but harder to read and diff.");
+
+                            if ui.button("Copy as Command Line").on_hover_text("Copies a --build command that packs the current settings without the GUI, e.g. for CI").clicked() {
+                                // --project reloads the saved file, which wouldn't have options
+                                // turned off or resources removed since, and flags can't undo those
+                                if self.project_path.is_some() && self.has_unsaved_changes() {
+                                    self.message = "❌ Save the project first: the command builds from the saved project file, so it wouldn't include the unsaved changes".to_string();
+                                } else {
+                                    let command = self.cli_command();
+                                    ui.output_mut(|output| output.copied_text = command.clone());
+                                    self.message = if self.project_path.is_some() {
+                                        format!("Copied: {}", command)
+                                    } else {
+                                        format!("Copied: {} (save the project first to include settings without a flag)", command)
+                                    };
+                                }
+                                ui.close_menu();
+                            }
+
+                            if ui.button("Load Project").clicked() && self.confirm_discard_changes("load another project") {
+                                if let Some(path) = rfd::FileDialog::new()
+                                    .add_filter("Resource Compiler Project", &["rcproj"])
+                                    .pick_file() {
+                                    match self.load_project(&path) {
+                                        Ok(problems) => self.message = with_load_problems("Project loaded successfully", &problems),
+                                        Err(e) => self.message = format!("❌ {}", e),
+                                    }
+                                }
+                                ui.close_menu();
+                            }
+                        
+                            if ui.add_enabled(self.compile_job.is_none(), egui::Button::new("Compile and Close")).clicked() {
+                                self.start_compile(ctx, true);
+                                ui.close_menu();
+                            }
+
+                            ui.separator();
+                        
+                            if ui.button("Import Packed EXE as Project...")
+                                .on_hover_text("Extracts the resources of a packed EXE into a folder you choose and restores its settings, to recover a lost project")
+                                .clicked()
+                                && self.confirm_discard_changes("import a packed EXE")
+                            {
+                                ui.close_menu();
+                                if let Some(path) = rfd::FileDialog::new().add_filter("Executable", &["exe"]).pick_file()
+                                    && let Some(folder) = rfd::FileDialog::new().set_title("Folder for the extracted resources").pick_folder()
+                                {
+                                    match self.import_packed_exe(&path, &folder) {
+                                        Ok(msg) => self.message = msg,
+                                        Err(e) => self.message = format!("❌ Error: {}", e),
+                                    }
+                                }
+                            }
+
+                            if ui.button("Add to Packed EXE...").clicked() {
+                                if let Some(path) = rfd::FileDialog::new()
+                                    .add_filter("Executable", &["exe"])
+                                    .pick_file() {
+                                    match self.load_packed_exe(path) {
+                                        Ok(msg) => self.message = msg,
+                                        Err(e) => self.message = format!("❌ Error: {}", e),
+                                    }
+                                }
+                                ui.close_menu();
+                            }
+                        });
+                    });
+                });
+
+                ui.add_space(10.0);
+
+                // Container with rounded corners and padding for the main content
+                egui::Frame::default()
+                    .fill(ui.style().visuals.faint_bg_color)
+                    .rounding(10.0)
+                    .inner_margin(10.0)
+                    .show(ui, |ui| {
+                        ui.heading("Project Settings");
+                        ui.add_space(5.0);
+
+                        // --- Extraction Path (supports env variables) ---
+                        ui.horizontal(|ui| {
+                            ui.label("Extraction Path:");
+                            ui.text_edit_singleline(&mut self.extraction_path)
+                                .on_hover_text("Folder the packed EXE extracts into. Absolute paths are used as is; relative paths are created in the folder the EXE is started from (usually next to it). {reg:HKLM\\Software\\MyCo\\InstallDir|C:\\MyCo} is replaced by that registry value on the target machine, or by the text after | if it is missing. After that, %VARIABLE% references such as %LOCALAPPDATA% are expanded on the target machine.");
+                            ui.label(" (C:\\folder | cool_folder)");
+                            if !self.extraction_path_template.trim().is_empty()
+                                && ui.small_button("↺").on_hover_text("Reset to the default extraction path from Settings").clicked()
+                            {
+                                self.extraction_path = self.resolve_extraction_template();
+                            }
+                        });
+
+                        // --- Output EXE Name ---
+                        ui.horizontal(|ui| {
+                            ui.label("Output EXE Name:");
+                            ui.text_edit_singleline(&mut self.output_exe)
+                                .on_hover_text("File the packed EXE is written to, relative to the current folder unless absolute. An existing file is overwritten.");
+                        });
+
+                        // --- Profiles (environment-specific variants of the same project) ---
+                        ui.horizontal(|ui| {
+                            ui.label("Profile:");
+                            egui::ComboBox::from_id_source("active_profile")
+                                .selected_text(if self.active_profile.is_empty() { "None" } else { self.active_profile.as_str() })
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut self.active_profile, String::new(), "None");
+                                    for name in &self.profiles {
+                                        ui.selectable_value(&mut self.active_profile, name.clone(), name);
+                                    }
+                                })
+                                .response
+                                .on_hover_text("Builds the shared resources plus the selected profile's. The output name gets the profile appended, or replaces {profile} in it. None packs the shared resources only.");
+                            ui.add(egui::TextEdit::singleline(&mut self.new_profile_name).hint_text("e.g. prod").desired_width(80.0));
+                            let name = self.new_profile_name.trim().to_string();
+                            if ui.add_enabled(!name.is_empty() && !self.profiles.contains(&name), egui::Button::new("Add Profile")).clicked() {
+                                self.profiles.push(name.clone());
+                                self.active_profile = name;
+                                self.new_profile_name.clear();
+                            }
+                            if !self.active_profile.is_empty() && ui.button("Remove Profile").clicked() {
+                                let removed = std::mem::take(&mut self.active_profile);
+                                self.profiles.retain(|name| *name != removed);
+                                let mut shared = 0;
+                                for item in self.resources.iter_mut().filter(|item| item.profile == removed) {
+                                    item.profile.clear();
+                                    shared += 1;
+                                }
+                                self.message = format!("Removed profile {}, {} of its resource(s) are shared now", removed, shared);
+                            }
+                        });
+                        if !self.active_profile.is_empty() {
+                            ui.label(format!("Builds {}", self.profile_output()));
+                        }
+
+                        // --- Package Version (recorded in the header for support/updates) ---
+                        ui.horizontal(|ui| {
+                            ui.label("Package Version:");
+                            ui.text_edit_singleline(&mut self.package_version)
+                                .on_hover_text("Stored in the header and shown by --inspect and the stub log. Not used for anything else.");
+                            ui.label("(optional, e.g. 1.2.0)");
+                        });
+                        ui.checkbox(&mut self.stamp_build_time, "Record build time")
+                            .on_hover_text("Stores when the EXE was built, shown by --inspect and the stub log. Off by default so identical inputs build byte-identical EXEs; SOURCE_DATE_EPOCH, when set, is recorded instead of the clock either way.");
+
+                        // --- Extraction Order ---
+                        ui.horizontal(|ui| {
+                            ui.checkbox(&mut self.main_file_last, "Extract main file last")
+                                .on_hover_text("Resources are extracted in list order. With this set the main file is written after everything else, so its DLLs and data files are already in place.");
+                        });
+
+                        // --- Target Architecture Selection ---
+                        ui.horizontal(|ui| {
+                            ui.label("Target Architecture:");
+                            egui::ComboBox::from_id_source("target_arch")
+                                .selected_text(match self.target_arch.as_str() {
+                                    "x86" => "x86 (32-bit)",
+                                    _ => "x64 (64-bit)"
+                                })
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut self.target_arch, "x64".to_string(), "x64 (64-bit)");
+                                    ui.selectable_value(&mut self.target_arch, "x86".to_string(), "x86 (32-bit)");
+                                })
+                                .response
+                                .on_hover_text("Picks which stub the packed EXE is built from. Choose x86 if it must run on 32-bit Windows; it does not change the bundled files.");
+                        });
+
+                        // --- Clean Install Toggle ---
+                        ui.horizontal(|ui| {
+                            ui.checkbox(&mut self.clean_before_extract, "Delete extraction folder before extracting")
+                                .on_hover_text("Removes files left over from older versions. System, profile and the EXE's own folders are never deleted.");
+                        });
+
+                        // --- Existing Files Policy ---
+                        ui.horizontal(|ui| {
+                            ui.label("Existing Files:");
+                            egui::ComboBox::from_id_source("overwrite_policy")
+                                .selected_text(match self.overwrite_policy.as_str() {
+                                    "refuse" => "Refuse",
+                                    "allow" => "Overwrite without asking",
+                                    _ => "Ask first",
+                                })
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut self.overwrite_policy, "ask".to_string(), "Ask first");
+                                    ui.selectable_value(&mut self.overwrite_policy, "refuse".to_string(), "Refuse");
+                                    ui.selectable_value(&mut self.overwrite_policy, "allow".to_string(), "Overwrite without asking");
+                                });
+                        }).response.on_hover_text("What the packed EXE does the first time the extraction folder already holds files it didn't create (e.g. the Desktop). Later runs recognise their own folder and overwrite it quietly.");
+
+                        // --- Update Mode (per file) ---
+                        ui.horizontal(|ui| {
+                            ui.label("Update Files:");
+                            egui::ComboBox::from_id_source("update_mode")
+                                .selected_text(match self.update_mode.as_str() {
+                                    "newer" => "Only if bundled file is newer",
+                                    "skip" => "Keep existing files",
+                                    _ => "Always overwrite",
+                                })
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut self.update_mode, "always".to_string(), "Always overwrite");
+                                    ui.selectable_value(&mut self.update_mode, "newer".to_string(), "Only if bundled file is newer");
+                                    ui.selectable_value(&mut self.update_mode, "skip".to_string(), "Keep existing files");
+                                });
+                        }).response.on_hover_text("What happens to a file that is already on disk. \"Only if bundled file is newer\" compares modified times, so files the user edited after extraction are kept unless the bundle carries a newer version. Has no effect with \"Delete extraction folder before extracting\".");
+
+                        // --- Single Instance Toggle ---
+                        ui.horizontal(|ui| {
+                            ui.checkbox(&mut self.single_instance, "Single instance")
+                                .on_hover_text("If the app is already running, bring it to the front instead of extracting and launching another copy");
+                        });
+
+                        // --- Uninstaller Toggle ---
+                        ui.horizontal(|ui| {
+                            ui.checkbox(&mut self.allow_uninstall, "Include uninstaller")
+                                .on_hover_text("Running the packed EXE with --uninstall removes the extracted files instead of launching");
+                        });
+
+                        // --- Error Messages (translate/brand the stub's dialogs) ---
+                        egui::CollapsingHeader::new("Error Messages")
+                            .id_source("stub_messages")
+                            .show(ui, |ui| {
+                                let messages = &mut self.stub_messages;
+                                for (label, text, default) in [
+                                    ("Title:", &mut messages.title, "Error"),
+                                    ("Extraction Failed:", &mut messages.extraction_failed, "The application files could not be extracted."),
+                                    ("Corrupt Archive:", &mut messages.corrupt_archive, "This file is damaged or incomplete. Please download it again."),
+                                    ("Launch Failed:", &mut messages.launch_failed, "The application could not be started."),
+                                ] {
+                                    ui.horizontal(|ui| {
+                                        ui.label(label);
+                                        ui.add(egui::TextEdit::singleline(text).hint_text(default));
+                                    });
+                                }
+                            });
+
+                        // --- License Agreement (accepted once, on first run) ---
+                        egui::CollapsingHeader::new("License Agreement")
+                            .id_source("eula")
+                            .show(ui, |ui| {
+                                ui.label("Shown on first run before anything is extracted. Declining exits without launching. Leave the text empty for no prompt.");
+                                ui.horizontal(|ui| {
+                                    ui.label("Title:");
+                                    ui.add(egui::TextEdit::singleline(&mut self.eula_title).hint_text("License Agreement"));
+                                    if ui.button("Load from File...").clicked()
+                                        && let Some(path) = rfd::FileDialog::new().add_filter("Text", &["txt", "md"]).pick_file()
+                                    {
+                                        match fs::read_to_string(&path) {
+                                            Ok(text) => self.eula_text = text,
+                                            Err(e) => self.message = format!("❌ Failed to read {}: {}", path.display(), e),
+                                        }
+                                    }
+                                });
+                                ui.add(egui::TextEdit::multiline(&mut self.eula_text)
+                                    .desired_rows(6)
+                                    .desired_width(f32::INFINITY)
+                                    .hint_text("License text"));
+                            });
+
+                        // --- Shortcuts (created once, on first run) ---
+                        ui.horizontal(|ui| {
+                            ui.label("Create Shortcut:");
+                            ui.checkbox(&mut self.shortcut_desktop, "Desktop");
+                            ui.checkbox(&mut self.shortcut_start_menu, "Start Menu");
+                        });
+                        if self.shortcut_desktop || self.shortcut_start_menu {
+                            ui.horizontal(|ui| {
+                                ui.label("Shortcut Name:");
+                                ui.text_edit_singleline(&mut self.shortcut_name);
+                                ui.label("(empty = main file name)");
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Arguments:");
+                                ui.text_edit_singleline(&mut self.shortcut_args);
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Icon Resource:");
+                                ui.text_edit_singleline(&mut self.shortcut_icon);
+                                ui.label("(.ico/.exe resource, empty = main file icon)");
+                            });
+                        }
+
+                        // --- Windows Service (installed once, replaces launching) ---
+                        ui.checkbox(&mut self.install_service, "Install main file as a Windows service")
+                            .on_hover_text("On first run the packed EXE registers the extracted main file with the Service Control Manager instead of launching it. Requires administrator rights, so the EXE will request them.");
+                        if self.install_service {
+                            ui.horizontal(|ui| {
+                                ui.label("Service Name:");
+                                ui.text_edit_singleline(&mut self.service_name);
+                                ui.label("(empty = main file name)");
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Display Name:");
+                                ui.text_edit_singleline(&mut self.service_display_name);
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Description:");
+                                ui.text_edit_singleline(&mut self.service_description);
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Start Type:");
+                                egui::ComboBox::from_id_source("service_start_type")
+                                    .selected_text(match self.service_start_type.as_str() {
+                                        "manual" => "Manual",
+                                        "disabled" => "Disabled",
+                                        _ => "Automatic",
+                                    })
+                                    .show_ui(ui, |ui| {
+                                        ui.selectable_value(&mut self.service_start_type, "auto".to_string(), "Automatic");
+                                        ui.selectable_value(&mut self.service_start_type, "manual".to_string(), "Manual");
+                                        ui.selectable_value(&mut self.service_start_type, "disabled".to_string(), "Disabled");
+                                    });
+                                ui.checkbox(&mut self.service_start_now, "Start after installing");
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Service Arguments:");
+                                ui.text_edit_singleline(&mut self.service_args);
+                            });
+                        }
+                    });
+
+                ui.add_space(10.0);
+
+                // Launch section: everything that decides what runs after extracting
+                egui::Frame::default()
+                    .fill(ui.style().visuals.faint_bg_color)
+                    .rounding(10.0)
+                    .inner_margin(10.0)
+                    .show(ui, |ui| {
+                        ui.heading("Launch");
+                        ui.label(egui::RichText::new(self.launch_summary()).italics());
+                        ui.add_space(5.0);
+
+                        // --- Main File (bundled resource filename or external command) ---
+                        ui.horizontal(|ui| {
+                            ui.label("Main File:");
+                            ui.radio_value(&mut self.main_is_external, false, "Bundled resource")
+                                .on_hover_text("Launch one of the packed resources from the extraction folder");
+                            ui.radio_value(&mut self.main_is_external, true, "External command")
+                                .on_hover_text("Launch a program that already exists on the target machine, e.g. notepad.exe or C:\\Tools\\app.exe");
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label(if self.main_is_external { "Command:" } else { "Resource:" });
+                            ui.text_edit_singleline(&mut self.main_file);
+                            if self.main_is_external {
+                                ui.label("(e.g. notepad.exe or C:\\Tools\\app.exe)");
+                            } else {
+                                ui.label("(Select a resource below to set)");
+                            }
+                        });
+
+                        // --- Execution Style Selection ---
+                        ui.horizontal(|ui| {
+                            ui.label("Execution Style:");
+                            egui::ComboBox::from_label("")
+                                .selected_text(match self.execution_style.as_str() {
+                                    "no-window" => "No Window",
+                                    "minimized" => "Minimized",
+                                    "normal" => "Normal",
+                                    "maximized" => "Maximized",
+                                    _ => "Normal"
+                                })
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut self.execution_style, "no-window".to_string(), "No Window");
+                                    ui.selectable_value(&mut self.execution_style, "minimized".to_string(), "Minimized");
+                                    ui.selectable_value(&mut self.execution_style, "normal".to_string(), "Normal");
+                                    ui.selectable_value(&mut self.execution_style, "maximized".to_string(), "Maximized");
+                                })
+                                .response
+                                .on_hover_text("Window state requested for the main file. No Window hides console programs entirely; GUI programs may ignore it and show their window anyway. Minimized/Maximized are likewise only a hint to the program.");
+                        });
+
+                        // --- Launch Verb (how ShellExecute opens the main file) ---
+                        ui.horizontal(|ui| {
+                            ui.label("Launch Verb:");
+                            egui::ComboBox::from_id_source("launch_verb")
+                                .selected_text(self.launch_verb.as_str())
+                                .show_ui(ui, |ui| {
+                                    for verb in ["open", "runas", "print", "edit", "explore"] {
+                                        ui.selectable_value(&mut self.launch_verb, verb.to_string(), verb);
+                                    }
+                                })
+                                .response
+                                .on_hover_text("ShellExecute verb used to start the main file. open runs it normally, runas asks for elevation, and print/edit/explore use the file type's registered handler.");
+                            ui.label("(e.g. print a bundled document instead of opening it)");
+                        });
+
+                        // --- Arguments and Working Directory ---
+                        ui.horizontal(|ui| {
+                            ui.label("Arguments:");
+                            ui.text_edit_singleline(&mut self.launch_args)
+                                .on_hover_text("Command line passed to the main file, exactly as typed (quote paths with spaces)");
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Working Directory:");
+                            ui.text_edit_singleline(&mut self.working_dir)
+                                .on_hover_text("Folder the main file starts in. Relative paths are inside the extraction path, e.g. bin. Empty = the folder the packed EXE was started from (the extraction path with console output).");
+                            ui.label("(empty = default)");
+                        });
+
+                        // --- Required Dependencies (checked before extracting) ---
+                        egui::CollapsingHeader::new(format!("Required Dependencies ({})", self.dependencies.len()))
+                            .id_source("dependencies")
+                            .show(ui, |ui| {
+                                ui.label("The packed EXE checks these before extracting and shows the message if one is missing");
+                                let mut dependency_to_remove = None;
+                                for (i, dependency) in self.dependencies.iter_mut().enumerate() {
+                                    ui.horizontal(|ui| {
+                                        egui::ComboBox::from_id_source(("dependency_kind", i))
+                                            .selected_text(if dependency.kind == "registry" { "Registry key" } else { "File" })
+                                            .show_ui(ui, |ui| {
+                                                ui.selectable_value(&mut dependency.kind, "file".to_string(), "File");
+                                                ui.selectable_value(&mut dependency.kind, "registry".to_string(), "Registry key");
+                                            });
+                                        let hint = if dependency.kind == "registry" {
+                                            "HKLM\\SOFTWARE\\dotnet\\Setup\\InstalledVersions\\x64"
+                                        } else {
+                                            "%SystemRoot%\\System32\\vcruntime140.dll"
+                                        };
+                                        ui.add(egui::TextEdit::singleline(&mut dependency.path).hint_text(hint).desired_width(260.0));
+                                        ui.add(egui::TextEdit::singleline(&mut dependency.name).hint_text("Name, e.g. VC++ Redistributable").desired_width(160.0));
+                                        if ui.button("✖").clicked() {
+                                            dependency_to_remove = Some(i);
+                                        }
+                                    });
+                                    ui.add(egui::TextEdit::singleline(&mut dependency.message)
+                                        .hint_text("Message when missing (empty = default naming it)")
+                                        .desired_width(f32::INFINITY));
+                                }
+                                if let Some(i) = dependency_to_remove {
+                                    self.dependencies.remove(i);
+                                }
+                                if ui.button("➕ Add Dependency").clicked() {
+                                    self.dependencies.push(Dependency {
+                                        kind: "file".to_string(),
+                                        ..Default::default()
+                                    });
+                                }
+                            });
+
+                        // --- Setup Command (run after extracting, before launching) ---
+                        ui.horizontal(|ui| {
+                            ui.label("Setup Command:");
+                            ui.text_edit_singleline(&mut self.post_extract_command)
+                                .on_hover_text("Run through cmd /c in the extraction folder after extracting; the main file is launched once it exits. E.g. regsvr32 /s mylib.dll");
+                            ui.label("(optional)");
+                        });
+                        if !self.post_extract_command.trim().is_empty() {
+                            ui.horizontal(|ui| {
+                                ui.label("Setup Window:");
+                                egui::ComboBox::from_id_source("post_extract_style")
+                                    .selected_text(match self.post_extract_style.as_str() {
+                                        "minimized" => "Minimized",
+                                        "normal" => "Normal",
+                                        "maximized" => "Maximized",
+                                        _ => "No Window",
+                                    })
+                                    .show_ui(ui, |ui| {
+                                        ui.selectable_value(&mut self.post_extract_style, "no-window".to_string(), "No Window");
+                                        ui.selectable_value(&mut self.post_extract_style, "minimized".to_string(), "Minimized");
+                                        ui.selectable_value(&mut self.post_extract_style, "normal".to_string(), "Normal");
+                                        ui.selectable_value(&mut self.post_extract_style, "maximized".to_string(), "Maximized");
+                                    });
+                                ui.checkbox(&mut self.post_extract_abort_on_failure, "Don't launch if it fails")
+                                    .on_hover_text("A non-zero exit code stops the packed EXE with the launch error message. Unchecked, the failure is only logged.");
+                            });
+                        }
+
+                        // --- Wait for Exit (launch and forget otherwise) ---
+                        ui.horizontal(|ui| {
+                            ui.checkbox(&mut self.wait_for_exit, "Wait for main app to exit")
+                                .on_hover_text("The packed EXE keeps running until the main file exits and then exits with its exit code, so scripts can wait on it and check the result. Unchecked, it launches the main file and exits straight away.");
+                        });
+
+                        // --- Output Window (GUI front for bundled CLI tools) ---
+                        ui.horizontal(|ui| {
+                            ui.checkbox(&mut self.capture_output, "Show console output in a window")
+                                .on_hover_text("Runs the main file without a console and shows what it prints in a simple window. Ignores the execution style and launch verb.");
+                        });
+
+                        // --- Administrator Rights (whole packed EXE, or only the main file) ---
+                        ui.horizontal(|ui| {
+                            ui.checkbox(&mut self.run_as_admin, "Extract as administrator")
+                                .on_hover_text("The packed EXE checks it is elevated before extracting. If not, it relaunches itself through a UAC prompt; if that is declined it shows the message below and exits without extracting or launching anything. Needed to extract into protected folders such as Program Files.");
+                            // Same setting as the runas launch verb, surfaced next to its counterpart.
+                            // Only toggles between open and runas, so other verbs leave it disabled
+                            let mut launch_elevated = self.launch_verb == "runas";
+                            let toggleable = matches!(self.launch_verb.as_str(), "open" | "runas");
+                            if ui.add_enabled(toggleable, egui::Checkbox::new(&mut launch_elevated, "Launch main file as administrator"))
+                                .on_hover_text("Extracts without elevation (to a folder the user can write) and asks for administrator rights only when starting the main file. Same as the runas launch verb.")
+                                .on_disabled_hover_text("The launch verb is set to something other than open, change it back to use this")
+                                .changed()
+                            {
+                                self.launch_verb = if launch_elevated { "runas" } else { "open" }.to_string();
+                            }
+                        });
+                        if self.run_as_admin {
+                            ui.horizontal(|ui| {
+                                ui.label("Admin Prompt Title:");
+                                ui.text_edit_singleline(&mut self.admin_title);
+                                ui.label("(empty = \"Admin Required\")");
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Admin Prompt Text:");
+                                ui.text_edit_singleline(&mut self.admin_message);
+                                ui.label("(empty = \"Please run as administrator.\")");
+                            });
+                        }
+
+                        // --- Launch Delay ---
+                        ui.horizontal(|ui| {
+                            ui.label("Launch Delay (ms):");
+                            ui.add(egui::DragValue::new(&mut self.launch_delay_ms).speed(100.0).clamp_range(0..=600_000))
+                                .on_hover_text("Pause between finishing extraction and starting the main file");
+                            ui.label("(0 = launch immediately)");
+                        });
+                    });
+
+                ui.add_space(10.0);
+            
+                // Resources section with improved appearance
+                egui::Frame::default()
+                    .fill(ui.style().visuals.faint_bg_color)
+                    .rounding(10.0)
+                    .inner_margin(10.0)
+                    .show(ui, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.heading("Resources");
+                        
+                            // Move the search to the header row
+                            if !self.resources.is_empty() {
+                                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                    if ui.button("🔍 Search").clicked() {
+                                        // Handle search button click if needed
+                                    }
+                                    ui.checkbox(&mut self.search_contents, "Contents")
+                                        .on_hover_text("Also search the text inside resources, e.g. to find which config sets a key. Binary and very large files are skipped.");
+                                    ui.text_edit_singleline(&mut self.search_query);
+                                    ui.label("Search:");
+                                });
+                            }
+                        });
+                    
+                        ui.add_space(5.0);
+                    
+                        // Always show the Add Resource button at the top
+                        ui.horizontal(|ui| {
+                            if ui.button("📂 Add Resource").on_hover_text("Pick a file to pack. Files can also be dropped onto the window.").clicked()
+                                && let Some(file) = rfd::FileDialog::new().pick_file()
+                                && !self.has_resource(&file)
+                            {
+                                self.resources.push(ResourceItem::new(file));
+                            }
+                            if ui.button("🗜 Add ZIP Contents").on_hover_text("Pack every file inside a .zip, keeping its folders. No need to unzip it first.").clicked()
+                                && let Some(file) = rfd::FileDialog::new().add_filter("ZIP archive", &["zip"]).pick_file()
+                            {
+                                if self.resource_zips.iter().any(|z| z.path == file) {
+                                    self.message = format!("{} is already added", file.to_string_lossy());
+                                } else {
+                                    match ResourceZip::open(file) {
+                                        Ok(resource_zip) => {
+                                            self.message = format!("Added {} file(s) from {}", resource_zip.entries.len(), resource_zip.path.to_string_lossy());
+                                            self.resource_zips.push(resource_zip);
+                                        }
+                                        Err(e) => self.message = format!("❌ Error: {}", e),
+                                    }
+                                }
+                            }
+                            if ui.button("📋 Import Manifest").on_hover_text("Add the resources listed in a .csv or .json manifest, with their target names and main/compress flags").clicked()
+                                && let Some(file) = rfd::FileDialog::new().add_filter("Manifest", &["csv", "json"]).pick_file()
+                            {
+                                self.message = match self.import_manifest(&file) {
+                                    Ok(message) => message,
+                                    Err(e) => format!("❌ Error: {}", e),
+                                };
+                            }
+                        });
+                    
+                        // Add every file matching a glob relative to a base folder
+                        ui.horizontal(|ui| {
+                            ui.label("Glob:");
+                            ui.text_edit_singleline(&mut self.glob_input)
+                                .on_hover_text("Pattern relative to the base folder, e.g. assets/**/*.png. Saved with the project and re-expanded when it is loaded.");
+                            let base_text = self.glob_base.as_ref()
+                                .map_or_else(|| "Base Folder...".to_string(), |p| p.to_string_lossy().to_string());
+                            if ui.button(base_text).clicked()
+                                && let Some(folder) = rfd::FileDialog::new().pick_folder()
+                            {
+                                self.glob_base = Some(folder);
+                            }
+                            if ui.button("➕ Add Matches").clicked() {
+                                match &self.glob_base {
+                                    Some(base) if !self.glob_input.trim().is_empty() => {
+                                        match expand_glob(base, self.glob_input.trim(), &self.ignore_patterns) {
+                                            Ok((matches, skipped)) => {
+                                                let mut added = 0;
+                                                for path in matches {
+                                                    if !self.has_resource(&path) {
+                                                        self.resources.push(ResourceItem::new(path));
+                                                        added += 1;
+                                                    }
+                                                }
+                                                self.resource_globs.push(ResourceGlob {
+                                                    base: base.clone(),
+                                                    pattern: self.glob_input.trim().to_string(),
+                                                });
+                                                self.message = format!("Added {} file(s) matching {}", added, self.glob_input.trim());
+                                                if skipped > 0 {
+                                                    self.message.push_str(&format!(", skipped {} ignored file(s)", skipped));
+                                                }
+                                            }
+                                            Err(e) => self.message = format!("❌ Error: {}", e),
+                                        }
+                                    }
+                                    _ => self.message = "❌ Error: Choose a base folder and enter a pattern".to_string(),
+                                }
+                            }
+                        });
+
+                        // Patterns saved with the project
+                        let mut glob_to_remove = None;
+                        for (i, resource_glob) in self.resource_globs.iter().enumerate() {
+                            ui.horizontal(|ui| {
+                                ui.label(format!("Pattern: {} (in {})", resource_glob.pattern, resource_glob.base.to_string_lossy()));
+                                if ui.small_button("✖").clicked() {
+                                    glob_to_remove = Some(i);
+                                }
+                            });
+                        }
+                        if let Some(i) = glob_to_remove {
+                            self.resource_globs.remove(i);
+                        }
+
+                        // Zip files whose contents are packed
+                        let mut zip_to_remove = None;
+                        for (i, resource_zip) in self.resource_zips.iter().enumerate() {
+                            ui.horizontal(|ui| {
+                                egui::CollapsingHeader::new(format!(
+                                    "🗜 {} ({} files)",
+                                    resource_zip.path.file_name().unwrap_or_default().to_string_lossy(),
+                                    resource_zip.entries.len()
+                                ))
+                                    .id_source(("resource_zip", i))
+                                    .show(ui, |ui| {
+                                        for name in &resource_zip.entries {
+                                            ui.horizontal(|ui| {
+                                                ui.label(name);
+                                                if ui.small_button("Set as Main").clicked() {
+                                                    self.main_file = name.clone();
+                                                }
+                                            });
+                                        }
+                                    });
+                                if ui.small_button("✖").clicked() {
+                                    zip_to_remove = Some(i);
+                                }
+                            });
+                        }
+                        if let Some(i) = zip_to_remove {
+                            self.resource_zips.remove(i);
+                        }
+
+                        ui.label("Drag & drop files here or use the Add Resource button above:");
+
+                        // Resources already inside the packed EXE being added to
+                        if let Some(base_exe) = self.base_exe.clone() {
+                            let mut detach = false;
+                            egui::CollapsingHeader::new(format!(
+                                "📦 {} embedded in {}",
+                                self.embedded_resources.len(),
+                                base_exe.file_name().unwrap_or_default().to_string_lossy()
+                            ))
+                                .id_source("embedded_resources")
+                                .show(ui, |ui| {
+                                    for name in &self.embedded_resources {
+                                        ui.label(name);
+                                    }
+                                    ui.label("Added resources with the same filename replace the embedded copy.");
+                                    if ui.button("Start From Scratch Instead").clicked() {
+                                        detach = true;
+                                    }
+                                });
+                            if detach {
+                                self.base_exe = None;
+                                self.embedded_resources.clear();
+                            }
+                        }
+
+                        if self.resources.is_empty() {
+                            ui.add_space(10.0);
+                            ui.centered_and_justified(|ui| {
+                                ui.label("No resources added yet.");
+                            });
+                            ui.add_space(10.0);
+                        } else {
+                            // Create a scrollable area for resources
+                            egui::ScrollArea::vertical().id_source("resource_list").max_height(300.0).show(ui, |ui| {
+                                // Filter resources based on search query (name, path or tag)
+                                let search_query_lower = self.search_query.to_lowercase();
+                                let mut resources_to_remove = Vec::new();
+                                let mut resource_to_clone = None;
+                                let content_matches = self.content_search.matches.lock().unwrap().clone();
+                            
+                                // Group matching resources by tag, untagged ones sort first
+                                let mut groups: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+                                for i in 0..self.resources.len() {
+                                    let resource_name = self.resources[i].path.file_name()
+                                        .map_or_else(|| "Unknown".to_string(), |n| n.to_string_lossy().to_string());
+                                    let resource_path = self.resources[i].path.to_string_lossy().to_string();
+                                    let tag = self.resources[i].tag.clone();
+                                
+                                    // Skip resources that don't match search query
+                                    let matches = self.search_query.is_empty()
+                                        || resource_name.to_lowercase().contains(&search_query_lower)
+                                        || resource_path.to_lowercase().contains(&search_query_lower)
+                                        || tag.to_lowercase().contains(&search_query_lower)
+                                        || (self.search_contents && content_matches.contains(&self.resources[i].path));
+                                    if !matches {
+                                        continue;
+                                    }
+                                
+                                    groups.entry(tag).or_default().push(i);
+                                }
+                            
+                                // Only show collapsible groups once something is tagged
+                                let has_tags = groups.keys().any(|tag| !tag.is_empty());
+                                for (tag, indices) in groups {
+                                    if has_tags {
+                                        let title = if tag.is_empty() { "Untagged" } else { tag.as_str() };
+                                        egui::CollapsingHeader::new(format!("🏷 {} ({})", title, indices.len()))
+                                            .id_source(("resource_group", &tag))
+                                            .default_open(true)
+                                            .show(ui, |ui| {
+                                                for &i in &indices {
+                                                    self.resource_row(ui, i, &mut resources_to_remove, &mut resource_to_clone);
+                                                }
+                                            });
+                                    } else {
+                                        for &i in &indices {
+                                            self.resource_row(ui, i, &mut resources_to_remove, &mut resource_to_clone);
+                                        }
+                                    }
+                                }
+                            
+                                // Remove resources marked for removal
+                                for &i in resources_to_remove.iter().rev() {
+                                    self.resources.remove(i);
+                                }
+
+                                // The copy goes right after the original and is selected, so its
+                                // name or folder can be changed straight away
+                                if let Some(i) = resource_to_clone.filter(|_| resources_to_remove.is_empty()) {
+                                    self.resources.insert(i + 1, self.resources[i].clone());
+                                    self.selected_resource = Some(i + 1);
+                                    self.message = "Cloned the resource. Give the copy a different name or folder below, or both extract to the same file.".to_string();
+                                }
+                            });
+                        
+                            // Resource reordering buttons - moved inside the resources container
+                            if self.selected_resource.is_some() {
+                                ui.horizontal(|ui| {
+                                    ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui| {
+                                        if ui.button("⬆ Move Up").clicked() && self.selected_resource.unwrap() > 0 {
+                                            let idx = self.selected_resource.unwrap();
+                                            self.resources.swap(idx, idx - 1);
+                                            self.selected_resource = Some(idx - 1);
+                                        }
+                                    
+                                        if ui.button("⬇ Move Down").clicked() && self.selected_resource.unwrap() < self.resources.len() - 1 {
+                                            let idx = self.selected_resource.unwrap();
+                                            self.resources.swap(idx, idx + 1);
+                                            self.selected_resource = Some(idx + 1);
+                                        }
+                                    
+                                        // Tag the selected resource to group it in the list
+                                        if let Some(item) = self.selected_resource.and_then(|idx| self.resources.get_mut(idx)) {
+                                            ui.label("Tag:");
+                                            if ui.add(egui::TextEdit::singleline(&mut item.tag).desired_width(120.0)).changed() && item.tag.trim().is_empty() {
+                                                item.tag.clear();
+                                            }
+
+                                            // Extract the selected resource into a subfolder (e.g. config/)
+                                            ui.label("Folder:");
+                                            if ui.add(egui::TextEdit::singleline(&mut item.subdir).desired_width(120.0))
+                                                .on_hover_text("Subfolder of the extraction path, empty = extract to the top level")
+                                                .changed() && item.subdir.trim().is_empty() {
+                                                item.subdir.clear();
+                                            }
+
+                                            // Extract the selected resource under a different name
+                                            ui.label("Name:");
+                                            let original = item.path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+                                            if ui.add(egui::TextEdit::singleline(&mut item.name).hint_text(original).desired_width(120.0))
+                                                .on_hover_text("Filename to extract as, empty = keep the original filename")
+                                                .changed() && item.name.trim().is_empty() {
+                                                item.name.clear();
+                                            }
+                                        }
+                                    });
+                                });
+
+                                // Read-only preview of the selected resource, re-read only when the selection changes
+                                if let Some(path) = self.selected_resource.and_then(|idx| self.resources.get(idx)).map(|r| r.path.clone()) {
+                                    if self.preview.as_ref().map(|(previewed, _)| previewed) != Some(&path) {
+                                        self.preview = Some((path.clone(), resource_preview(&path)));
+                                    }
+                                    if let Some((_, preview)) = &self.preview {
+                                        egui::CollapsingHeader::new("👁 Preview")
+                                            .id_source("resource_preview")
+                                            .show(ui, |ui| {
+                                                egui::ScrollArea::both()
+                                                    .id_source("resource_preview_scroll")
+                                                    .max_height(200.0)
+                                                    .show(ui, |ui| {
+                                                        let mut text = preview.as_str();
+                                                        ui.add(egui::TextEdit::multiline(&mut text)
+                                                            .code_editor()
+                                                            .desired_width(f32::INFINITY));
+                                                    });
+                                            });
+                                    }
+                                }
+                            }
+                        }
+                    });
+
+                ui.add_space(10.0);
+
+                // Readiness: what's still missing before a build can succeed
+                egui::Frame::default()
+                    .fill(ui.style().visuals.faint_bg_color)
+                    .rounding(10.0)
+                    .inner_margin(10.0)
+                    .show(ui, |ui| {
+                        let checks = self.readiness_checks();
+                        let ready = checks.iter().all(|(_, ok)| *ok);
+                        egui::CollapsingHeader::new(if ready { "✔ Ready to compile" } else { "✖ Not ready to compile" })
+                            .id_source("readiness")
+                            .default_open(!ready)
+                            .show(ui, |ui| {
+                                for (label, ok) in checks {
+                                    ui.horizontal(|ui| {
+                                        if ok {
+                                            ui.colored_label(egui::Color32::from_rgb(80, 180, 80), "✔");
+                                        } else {
+                                            ui.colored_label(egui::Color32::from_rgb(220, 60, 60), "✖");
+                                        }
+                                        ui.label(label);
+                                    });
+                                }
+                            });
+                    });
+
+                ui.add_space(10.0);
+            
+                // Action buttons section
+                ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
+                    let compiling = self.compile_job.is_some();
+                    if ui.add_enabled(!compiling, egui::Button::new("📦 Compile EXE")).clicked() {
+                        self.start_compile(ctx, false);
+                    }
+                    ui.checkbox(&mut self.close_after_compile, "Close after successful compile");
+                    if let Some(job) = &self.compile_job {
+                        ui.horizontal(|ui| {
+                            ui.spinner();
+                            ui.label("Compiling...");
+                            if ui.button("✖ Cancel").clicked() {
+                                job.cancel.store(true, Ordering::Relaxed);
+                            }
+                        });
+                    }
+                });
+            
+                // Message area
+                if (!self.message.is_empty()) {
+                    ui.add_space(10.0);
+                    let (bg_color, text_color) = if self.message.starts_with("❌") {
+                        // Error message
+                        (
+                            egui::Color32::from_rgba_premultiplied(180, 0, 0, 25),
+                            if self.dark_mode { egui::Color32::from_rgb(255, 200, 200) } else { egui::Color32::from_rgb(120, 0, 0) }
+                        )
+                    } else {
+                        // Success message
+                        (
+                            egui::Color32::from_rgba_premultiplied(0, 180, 0, 25),
+                            if self.dark_mode { egui::Color32::from_rgb(200, 255, 200) } else { egui::Color32::from_rgb(0, 100, 0) }
+                        )
+                    };
+
+                    egui::Frame::default()
+                        .rounding(8.0)
+                        .fill(bg_color)
+                        .inner_margin(8.0)
+                        .show(ui, |ui| {
+                            ui.colored_label(text_color, &self.message);
+                        });
+                }
+            });
+
+            // Settings panel (if enabled)
+            if self.show_settings {
+                egui::Window::new("Settings")
+                    .collapsible(false)
+                    .resizable(false)
+                    .vscroll(true)
+                    .show(ctx, |ui| {
+                        ui.heading("Application Settings");
+
+                        ui.horizontal(|ui| {
+                            ui.label("Default Extraction Path:");
+                            let response = ui.add(egui::TextEdit::singleline(&mut self.extraction_path_template)
+                                .hint_text("C:\\MyCompany\\{projectname}"))
+                                .on_hover_text("Extraction path for new projects, remembered between sessions. {projectname}, {exename} and {date} are filled in when the project is created.");
+                            if response.changed()
+                                && let Err(e) = self.save_app_settings()
+                            {
+                                self.message = format!("❌ Error: {}", e);
+                            }
+                        });
+                        ui.separator();
+                        
+                        ui.checkbox(&mut self.compress_resources, "Compress resources")
+                            .on_hover_text("Gzips all resource data as one stream. Smaller EXE, but the stub has to decompress everything in memory before extracting. Little gain for already compressed files (zip, png, mp4).");
+                        ui.add_enabled(self.compress_resources, egui::Checkbox::new(&mut self.seekable_compression, "Seekable compression (1 MB blocks)"))
+                            .on_hover_text("Compresses in independent blocks with an index in the header, so the stub only inflates the blocks of the file it is writing instead of the whole archive. Slightly larger output.");
+                        ui.add_enabled(self.compress_resources, egui::Checkbox::new(&mut self.cache_compression, "Reuse compressed data between builds"))
+                            .on_hover_text("Keeps the last build's compressed data in the temp folder and reuses it while the packed files and compression settings are unchanged");
+                        ui.checkbox(&mut self.sidecar_pack, "Store resources in a separate .rscpack file")
+                            .on_hover_text("Keeps the EXE small; the .rscpack file must be shipped next to it");
+                        ui.checkbox(&mut self.pe_resource_archive, "Store resources as a PE resource (antivirus friendly)")
+                            .on_hover_text("Embeds the archive as an RCDATA resource instead of appending it after the EXE. Windows only.");
+                        ui.checkbox(&mut self.preserve_attributes, "Preserve file attributes (read-only, hidden, system)")
+                            .on_hover_text("Extracted files get the attributes and modified time of the original files");
+                        ui.checkbox(&mut self.verify_after_build, "Verify output after build")
+                            .on_hover_text("Reads the written EXE back and checks its header, offsets and checksum");
+                        ui.checkbox(&mut self.show_progress, "Show extraction progress (for large bundles)")
+                            .on_hover_text("Shows \"Extracting file X of N\" in a small window (or the console for console stubs) while extracting");
+                        ui.horizontal(|ui| {
+                            ui.label("Stub Log:");
+                            egui::ComboBox::from_id_source("log_level")
+                                .selected_text(match self.log_level.as_str() {
+                                    "error" => "Errors only",
+                                    "info" => "Info",
+                                    "debug" => "Debug",
+                                    _ => "Off"
+                                })
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut self.log_level, "off".to_string(), "Off");
+                                    ui.selectable_value(&mut self.log_level, "error".to_string(), "Errors only");
+                                    ui.selectable_value(&mut self.log_level, "info".to_string(), "Info");
+                                    ui.selectable_value(&mut self.log_level, "debug".to_string(), "Debug");
+                                });
+                        }).response.on_hover_text("The packed EXE writes <name>_extract.log to the temp folder");
+                        ui.horizontal(|ui| {
+                            ui.label("Max Extraction Size (MB):");
+                            ui.add(egui::DragValue::new(&mut self.max_extract_size_mb).speed(10.0).clamp_range(0..=MAX_EXTRACT_SIZE_MB))
+                                .on_hover_text("The packed EXE refuses to extract more than this, which guards against corrupt or tampered archives");
+                            ui.label("(0 = default 8 GB)");
+                        });
+                        // Not a required capability: an older stub just doesn't retry
+                        ui.horizontal(|ui| {
+                            ui.label("Retries on Network Paths:");
+                            ui.add(egui::DragValue::new(&mut self.io_retries).clamp_range(0..=10))
+                                .on_hover_text("If creating the extraction folder or writing the first file fails with a network error, the packed EXE tries again this many times, waiting 0.25 s, 0.5 s, 1 s, ... (at most 4 s) in between. Smooths over network shares that drop out for a moment.");
+                            ui.label("(0 = fail straight away)");
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Split Output Every (MB):");
+                            ui.add(egui::DragValue::new(&mut self.split_size_mb).speed(10.0).clamp_range(0..=MAX_SPLIT_SIZE_MB));
+                            ui.label("(0 = single file)");
+                        }).response.on_hover_text("Writes packed.exe plus packed.part2, packed.part3, ... which must be kept next to each other");
+                        ui.horizontal(|ui| {
+                            ui.label("Align Resources To:");
+                            egui::ComboBox::from_id_source("resource_alignment")
+                                .selected_text(if self.resource_alignment > 1 { format!("{} bytes", self.resource_alignment) } else { "No alignment".to_string() })
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut self.resource_alignment, 0, "No alignment");
+                                    for alignment in [8, 16, 64, 512, 4096] {
+                                        ui.selectable_value(&mut self.resource_alignment, alignment, format!("{} bytes", alignment));
+                                    }
+                                });
+                        }).response.on_hover_text("Pads so each resource starts on this boundary, for files that are memory-mapped straight out of the packed EXE (e.g. SQLite databases). Only useful without compression; the boundary is relative to the output file unless a custom icon or PE resource storage is used.");
+                        ui.checkbox(&mut self.stub_console, "Show console window for the packed EXE (debug)")
+                            .on_hover_text("Packs the console build of the stub (e.g. stub_console.exe) so its output is visible");
+                        ui.horizontal(|ui| {
+                            ui.label("Output Subsystem:");
+                            egui::ComboBox::from_id_source("output_subsystem")
+                                .selected_text(match self.output_subsystem.as_str() {
+                                    "console" => "Console",
+                                    "windows" => "Windows (no console)",
+                                    _ => "Same as stub",
+                                })
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut self.output_subsystem, "stub".to_string(), "Same as stub");
+                                    ui.selectable_value(&mut self.output_subsystem, "console".to_string(), "Console");
+                                    ui.selectable_value(&mut self.output_subsystem, "windows".to_string(), "Windows (no console)");
+                                });
+                        }).response.on_hover_text("Patches the subsystem of the packed EXE. Console keeps a console window open (handy for scripts); Windows starts without one.");
+                        ui.checkbox(&mut self.compress_stub, "Compress the stub with UPX")
+                            .on_hover_text("Shrinks the fixed overhead every packed EXE carries. Needs upx on PATH. Can't be combined with a custom icon or PE resource storage, and some antivirus products distrust UPX-packed files.");
+                        ui.add_enabled(!self.sign_output, egui::Checkbox::new(&mut self.strip_stub_signature, "Remove the stub's signature"))
+                            .on_hover_text("A signed stub's signature breaks once resources are appended after it, and Windows then reports the packed EXE's signature as corrupt. Removing it leaves the packed EXE cleanly unsigned. Always done when signing the output.");
+                        ui.checkbox(&mut self.sign_output, "Sign the output EXE")
+                            .on_hover_text("Runs signtool (from the Windows SDK, must be on PATH) once the EXE is completely written, so the signature covers the appended resources and the icon. Can't be combined with split output.");
+                        if self.sign_output {
+                            ui.horizontal(|ui| {
+                                ui.label("Certificate:");
+                                ui.add(egui::TextEdit::singleline(&mut self.sign_certificate).hint_text(".pfx file or thumbprint"))
+                                    .on_hover_text("A .pfx file, or the SHA-1 thumbprint of a certificate in your certificate store");
+                                if ui.button("Browse").clicked()
+                                    && let Some(path) = rfd::FileDialog::new().add_filter("Certificate", &["pfx", "p12"]).pick_file()
+                                {
+                                    self.sign_certificate = path.to_string_lossy().to_string();
+                                }
+                            });
+                            if !is_thumbprint(&self.sign_certificate) {
+                                ui.horizontal(|ui| {
+                                    ui.label("Password:");
+                                    ui.add(egui::TextEdit::singleline(&mut self.sign_password).password(true))
+                                        .on_hover_text("Password of the .pfx file. Not saved with the project.");
+                                });
+                            }
+                            ui.horizontal(|ui| {
+                                ui.label("Timestamp URL:");
+                                ui.add(egui::TextEdit::singleline(&mut self.sign_timestamp_url).hint_text("none"))
+                                    .on_hover_text("Timestamping keeps the signature valid after the certificate expires. Leave empty to sign without one.");
+                            });
+                        }
+                        ui.add_space(5.0);
+
+                        ui.label("Ignore Patterns (one per line):")
+                            .on_hover_text("Skipped when adding files by glob, e.g. .git, node_modules or *.log. Entries containing '/' match the path below the base folder.");
+                        ui.add(egui::TextEdit::multiline(&mut self.ignore_patterns).desired_rows(3));
+                        ui.add_space(5.0);
+                        
+                        ui.horizontal(|ui| {
+                            ui.label("x64 Stub:");
+                            ui.add(egui::TextEdit::singleline(&mut self.stub_path_x64)
+                                .hint_text(if EMBEDDED_STUB_X64.is_empty() { "stub.exe" } else { "built-in" }));
+                            if ui.button("Browse").clicked()
+                                && let Some(path) = rfd::FileDialog::new()
+                                    .add_filter("Executable", &["exe"])
+                                    .pick_file()
+                            {
+                                self.stub_path_x64 = path.to_string_lossy().to_string();
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("x86 Stub:");
+                            ui.add(egui::TextEdit::singleline(&mut self.stub_path_x86)
+                                .hint_text(if EMBEDDED_STUB_X86.is_empty() { "stub_x86.exe" } else { "built-in" }));
+                            if ui.button("Browse").clicked()
+                                && let Some(path) = rfd::FileDialog::new()
+                                    .add_filter("Executable", &["exe"])
+                                    .pick_file()
+                            {
+                                self.stub_path_x86 = path.to_string_lossy().to_string();
+                            }
+                        });
+                        ui.add_space(5.0);
+
+                        ui.horizontal(|ui| {
+                            ui.label("Custom Icon:");
+                            if let Some(ref path) = self.icon_path {
+                                ui.label(path.file_name().unwrap_or_default().to_string_lossy().to_string());
+                                if ui.button("Clear").clicked() {
+                                    self.icon_path = None;
+                                }
+                            } else {
+                                if ui.button("Select Icon").clicked() {
+                                    if let Some(path) = rfd::FileDialog::new()
+                                        .add_filter("Icon", &["ico"])
+                                        .pick_file() {
+                                        self.icon_path = Some(path);
+                                    }
+                                }
+                            }
+                        });
+                        
+                        ui.add_space(10.0);
+                        ui.horizontal(|ui| {
+                            ui.label("Compression Report:");
+                            if ui.button("Analyze Resources").clicked() {
+                                match compression_report(&self.resources.iter().map(|r| r.path.clone()).collect::<Vec<_>>()) {
+                                    Ok(stats) => self.compression_stats = Some(stats),
+                                    Err(e) => self.message = format!("❌ Error: {}", e),
+                                }
+                            }
+                        });
+                        if let Some(stats) = &self.compression_stats {
+                            egui::Grid::new("compression_report")
+                                .striped(true)
+                                .show(ui, |ui| {
+                                    ui.strong("Type");
+                                    ui.strong("Files");
+                                    ui.strong("Original");
+                                    ui.strong("Compressed");
+                                    ui.strong("Ratio");
+                                    ui.end_row();
+                                    for stat in stats {
+                                        let ratio = if stat.original_size == 0 {
+                                            100.0
+                                        } else {
+                                            stat.compressed_size as f64 / stat.original_size as f64 * 100.0
+                                        };
+                                        ui.label(&stat.extension);
+                                        ui.label(stat.files.to_string());
+                                        ui.label(format_size(stat.original_size));
+                                        ui.label(format_size(stat.compressed_size));
+                                        ui.label(format!("{:.1}%", ratio));
+                                        ui.end_row();
+                                    }
+                                });
+                        }
+
+                        ui.horizontal(|ui| {
+                            ui.label("Build Comparison:");
+                            if ui.add_enabled(self.comparison_job.is_none(), egui::Button::new("Compare Compressed vs Uncompressed"))
+                                .on_hover_text("Packs the current resources in memory both ways. No files are written.")
+                                .clicked()
+                            {
+                                self.start_comparison(ctx);
+                            }
+                            if let Some(job) = &self.comparison_job {
+                                ui.spinner();
+                                ui.label("Comparing...");
+                                if ui.button("✖ Cancel").clicked() {
+                                    job.cancel.store(true, Ordering::Relaxed);
+                                }
+                            }
+                        });
+                        if let Some(comparison) = &self.compression_comparison {
+                            egui::Grid::new("compression_comparison")
+                                .striped(true)
+                                .show(ui, |ui| {
+                                    ui.label("");
+                                    ui.strong("Uncompressed");
+                                    ui.strong("Compressed");
+                                    ui.end_row();
+                                    ui.label("Packed EXE size (approx.)");
+                                    ui.label(format_size(comparison.uncompressed_size));
+                                    ui.label(format_size(comparison.compressed_size));
+                                    ui.end_row();
+                                    ui.label("Extra time at startup");
+                                    ui.label("none");
+                                    ui.label(format!("~{} ms to decompress", comparison.decompress_time.as_millis()));
+                                    ui.end_row();
+                                });
+                            let saved = comparison.uncompressed_size.saturating_sub(comparison.compressed_size);
+                            ui.label(if saved > 0 {
+                                format!("Compression saves {}. Writing the files takes the same time either way.", format_size(saved))
+                            } else {
+                                "Compression does not make this bundle smaller; leave it off.".to_string()
+                            });
+                        }
+
+                        ui.horizontal(|ui| {
+                            ui.label("Output Layout:");
+                            if ui.button("Show Byte Layout")
+                                .on_hover_text("Reads the output EXE and shows where the stub, header, each resource and the footer lie")
+                                .clicked()
+                            {
+                                match output_layout(&self.output_exe) {
+                                    Ok(regions) => self.output_layout = Some(regions),
+                                    Err(e) => self.message = format!("❌ Error: {}", e),
+                                }
+                            }
+                        });
+                        if let Some(regions) = &self.output_layout {
+                            show_layout(ui, regions);
+                        }
+                        
+                        ui.add_space(10.0);
+                        if ui.button("Close").clicked() {
+                            self.show_settings = false;
+                        }
+                    });
+            }
+        });
+        
+        // Handle keyboard shortcuts
+        let mut save_shortcut = false;
+        ctx.input(|i| {
+            if i.modifiers.ctrl {
+                if i.key_pressed(egui::Key::N) {
+                    // New project
+                    if self.confirm_discard_changes("start a new project") {
+                        self.new_project();
+                    }
+                }
+                else if i.key_pressed(egui::Key::S) {
+                    // Saved after the input lock is released, it may open a file dialog
+                    save_shortcut = true;
+                }
+                else if i.key_pressed(egui::Key::O) {
+                    // Open project logic - simplified, should open a file dialog
+                    self.message = "Use File menu to open project".to_string();
+                }
+                else if i.key_pressed(egui::Key::B) {
+                    // Compile EXE
+                    self.start_compile(ctx, false);
+                }
+            }
+            
+            if i.key_pressed(egui::Key::Delete) && self.selected_resource.is_some() {
+                let idx = self.selected_resource.unwrap();
+                self.resources.remove(idx);
+                if idx >= self.resources.len() {
+                    self.selected_resource = if self.resources.is_empty() { 
+                        None 
+                    } else { 
+                        Some(self.resources.len() - 1) 
+                    };
+                }
+            }
+        });
+        if save_shortcut {
+            self.save_project_or_ask();
+        }
+    }
+}
+
+/// compile_exe builds the new EXE by:
+/// 1. Verifying the main file is among the resources (unless it is an external command) and that every resource still exists.
+/// 2. Reading the pre-built stub for the selected architecture (stub.exe / stub_x86.exe by default).
+/// 3. Building a JSON header that includes extraction_path, main_file, resources, execution_style, and run_as_admin.
+/// 4. Appending the resource files' bytes.
+/// 5. Adding a footer containing the header length, archive data length, and a fixed marker.
+///
+/// With `base_exe` set, the stub and embedded resources of that packed EXE replace step 2.
+/// With `sidecar_pack` set, steps 4 and 5 are written to `<output>.rscpack` instead of the EXE.
+/// With `pe_resource_archive` set, they are stored as an RCDATA resource of the EXE.
+fn compile_exe(state: &AppState, cancel: &AtomicBool) -> Result<String, String> {
+    // Narrow the resources and output to the selected profile, if any
+    let variant = state.profile_variant()?;
+    let state = &variant;
+
+    // Verify that the main file (by filename) is among the added resources,
+    // unless it names something already present on the target machine.
+    if state.main_is_external {
+        if state.main_file.trim().is_empty() {
+            return Err("Enter the external command to launch".to_string());
+        }
+    } else {
+        if !state.main_file_in_resources() {
+            return Err("Main file must be one of the added resources (by filename)".to_string());
+        }
+    }
+    if state.capture_output && state.launch_verb == "runas" {
+        return Err("The console output window can't launch the main file as administrator, use \"Extract as administrator\" instead".to_string());
+    }
+    if state.install_service && state.main_is_external {
+        return Err("A Windows service must run an extracted main file, not an external command".to_string());
+    }
+
+    // Check every resource up front so all missing files are reported at once.
+    let missing: Vec<String> = state.resources.iter()
+        .map(|r| &r.path)
+        .chain(state.resource_zips.iter().map(|z| &z.path))
+        .filter(|p| !p.exists())
+        .map(|p| p.to_string_lossy().to_string())
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!("{} resource(s) no longer exist:\n{}", missing.len(), missing.join("\n")));
+    }
+    // A cloned resource left with the original's name and folder packs the same file twice
+    let mut archive_names = HashSet::new();
+    for item in &state.resources {
+        let name = item.archive_filename()?;
+        if !archive_names.insert((&item.path, name.clone())) {
+            return Err(format!("{:?} is listed twice as {}; give the copy a different name or folder", item.path, name));
+        }
+    }
+
+    if state.sidecar_pack && state.pe_resource_archive {
+        return Err("Choose either a sidecar pack or PE resource storage, not both".to_string());
+    }
+    if state.sign_output {
+        if state.sign_certificate.trim().is_empty() {
+            return Err("Enter a .pfx file or certificate thumbprint to sign with".to_string());
+        }
+        // The packed EXE finds its other volumes through a trailer the signature would follow
+        if state.split_size_mb > 0 && !state.sidecar_pack && !state.pe_resource_archive {
+            return Err("Split output can't be signed, turn off splitting or signing".to_string());
+        }
+    }
+
+    // Check the icon before writing anything, rcedit's own errors say little
+    let icon_data = match &state.icon_path {
+        Some(icon_path) => {
+            let data = fs::read(icon_path)
+                .map_err(|e| format!("Failed to read icon file {:?}: {}", icon_path, e))?;
+            validate_icon(&data).map_err(|e| format!("Icon {:?} can't be used: {}", icon_path, e))?;
+            Some(data)
+        }
+        None => None,
+    };
+
+    // Read the stub binary for the selected architecture, or reuse the stub and
+    // embedded resources of an existing packed EXE when adding to it.
+    let base = match &state.base_exe {
+        Some(base_path) => {
+            let base_bytes = fs::read(base_path)
+                .map_err(|e| format!("Failed to read packed EXE {:?}: {}", base_path, e))?;
+            let parsed = parse_archive(&base_bytes)
+                .map_err(|e| format!("{:?} does not contain an appended archive: {}", base_path, e))?;
+            Some((base_bytes, parsed))
+        }
+        None => None,
+    };
+    let mut stub_bytes = match &base {
+        Some((base_bytes, parsed)) => base_bytes[..parsed.archive_start].to_vec(),
+        None => state.read_stub()?,
+    };
+    // Catch a stub path pointing at the wrong file before building something that won't run
+    check_stub(&stub_bytes)?;
+    // A signature (or, when adding to a signed packed EXE, its now dangling
+    // directory entry) can't survive the archive being appended
+    let signature_note = if state.stub_signature_survives() {
+        ""
+    } else if state.strip_stub_signature || state.sign_output {
+        strip_signature(&mut stub_bytes);
+        ""
+    } else if strip_signature(&mut stub_bytes.clone()) {
+        " (warning: the stub was signed, packing invalidated its signature)"
+    } else {
+        ""
+    };
+
+    // A tagged stub lists what it understands; refuse options it would ignore.
+    // Untagged (older) stubs are packed as before, the readiness panel warns.
+    if let Some(features) = read_stub_capabilities(&stub_bytes) {
+        let unsupported: Vec<&str> = state.required_capabilities().into_iter()
+            .filter(|(feature, _)| !features.iter().any(|f| f == feature))
+            .map(|(_, option)| option)
+            .collect();
+        if !unsupported.is_empty() {
+            return Err(format!("The stub does not support {}; rebuild stub.exe or turn these options off", unsupported.join(", ")));
+        }
+    }
+
+    match state.output_subsystem.as_str() {
+        "console" => set_pe_subsystem(&mut stub_bytes, IMAGE_SUBSYSTEM_WINDOWS_CUI)?,
+        "windows" => set_pe_subsystem(&mut stub_bytes, IMAGE_SUBSYSTEM_WINDOWS_GUI)?,
+        _ => {}
+    }
+
+    // UPX rewrites the PE layout, which the resource updates for the icon and
+    // PE resource storage can't cope with. Compression failing (no upx, stub
+    // already packed) isn't fatal, the original stub is used and noted.
+    let stub_note = if state.compress_stub {
+        if state.icon_path.is_some() || state.pe_resource_archive {
+            return Err("Compressing the stub can't be combined with a custom icon or PE resource storage".to_string());
+        }
+        let original_size = stub_bytes.len();
+        match compress_stub_with_upx(&stub_bytes) {
+            Ok(compressed) => {
+                stub_bytes = compressed;
+                format!(" (stub compressed from {} to {})", format_size(original_size as u64), format_size(stub_bytes.len() as u64))
+            }
+            Err(e) => format!(" (stub left uncompressed: {})", e),
+        }
+    } else {
+        String::new()
+    };
+
+    // Build the header with the extra fields.
+    let mut header = ArchiveHeader {
+        extraction_path: state.extraction_path.clone(),
+        main_file: state.main_file.clone(),
+        main_is_external: state.main_is_external,
+        resources: Vec::new(),
+        execution_style: state.execution_style.clone(),
+        launch_verb: state.launch_verb.clone(),
+        launch_args: state.launch_args.trim().to_string(),
+        dependencies: state.dependencies.iter()
+            .filter(|dependency| !dependency.path.trim().is_empty())
+            .cloned()
+            .collect(),
+        post_extract_command: state.post_extract_command.trim().to_string(),
+        post_extract_style: state.post_extract_style.clone(),
+        post_extract_abort_on_failure: state.post_extract_abort_on_failure,
+        working_dir: state.working_dir.trim().to_string(),
+        build_time: build_timestamp(state.stamp_build_time),
+        package_version: state.package_version.clone(),
+        capture_output: state.capture_output,
+        main_file_last: state.main_file_last,
+        compression_block_size: 0, // set below if seekable compression is kept
+        compressed_blocks: Vec::new(),
+        // Only an administrator can register a service
+        run_as_admin: state.run_as_admin || state.install_service,
+        is_compressed: state.compress_resources,  // Set the compression flag
+        show_progress: state.show_progress,
+        target_arch: state.target_arch.clone(),
+        log_level: state.log_level.clone(),
+        max_extract_size: state.max_extract_size_mb.min(MAX_EXTRACT_SIZE_MB).saturating_mul(1024 * 1024),
+        io_retries: state.io_retries,
+        clean_before_extract: state.clean_before_extract,
+        overwrite_policy: state.overwrite_policy.clone(),
+        update_mode: if state.update_mode == "always" { String::new() } else { state.update_mode.clone() },
+        launch_delay_ms: state.launch_delay_ms,
+        allow_uninstall: state.allow_uninstall,
+        single_instance: state.single_instance,
+        wait_for_exit: state.wait_for_exit,
+        data_crc32: None, // filled in once the resource data is final
+        admin_title: state.admin_title.clone(),
+        admin_message: state.admin_message.clone(),
+        eula_title: state.eula_title.trim().to_string(),
+        eula_text: if state.eula_text.trim().is_empty() { String::new() } else { state.eula_text.clone() },
+        messages: state.stub_messages.clone(),
+        shortcut: if state.shortcut_desktop || state.shortcut_start_menu {
+            Some(ShortcutOptions {
+                name: state.shortcut_name.clone(),
+                arguments: state.shortcut_args.clone(),
+                icon: state.shortcut_icon.clone(),
+                desktop: state.shortcut_desktop,
+                start_menu: state.shortcut_start_menu,
+            })
+        } else {
+            None
+        },
+        service: state.install_service.then(|| ServiceOptions {
+            name: state.service_name.trim().to_string(),
+            display_name: state.service_display_name.trim().to_string(),
+            description: state.service_description.trim().to_string(),
+            start_type: state.service_start_type.clone(),
+            arguments: state.service_args.clone(),
+            start_now: state.service_start_now,
+        }),
+    };
+
+    let packer = pack_resources(state, base.as_ref().map(|(_, parsed)| parsed), cancel)?;
+    header.resources = packer.entries;
+    // The main file must be launchable wherever it was packed from
+    if !header.main_is_external {
+        for entry in header.resources.iter_mut().filter(|e| e.filename == header.main_file) {
+            entry.executable = true;
+        }
+    }
+    let resource_data = packer.data;
+    let deduplicated_bytes = packer.deduplicated_bytes;
+
+    // Apply compression ONLY to resource data if enabled, and only keep the
+    // compressed form if it is actually smaller (already-compressed media can grow).
+    check_cancelled(cancel)?;
+    let mut compression_cached = false;
+    // Resources compressed on their own aren't compressed again as a whole
+    if state.uses_entry_compression() {
+        header.is_compressed = false;
+    }
+    let compressed = if header.is_compressed {
+        let cache_path = compression_cache_path(&state.output_exe);
+        let cached = if state.cache_compression {
+            load_cached_compression(&cache_path, &resource_data, state.seekable_compression)
+        } else {
+            None
+        };
+        let compressed = match cached {
+            Some(cached) => {
+                compression_cached = true;
+                cached
+            }
+            None => {
+                let (compressed, blocks) = compress_resource_data(&resource_data, state.seekable_compression, cancel)?;
+                if state.cache_compression {
+                    store_cached_compression(&cache_path, &resource_data, state.seekable_compression, &compressed, &blocks);
+                }
+                (compressed, blocks)
+            }
+        };
+        check_cancelled(cancel)?;
+        Some(compressed)
+    } else {
+        None
+    };
+    let final_resource_data = finish_resource_data(&mut header, resource_data, compressed);
+
+    // The archive data (header JSON followed by the possibly compressed resource
+    // bytes) must fit what the stub will read
+    if final_resource_data.len() > MAX_ARCHIVE_SIZE {
+        return Err(format!(
+            "The packed archive is {}, more than the {} the archive format can describe (its footer stores 32-bit lengths). Turn on compression or pack fewer files.",
+            format_size(final_resource_data.len() as u64), format_size(MAX_ARCHIVE_SIZE as u64)
+        ));
+    }
+
+    // Serialize the header (after compression so is_compressed is final) and
+    // assemble the archive: [header JSON] + [resource data] + [footer].
+    // The header is padded so aligned resource data is also aligned within the
+    // file it ends up in. Adding an icon or a PE resource rewrites the EXE
+    // afterwards, so only the archive offsets hold there.
+    let alignment = if !header.is_compressed && !state.pe_resource_archive && state.icon_path.is_none() {
+        state.resource_alignment as usize
+    } else {
+        1
+    };
+    let prefix = if state.sidecar_pack { 0 } else { stub_bytes.len() };
+    let pack_data = pack_archive_aligned(&header, &final_resource_data, prefix, alignment)?;
+
+    // Final output: [stub binary] + [archive data] + [footer]
+    // In sidecar mode the EXE is just the stub and [archive data] + [footer]
+    // go into a .rscpack file next to it, which the stub looks for at runtime.
+    // In PE resource mode they are stored as an RCDATA resource once the EXE is written.
+
+    let mut output_data = Vec::new();
+    output_data.extend_from_slice(&stub_bytes);
+    check_cancelled(cancel)?;
+    if state.sidecar_pack {
+        let pack_path = sidecar_path(&state.output_exe);
+        fs::write(&pack_path, &pack_data)
+            .map_err(|e| format!("Failed to write sidecar pack {:?}: {}", pack_path, e))?;
+        // Don't leave a pack without its EXE behind
+        if cancel.load(Ordering::Relaxed) {
+            let _ = fs::remove_file(&pack_path);
+            return Err(BUILD_CANCELLED.to_string());
+        }
+    } else if !state.pe_resource_archive {
+        output_data.extend_from_slice(&pack_data);
+    }
+
+    // Split appended builds into size-limited volumes
+    let split_size = (state.split_size_mb.min(MAX_SPLIT_SIZE_MB) as usize).saturating_mul(1024 * 1024);
+    let exe_path = std::path::Path::new(&state.output_exe);
+    let mut volumes = 1;
+    if split_size > 0 && !state.sidecar_pack && !state.pe_resource_archive && output_data.len() > split_size {
+        let (exe_data, parts) = split_output(&output_data, stub_bytes.len(), split_size)?;
+        for (i, part) in parts.iter().enumerate() {
+            let path = part_path(exe_path, i as u32 + 2);
+            fs::write(&path, part)
+                .map_err(|e| format!("Failed to write volume {:?}: {}", path, e))?;
+        }
+        volumes += parts.len() as u32;
+        output_data = exe_data;
+        // Don't leave parts without their EXE behind
+        if cancel.load(Ordering::Relaxed) {
+            for volume in 2..=volumes {
+                let _ = fs::remove_file(part_path(exe_path, volume));
+            }
+            return Err(BUILD_CANCELLED.to_string());
+        }
+    }
+    // Remove part files left over from an earlier build with more volumes
+    let mut stale = volumes + 1;
+    while fs::remove_file(part_path(exe_path, stale)).is_ok() {
+        stale += 1;
+    }
+
+    let compression_note = if state.compress_resources && !header.is_compressed {
+        " (stored uncompressed, compression did not reduce size)"
+    } else if compression_cached {
+        " (compressed data reused from the previous build)"
+    } else {
+        ""
+    };
+
+    let dedup_note = if deduplicated_bytes > 0 {
+        format!(" ({} of duplicate data stored once)", format_size(deduplicated_bytes))
+    } else {
+        String::new()
+    };
+
+    let sidecar_note = if state.sidecar_pack {
+        format!(" + {}", sidecar_path(&state.output_exe).to_string_lossy())
+    } else if volumes > 1 {
+        format!(" + {} part file(s)", volumes - 1)
+    } else {
+        String::new()
+    };
+
+    // Apply custom icon if specified
+    let icon_note = if let Some(icon_data) = &icon_data {
+        // Use resource_builder to inject the icon into the PE file
+        // This is a simplified approach; in a real application, you would use a proper
+        // Windows resource editor library to modify the PE resources
+        embed_icon_in_exe(&state.output_exe, &output_data, icon_data)?;
+        
+        " with custom icon"
+    } else {
+        // No custom icon, just write the file directly
+        fs::write(&state.output_exe, output_data)
+            .map_err(|e| format!("Failed to write output exe: {}", e))?;
+        
+        ""
+    };
+
+    if state.pe_resource_archive {
+        embed_archive_resource(&state.output_exe, &pack_data)?;
+    }
+
+    // Sign last, any later change to the file would invalidate the signature
+    let sign_note = if state.sign_output {
+        sign_output_exe(state)?;
+        ", signed"
+    } else {
+        ""
+    };
+
+    let verify_note = if state.verify_after_build {
+        let written = read_written_archive(state)?;
+        let verified = verify_archive(&written)
+            .map_err(|e| format!("Verification of {} failed: {}", state.output_exe, e))?;
+        format!(", verified {} resource(s)", verified.resources.len())
+    } else {
+        String::new()
+    };
+
+    Ok(format!("✅ Successfully created {}{}{}{}{}{}{}{}{}", state.output_exe, icon_note, sidecar_note, dedup_note, compression_note, stub_note, sign_note, verify_note, signature_note))
+}
+
+// Re-read the archive (data + footer) from wherever compile_exe stored it.
+fn read_written_archive(state: &AppState) -> Result<Vec<u8>, String> {
+    if state.sidecar_pack {
+        let pack_path = sidecar_path(&state.output_exe);
+        fs::read(&pack_path).map_err(|e| format!("Failed to re-read {:?}: {}", pack_path, e))
+    } else if state.pe_resource_archive {
+        read_archive_resource(&state.output_exe)
+    } else {
+        read_packed_file(std::path::Path::new(&state.output_exe))
+    }
+}
+
+// Read a packed EXE. Split builds are reassembled from the part files next to
+// it, so the result always ends with the archive footer.
+fn read_packed_file(path: &std::path::Path) -> Result<Vec<u8>, String> {
+    let mut bytes = fs::read(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    if bytes.len() < SPLIT_TRAILER_SIZE || !bytes.ends_with(SPLIT_MARKER) {
+        return Ok(bytes);
+    }
+    let trailer_start = bytes.len() - SPLIT_TRAILER_SIZE;
+    let volumes = read_u32_le(&bytes, trailer_start, "split trailer")?;
+    bytes.truncate(trailer_start);
+    for volume in 2..=volumes {
+        let part_path = part_path(path, volume);
+        let part = fs::read(&part_path)
+            .map_err(|e| format!("Missing volume {:?} (part {} of {}): {}", part_path, volume, volumes, e))?;
+        bytes.extend_from_slice(&part);
+    }
+    Ok(bytes)
+}
+
+// Path of volume `volume` (2, 3, ...) of a split build
+fn part_path(exe_path: &std::path::Path, volume: u32) -> PathBuf {
+    exe_path.with_extension(format!("part{}", volume))
+}
+
+// Split a packed EXE into volumes of at most `volume_size` bytes: the EXE (stub,
+// first chunk of the archive and the split trailer) and the remaining part files.
+fn split_output(output: &[u8], stub_len: usize, volume_size: usize) -> Result<(Vec<u8>, Vec<Vec<u8>>), String> {
+    let first_len = volume_size.saturating_sub(SPLIT_TRAILER_SIZE);
+    if first_len <= stub_len {
+        return Err(format!("Split size must be larger than the stub ({})", format_size(stub_len as u64)));
+    }
+    let parts: Vec<Vec<u8>> = output[first_len..].chunks(volume_size).map(|c| c.to_vec()).collect();
+    let mut exe = output[..first_len].to_vec();
+    exe.extend_from_slice(&(parts.len() as u32 + 1).to_le_bytes());
+    exe.extend_from_slice(SPLIT_MARKER);
+    Ok((exe, parts))
+}
+
+// Read the RCDATA archive resource back out of a written EXE.
+fn read_archive_resource(exe_path: &str) -> Result<Vec<u8>, String> {
+    #[cfg(windows)]
+    {
+        use std::ffi::OsStr;
+        use std::iter;
+        use std::os::windows::ffi::OsStrExt;
+        use winapi::um::libloaderapi::{
+            FindResourceW, FreeLibrary, LoadLibraryExW, LoadResource, LockResource, SizeofResource,
+            LOAD_LIBRARY_AS_DATAFILE,
+        };
+        use winapi::um::winuser::RT_RCDATA;
+
+        let wide_path: Vec<u16> = OsStr::new(exe_path)
+            .encode_wide()
+            .chain(iter::once(0))
+            .collect();
+        let wide_name: Vec<u16> = OsStr::new("RSCARCHIVE")
+            .encode_wide()
+            .chain(iter::once(0))
+            .collect();
+
+        unsafe {
+            // Load as a data file so nothing in the EXE runs
+            let module = LoadLibraryExW(wide_path.as_ptr(), std::ptr::null_mut(), LOAD_LIBRARY_AS_DATAFILE);
+            if module.is_null() {
+                return Err(format!("Failed to open {} for reading resources: {}", exe_path, std::io::Error::last_os_error()));
+            }
+            let resource = FindResourceW(module, wide_name.as_ptr(), RT_RCDATA);
+            let data = if resource.is_null() {
+                None
+            } else {
+                let size = SizeofResource(module, resource) as usize;
+                let loaded = LoadResource(module, resource);
+                let ptr = if loaded.is_null() { std::ptr::null_mut() } else { LockResource(loaded) };
+                if ptr.is_null() {
+                    None
+                } else {
+                    Some(std::slice::from_raw_parts(ptr as *const u8, size).to_vec())
+                }
+            };
+            FreeLibrary(module);
+            data.ok_or_else(|| format!("{} has no archive resource", exe_path))
+        }
+    }
+
+    #[cfg(not(windows))]
+    {
+        let _ = exe_path;
+        Err("PE resource storage is only supported on Windows".to_string())
+    }
+}
+
+// Parse an archive (header JSON + resource data + footer, as found at the end of
+// a packed EXE or pack file), decompressing the resource data if needed.
+fn parse_archive(pack: &[u8]) -> Result<ParsedArchive, String> {
+    let (header, archive_start, data_range) = unpack_header(pack)?;
+    let resource_bytes = &pack[data_range];
+    header.check_data_crc(resource_bytes)?;
+    let data = header.open_resource_data(resource_bytes, u64::MAX)?.into_bytes()?;
+
+    Ok(ParsedArchive { header, data, archive_start })
+}
+
+// Byte layout of a written output, for the layout view. Like --inspect, an
+// archive appended to the EXE is tried first, then the PE resource and sidecar
+// pack; offsets of those two are within the archive, not the EXE.
+fn output_layout(path: &str) -> Result<Vec<LayoutRegion>, String> {
+    let bytes = read_packed_file(Path::new(path))?;
+    match archive_layout(&bytes) {
+        Ok(regions) => Ok(regions),
+        Err(e) => {
+            let pack = read_archive_resource(path)
+                .or_else(|_| fs::read(sidecar_path(path)).map_err(|_| ()))
+                .map_err(|_| format!("{} does not contain an archive: {}", path, e))?;
+            Ok(archive_layout(&pack)?)
+        }
+    }
+}
+
+// Draw a layout as a proportional bar (every region at least a sliver wide) and
+// a table of offsets and sizes
+fn show_layout(ui: &mut egui::Ui, regions: &[LayoutRegion]) {
+    let color = |kind: RegionKind| match kind {
+        RegionKind::Stub => egui::Color32::from_rgb(110, 110, 120),
+        RegionKind::Header => egui::Color32::from_rgb(220, 170, 50),
+        RegionKind::Resource => egui::Color32::from_rgb(80, 140, 220),
+        RegionKind::Padding => egui::Color32::from_rgb(60, 60, 60),
+        RegionKind::CompressedData => egui::Color32::from_rgb(120, 80, 200),
+        RegionKind::Footer => egui::Color32::from_rgb(200, 70, 70),
+        RegionKind::Signature => egui::Color32::from_rgb(70, 170, 110),
+    };
+    let total = regions.last().map_or(0, |region| region.range.end).max(1) as f32;
+    let (bar, _) = ui.allocate_exact_size(Vec2::new(ui.available_width(), 24.0), egui::Sense::hover());
+    let mut x = bar.left();
+    for (index, region) in regions.iter().enumerate() {
+        let width = (region.range.len() as f32 / total * bar.width()).max(2.0);
+        let right = if index + 1 == regions.len() { bar.right() } else { (x + width).min(bar.right()) };
+        let rect = egui::Rect::from_min_max(egui::pos2(x, bar.top()), egui::pos2(right, bar.bottom()));
+        // Alternate shades so neighbouring resources stay distinguishable
+        let fill = if region.kind == RegionKind::Resource && index % 2 == 1 {
+            color(region.kind).linear_multiply(0.7)
+        } else {
+            color(region.kind)
+        };
+        ui.painter().rect_filled(rect, 0.0, fill);
+        x = right;
+    }
+
+    egui::Grid::new("output_layout")
+        .striped(true)
+        .show(ui, |ui| {
+            ui.strong("");
+            ui.strong("Region");
+            ui.strong("Offset");
+            ui.strong("Size");
+            ui.end_row();
+            for region in regions {
+                ui.colored_label(color(region.kind), "■");
+                ui.label(&region.label);
+                ui.monospace(format!("0x{:08X}", region.range.start));
+                ui.label(format!("{} ({} bytes)", format_size(region.range.len() as u64), region.range.len()));
+                ui.end_row();
+            }
+        });
+}
+
+// `--inspect <packed.exe>`: the archive header of a packed EXE as pretty JSON.
+// Nothing is extracted or decompressed. Appended archives are checked first,
+// then the PE resource and sidecar pack storage modes.
+fn inspect_archive(path: &str) -> Result<String, String> {
+    let bytes = read_packed_file(std::path::Path::new(path))?;
+    let header = match unpack_header(&bytes) {
+        Ok((header, _, _)) => header,
+        Err(e) => {
+            let pack = read_archive_resource(path)
+                .or_else(|_| fs::read(sidecar_path(path)).map_err(|_| ()))
+                .map_err(|_| format!("{} does not contain an archive: {}", path, e))?;
+            unpack_header(&pack)?.0
+        }
+    };
+    serde_json::to_string_pretty(&header).map_err(|e| format!("Failed to serialize header: {}", e))
+}
+
+// Run a command line subcommand if one was given, returning its exit code.
+// Without arguments the GUI starts as usual.
+fn run_cli() -> Option<i32> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.as_slice() {
+        [flag, build_args @ ..] if flag == "--build" => {
+            attach_parent_console();
+            let result = parse_build_args(build_args)
+                .and_then(|state| compile_exe(&state, &AtomicBool::new(false)));
+            match result {
+                Ok(message) => {
+                    println!("{}", message);
+                    Some(0)
+                }
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    Some(1)
+                }
+            }
+        }
+        [flag, path] if flag == "--inspect" => {
+            attach_parent_console();
+            match inspect_archive(path) {
+                Ok(json) => {
+                    println!("{}", json);
+                    Some(0)
+                }
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    Some(1)
+                }
+            }
+        }
+        _ => None,
+    }
+}
+
+// Settings for `--build`: an optional --project to start from, option flags
+// (see AppState::cli_command) and resource paths
+fn parse_build_args(args: &[String]) -> Result<AppState, String> {
+    let mut state = AppState::default();
+    let mut profile = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().cloned().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            // A build missing part of the project would pack the wrong thing
+            "--project" => {
+                let problems = state.load_project(Path::new(&value()?))?;
+                if !problems.is_empty() {
+                    return Err(problems.join("\n"));
+                }
+            }
+            "--output" => state.output_exe = value()?,
+            "--extract-to" => state.extraction_path = value()?,
+            "--main" => state.main_file = value()?,
+            // Applied after the loop, so it also overrides the profile saved with --project
+            "--profile" => profile = Some(value()?),
+            "--style" => {
+                let style = value()?;
+                if !["normal", "minimized", "maximized", "no-window"].contains(&style.as_str()) {
+                    return Err(format!("Unknown --style {:?}, expected normal, minimized, maximized or no-window", style));
+                }
+                state.execution_style = style;
+            }
+            "--compress" => state.compress_resources = true,
+            "--seekable" => {
+                state.compress_resources = true;
+                state.seekable_compression = true;
+            }
+            "--admin" => state.run_as_admin = true,
+            "--launch-admin" => state.launch_verb = "runas".to_string(),
+            "--icon" => state.icon_path = Some(PathBuf::from(value()?)),
+            "--sign" => {
+                state.sign_output = true;
+                state.sign_certificate = value()?;
+            }
+            // Read here rather than taken as a value, so it stays out of shell history and CI logs
+            "--sign-password-stdin" => {
+                let mut password = String::new();
+                std::io::stdin().read_line(&mut password)
+                    .map_err(|e| format!("Failed to read the signing password from stdin: {}", e))?;
+                state.sign_password = password.trim_end_matches(['\r', '\n']).to_string();
+            }
+            "--timestamp-url" => state.sign_timestamp_url = value()?,
+            "--arch" => {
+                let arch = value()?;
+                if arch != "x64" && arch != "x86" {
+                    return Err(format!("Unknown --arch {:?}, expected x64 or x86", arch));
+                }
+                state.target_arch = arch;
+            }
+            flag if flag.starts_with("--") => return Err(format!("Unknown option {}", flag)),
+            resource => {
+                let path = PathBuf::from(resource);
+                if !path.is_file() {
+                    return Err(format!("Resource {:?} does not exist", path));
+                }
+                // Already listed when it comes from --project as well
+                if !state.has_resource(&path) {
+                    state.resources.push(ResourceItem::new(path));
+                }
+            }
+        }
+    }
+    if state.resources.is_empty() && state.resource_zips.is_empty() && state.base_exe.is_none() {
+        return Err("No resources given".to_string());
+    }
+    if let Some(profile) = profile {
+        state.active_profile = profile;
+    }
+    if state.sign_password.is_empty() {
+        state.sign_password = std::env::var("RC_SIGN_PASSWORD").unwrap_or_default();
+    }
+    Ok(state)
+}
+
+// Quote a command line argument for cmd.exe / PowerShell when it needs it
+fn quote_arg(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '"', '&', '|', '<', '>', '^', '(', ')', ';', ',']) {
+        arg.to_string()
+    } else {
+        format!("\"{}\"", arg.replace('"', "\\\""))
+    }
+}
+
+// Status message after loading a project, listing anything that couldn't be restored
+fn with_load_problems(message: &str, problems: &[String]) -> String {
+    if problems.is_empty() {
+        message.to_string()
+    } else {
+        format!("⚠ {}, but:\n{}", message, problems.join("\n"))
+    }
+}
+
+// Starting state for the window: a project file given on the command line
+// (e.g. from a shortcut) is opened straight away.
+fn initial_state() -> AppState {
+    let mut state = AppState::default();
+    state.load_app_settings();
+    if !state.extraction_path_template.trim().is_empty() {
+        state.extraction_path = state.resolve_extraction_template();
+        state.saved_project = Some(state.project_json());
+    }
+    if let Some(path) = std::env::args().nth(1).filter(|arg| arg.ends_with(".rcproj")) {
+        match state.load_project(Path::new(&path)) {
+            Ok(problems) => state.message = with_load_problems(&format!("Opened project {}", path), &problems),
+            Err(e) => state.message = format!("❌ {}", e),
+        }
+    }
+    state
+}
+
+// settings.json under %APPDATA%\ResourceCompiler (or the XDG config folder elsewhere)
+fn app_settings_path() -> Option<PathBuf> {
+    let config_dir = std::env::var_os("APPDATA")
+        .or_else(|| std::env::var_os("XDG_CONFIG_HOME"))
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_dir.join("ResourceCompiler").join("settings.json"))
+}
+
+// Open a file in the application associated with its type, like double-clicking it
+fn open_with_default_app(path: &Path) -> Result<(), String> {
+    #[cfg(windows)]
+    {
+        use std::iter;
+        use std::os::windows::ffi::OsStrExt;
+        use winapi::um::shellapi::ShellExecuteW;
+        use winapi::um::winuser::SW_SHOWNORMAL;
+
+        let wide_path: Vec<u16> = path.as_os_str()
+            .encode_wide()
+            .chain(iter::once(0))
+            .collect();
+        let wide_verb: Vec<u16> = "open".encode_utf16().chain(iter::once(0)).collect();
+        // ShellExecute reports success with a value greater than 32
+        let result = unsafe {
+            ShellExecuteW(
+                std::ptr::null_mut(),
+                wide_verb.as_ptr(),
+                wide_path.as_ptr(),
+                std::ptr::null(),
+                std::ptr::null(),
+                SW_SHOWNORMAL,
+            )
+        };
+        if result as usize <= 32 {
+            return Err(format!("Failed to open {:?}: {}", path, std::io::Error::last_os_error()));
+        }
+        Ok(())
+    }
+
+    #[cfg(not(windows))]
+    {
+        use std::process::Command;
+        let opener = if cfg!(target_os = "macos") { "open" } else { "xdg-open" };
+        Command::new(opener)
+            .arg(path)
+            .spawn()
+            .map(|_| ())
+            .map_err(|e| format!("Failed to open {:?} with {}: {}", path, opener, e))
+    }
+}
+
+// Open the folder containing a file, with the file selected where the file
+// manager supports it
+fn show_in_folder(path: &Path) -> Result<(), String> {
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        use std::process::Command;
+        // explorer wants /select,"path" as one argument, so it can't go through arg()
+        Command::new("explorer")
+            .raw_arg(format!("/select,\"{}\"", path.display()))
+            .spawn()
+            .map(|_| ())
+            .map_err(|e| format!("Failed to open Explorer for {:?}: {}", path, e))
+    }
+
+    #[cfg(not(windows))]
+    {
+        use std::process::Command;
+        if cfg!(target_os = "macos") {
+            Command::new("open").arg("-R").arg(path).spawn()
+                .map(|_| ())
+                .map_err(|e| format!("Failed to reveal {:?}: {}", path, e))
+        } else {
+            open_with_default_app(path.parent().unwrap_or(Path::new(".")))
+        }
+    }
+}
+
+// The GUI is a windows-subsystem app with no console of its own; attach to the
+// console of the shell that started us so CLI output is visible there.
+fn attach_parent_console() {
+    #[cfg(windows)]
+    unsafe {
+        winapi::um::wincon::AttachConsole(winapi::um::wincon::ATTACH_PARENT_PROCESS);
+    }
+}
+
+// Parse an archive and check that every resource's offset and size fit the data.
+fn verify_archive(pack: &[u8]) -> Result<ArchiveHeader, String> {
+    let parsed = parse_archive(pack)?;
+
+    parsed.header.check_layout(parsed.data.len())?;
+
+    Ok(parsed.header)
+}
+
+// Filename a resource is stored under in the header. The header is UTF-8 JSON,
+// so names that can't be represented exactly are rejected instead of being
+// silently mangled (e.g. unpaired UTF-16 surrogates on Windows).
+fn resource_filename(path: &std::path::Path) -> Result<String, String> {
+    let name = path.file_name().ok_or("Invalid resource file name")?;
+    name.to_str()
+        .map(|n| n.to_string())
+        .ok_or_else(|| format!("Resource filename {:?} is not valid Unicode and can't be stored", name))
+}
+
+// A target name must be a plain filename so the resource stays in its folder
+fn check_target_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\', ':']) {
+        return Err(format!("Target name {:?} must be a plain filename", name));
+    }
+    Ok(())
+}
+
+// One line of a resource manifest
+#[derive(Deserialize)]
+struct ManifestEntry {
+    source: PathBuf,
+    #[serde(default)]
+    target: Option<String>, // archive name, optionally with folders (e.g. config/app.ini)
+    #[serde(default)]
+    compress: bool,
+    #[serde(default)]
+    main: bool,
+}
+
+// Read a manifest: a JSON array of entries, or CSV with the columns
+// source,target,compress,main (an optional header row may reorder them).
+fn read_manifest(path: &Path) -> Result<Vec<ManifestEntry>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Failed to read manifest {:?}: {}", path, e))?;
+    if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json")) {
+        return serde_json::from_str(&text).map_err(|e| format!("Invalid manifest {:?}: {}", path, e));
+    }
+
+    let mut columns = vec!["source".to_string(), "target".to_string(), "compress".to_string(), "main".to_string()];
+    let mut entries = Vec::new();
+    for (line_no, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields = split_csv_line(line);
+        if entries.is_empty() && fields.first().is_some_and(|f| f.eq_ignore_ascii_case("source")) {
+            columns = fields.iter().map(|f| f.to_ascii_lowercase()).collect();
+            continue;
+        }
+
+        let field = |column: &str| columns.iter().position(|c| c == column)
+            .and_then(|i| fields.get(i).map(String::as_str))
+            .filter(|f| !f.is_empty());
+        let flag = |column: &str| -> Result<bool, String> {
+            match field(column).map(str::to_ascii_lowercase).as_deref() {
+                None | Some("0") | Some("false") | Some("no") => Ok(false),
+                Some("1") | Some("true") | Some("yes") | Some("x") => Ok(true),
+                Some(other) => Err(format!("Line {}: {:?} is not a valid {} flag", line_no + 1, other, column)),
+            }
+        };
+        let source = field("source").ok_or_else(|| format!("Line {}: missing source path", line_no + 1))?;
+        entries.push(ManifestEntry {
+            source: PathBuf::from(source),
+            target: field("target").map(str::to_string),
+            compress: flag("compress")?,
+            main: flag("main")?,
+        });
+    }
+    Ok(entries)
+}
+
+// Fields of one CSV line. A field in double quotes may contain commas, and ""
+// inside it stands for a quote; whitespace around fields is dropped.
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.trim().is_empty() => {
+                field.clear();
+                quoted = true;
+            }
+            ',' if !quoted => fields.push(std::mem::take(&mut field).trim().to_string()),
+            c => field.push(c),
+        }
+    }
+    fields.push(field.trim().to_string());
+    fields
+}
+
+// Split a manifest target into its subfolder (if any) and filename
+fn split_manifest_target(target: &str) -> Result<(Option<String>, String), String> {
+    let (subdir, name) = match target.rsplit_once(['/', '\\']) {
+        Some((subdir, name)) => (Some(subdir.to_string()).filter(|s| !s.is_empty()), name.to_string()),
+        None => (None, target.to_string()),
+    };
+    check_target_name(&name)?;
+    if let Some(subdir) = &subdir
+        && subdir.split(['/', '\\']).any(|part| part == ".." || part.contains(':'))
+    {
+        return Err(format!("Target {:?} must stay inside the extraction path", target));
+    }
+    Ok((subdir, name))
+}
+
+// Preview of the start of a resource: the text itself if it looks like UTF-8
+// text, otherwise a hex dump of the first bytes.
+fn resource_preview(path: &std::path::Path) -> String {
+    use std::io::Read;
+
+    let mut sample = Vec::new();
+    let read = fs::File::open(path)
+        .and_then(|file| file.take(PREVIEW_BYTES as u64).read_to_end(&mut sample));
+    if let Err(e) = read {
+        return format!("Failed to read {:?}: {}", path, e);
+    }
+
+    // A multi-byte character cut off at the end of the sample is still text
+    let text = match std::str::from_utf8(&sample) {
+        Ok(text) => Some(text),
+        Err(e) if e.error_len().is_none() => std::str::from_utf8(&sample[..e.valid_up_to()]).ok(),
+        Err(_) => None,
+    };
+    if let Some(text) = text.filter(|text| !text.contains('\0')) {
+        return text.to_string();
+    }
+
+    let mut dump = String::new();
+    for (row, chunk) in sample[..sample.len().min(HEX_PREVIEW_BYTES)].chunks(16).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+        let ascii: String = chunk.iter()
+            .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+            .collect();
+        dump.push_str(&format!("{:08x}  {:<47}  {}\n", row * 16, hex.join(" "), ascii));
+    }
+    dump
+}
+
+// egui's bundled fonts only cover Latin and a few symbols, so add system fonts
+// as fallbacks to display CJK and other non-Latin resource names.
+fn install_fallback_fonts(ctx: &egui::Context) {
+    let font_dir = std::env::var_os("WINDIR")
+        .map(|dir| PathBuf::from(dir).join("Fonts"))
+        .unwrap_or_default();
+    let candidates = [
+        font_dir.join("YuGothM.ttc"),
+        font_dir.join("meiryo.ttc"),
+        font_dir.join("msgothic.ttc"),
+        font_dir.join("msyh.ttc"),
+        font_dir.join("malgun.ttf"),
+        font_dir.join("seguisym.ttf"),
+        PathBuf::from("/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc"),
+        PathBuf::from("/System/Library/Fonts/Hiragino Sans GB.ttc"),
+    ];
+
+    let mut fonts = egui::FontDefinitions::default();
+    for path in candidates.iter().filter(|p| p.is_file()) {
+        if let Ok(bytes) = fs::read(path) {
+            let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+            fonts.font_data.insert(name.clone(), egui::FontData::from_owned(bytes));
+            for family in [egui::FontFamily::Proportional, egui::FontFamily::Monospace] {
+                fonts.families.entry(family).or_default().push(name.clone());
+            }
+        }
+    }
+    ctx.set_fonts(fonts);
+}
+
+// Whether the source file has an executable bit set; Windows has no such bit.
+fn is_executable(metadata: &fs::Metadata) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        metadata.permissions().mode() & 0o111 != 0
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        false
+    }
+}
+
+// Windows file attributes of a resource. Elsewhere only read-only maps across.
+fn file_attributes(metadata: &fs::Metadata) -> u32 {
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+        metadata.file_attributes()
+    }
+
+    #[cfg(not(windows))]
+    {
+        // FILE_ATTRIBUTE_READONLY
+        if metadata.permissions().readonly() { 0x1 } else { 0 }
+    }
+}
+
+// Bail out of compile_exe if the user pressed Cancel. Checked between steps,
+// before anything is written to the output path.
+fn check_cancelled(cancel: &AtomicBool) -> Result<(), String> {
+    if cancel.load(Ordering::Relaxed) {
+        Err(BUILD_CANCELLED.to_string())
+    } else {
+        Ok(())
+    }
+}
+
+// ISO 8601 build time for the header. SOURCE_DATE_EPOCH (the reproducible
+// builds convention) is used whenever it is set, since it keeps the output
+// byte-identical; otherwise the clock, only if `stamp` asks for it.
+fn build_timestamp(stamp: bool) -> String {
+    let fixed = std::env::var("SOURCE_DATE_EPOCH").ok()
+        .and_then(|secs| secs.trim().parse::<i64>().ok())
+        .and_then(|secs| chrono::DateTime::from_timestamp(secs, 0));
+    match fixed {
+        Some(time) => time.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        None if stamp => chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        None => String::new(),
+    }
+}
+
+// Packed EXE size with and without compression, see compare_compression
+#[derive(Clone)]
+struct CompressionComparison {
+    uncompressed_size: u64,
+    compressed_size: u64,
+    decompress_time: std::time::Duration, // time this machine took to inflate the compressed data
+}
+
+// Totals for one file extension in the compression report.
+#[derive(Clone)]
+struct CompressionStat {
+    extension: String,
+    files: usize,
+    original_size: u64,
+    compressed_size: u64,
+}
+
+// Read the resources (and any carried over from a base packed EXE) into a packer.
+fn pack_resources(state: &AppState, base: Option<&ParsedArchive>, cancel: &AtomicBool) -> Result<ResourcePacker, String> {
+    use std::io::Read;
+
+    let new_filenames: Vec<String> = state.resources.iter()
+        .filter_map(|r| r.archive_filename().ok())
+        .chain(state.resource_zips.iter().flat_map(|z| z.entries.iter().cloned()))
+        .collect();
+
+    let mut packer = ResourcePacker::new(state.resource_alignment as usize);
+    let entry_compression = state.uses_entry_compression();
+
+    // Carry over resources already embedded in the base EXE, unless a newly
+    // added resource with the same filename replaces them.
+    if let Some(parsed) = base {
+        for (entry, start) in parsed.header.resources.iter().zip(parsed.header.resource_starts()) {
+            if new_filenames.contains(&entry.filename) {
+                continue;
+            }
+            // Resources compressed on their own are carried over as they are stored
+            let data = parsed.data.get(start..start + entry.stored_len())
+                .ok_or_else(|| format!("Resource {} runs past the end of the base EXE's data", entry.filename))?;
+            packer.add(entry.filename.clone(), data, entry.modified, entry.attributes)?;
+            if let Some(added) = packer.entries.last_mut() {
+                added.executable = entry.executable;
+                added.stored_size = entry.stored_size;
+                added.size = entry.size;
+            }
+        }
+    }
+
+    // Stream each resource file into the archive data. Compressing a resource on
+    // its own needs all of it in memory, so those are read in one go.
+    for item in &state.resources {
+        check_cancelled(cancel)?;
+        let res_path = &item.path;
+        let mut file = fs::File::open(res_path)
+            .map_err(|e| format!("Failed to read resource {:?}: {}", res_path, e))?;
+        let filename = item.archive_filename()?;
+        let metadata = file.metadata().ok();
+        let modified = metadata.as_ref()
+            .and_then(|m| m.modified().ok())
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_millis() as u64);
+        let attributes = if state.preserve_attributes {
+            metadata.as_ref().map(file_attributes)
+        } else {
+            None
+        };
+        let size_hint = metadata.as_ref().map_or(0, |m| m.len() as usize);
+        if entry_compression {
+            let mut data = Vec::with_capacity(size_hint);
+            file.read_to_end(&mut data)
+                .map_err(|e| format!("Failed to read resource {:?}: {}", res_path, e))?;
+            packer.add_compressed(filename, &data, modified, attributes, state.entry_compression(item))?;
+        } else {
+            packer.add_from_reader(filename, &mut file, size_hint, modified, attributes)?;
+        }
+        if let Some(added) = packer.entries.last_mut() {
+            added.executable = metadata.as_ref().is_some_and(is_executable);
+        }
+    }
+
+    // Stream the files inside each added zip into the archive. Individually added
+    // resources and earlier zips win if the same name appears more than once.
+    let mut packed_names: HashSet<String> = state.resources.iter()
+        .filter_map(|r| r.archive_filename().ok())
+        .collect();
+    for resource_zip in &state.resource_zips {
+        let file = fs::File::open(&resource_zip.path)
+            .map_err(|e| format!("Failed to open {:?}: {}", resource_zip.path, e))?;
+        let mut archive = zip::ZipArchive::new(file)
+            .map_err(|e| format!("{:?} is not a valid zip file: {}", resource_zip.path, e))?;
+        for i in 0..archive.len() {
+            check_cancelled(cancel)?;
+            let mut entry = archive.by_index(i)
+                .map_err(|e| format!("Failed to read entry {} of {:?}: {}", i, resource_zip.path, e))?;
+            let Some(filename) = zip_entry_name(&entry) else {
+                continue;
+            };
+            if !packed_names.insert(filename.clone()) {
+                continue;
+            }
+            let modified = zip_modified_millis(entry.last_modified());
+            let executable = entry.unix_mode().is_some_and(|mode| mode & 0o111 != 0);
+            let size_hint = entry.size() as usize;
+            let added = if entry_compression {
+                let mut data = Vec::with_capacity(size_hint);
+                entry.read_to_end(&mut data)
+                    .map_err(|e| format!("Failed to read {} (in {:?}): {}", filename, resource_zip.path, e))?;
+                let compression = if state.compress_resources { EntryCompression::Auto } else { EntryCompression::Store };
+                packer.add_compressed(filename, &data, modified, None, compression)
+            } else {
+                packer.add_from_reader(filename, &mut entry, size_hint, modified, None)
+            };
+            added.map_err(|e| format!("{} (in {:?})", e, resource_zip.path))?;
+            if let Some(added) = packer.entries.last_mut() {
+                added.executable = executable;
+            }
+        }
+    }
+    Ok(packer)
+}
+
+// Gzip resource data in one stream, or with `seekable` in independent
+// COMPRESSION_BLOCK_SIZE blocks, returning each block's compressed length so
+// any block can be found and inflated on its own.
+fn compress_resource_data(data: &[u8], seekable: bool, cancel: &AtomicBool) -> Result<(Vec<u8>, Vec<u32>), String> {
+    if !seekable {
+        return Ok((gzip_compress(data)?, Vec::new()));
+    }
+    let mut compressed = Vec::new();
+    let mut blocks = Vec::new();
+    for block in data.chunks(COMPRESSION_BLOCK_SIZE) {
+        check_cancelled(cancel)?;
+        let block = gzip_compress(block)?;
+        blocks.push(block.len() as u32);
+        compressed.extend_from_slice(&block);
+    }
+    Ok((compressed, blocks))
+}
+
+// Where the compressed data of the last build of `output` is cached: one file
+// per output EXE in the temp folder, overwritten by each build.
+fn compression_cache_path(output: &str) -> PathBuf {
+    use sha2::{Digest, Sha256};
+    let output = Path::new(output).canonicalize().unwrap_or_else(|_| PathBuf::from(output));
+    let digest = Sha256::digest(output.to_string_lossy().as_bytes());
+    let name: String = digest[..8].iter().map(|b| format!("{:02x}", b)).collect();
+    std::env::temp_dir().join("ResourceCompiler").join(format!("{}.cache", name))
+}
+
+// Identifies the uncompressed data and the settings it was compressed with:
+// a SHA-256 of the packed bytes themselves rather than source mtimes, so any
+// change to a file, its name, order or the packing options misses the cache.
+fn compression_cache_key(data: &[u8], seekable: bool) -> Vec<u8> {
+    use sha2::{Digest, Sha256};
+    let mut hasher = Sha256::new();
+    hasher.update(data);
+    hasher.update([seekable as u8]);
+    hasher.update((COMPRESSION_BLOCK_SIZE as u32).to_le_bytes());
+
+    let mut key = b"RSCCACHE2".to_vec();
+    key.extend_from_slice(&hasher.finalize());
+    key.extend_from_slice(&(data.len() as u64).to_le_bytes());
+    key
+}
+
+// Cache file layout: [key][block count u32][block lengths u32...][compressed data]
+fn load_cached_compression(cache_path: &Path, data: &[u8], seekable: bool) -> Option<(Vec<u8>, Vec<u32>)> {
+    let cached = fs::read(cache_path).ok()?;
+    let key = compression_cache_key(data, seekable);
+    let rest = cached.strip_prefix(key.as_slice())?;
+    let block_count = u32::from_le_bytes(rest.get(..4)?.try_into().ok()?) as usize;
+    let blocks_end = 4 + block_count * 4;
+    let blocks = rest.get(4..blocks_end)?
+        .chunks_exact(4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect::<Vec<_>>();
+    let compressed = rest[blocks_end..].to_vec();
+    if !blocks.is_empty() && blocks.iter().map(|&b| b as usize).sum::<usize>() != compressed.len() {
+        return None;
+    }
+    Some((compressed, blocks))
+}
+
+// Best effort: a build never fails because the cache couldn't be written
+fn store_cached_compression(cache_path: &Path, data: &[u8], seekable: bool, compressed: &[u8], blocks: &[u32]) {
+    let mut cached = compression_cache_key(data, seekable);
+    cached.extend_from_slice(&(blocks.len() as u32).to_le_bytes());
+    for block in blocks {
+        cached.extend_from_slice(&block.to_le_bytes());
+    }
+    cached.extend_from_slice(compressed);
+    if let Some(dir) = cache_path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    let _ = fs::write(cache_path, cached);
+}
+
+// Build the archive data in memory with and without compression and report the
+// resulting EXE sizes. Nothing is written; the header size is estimated and the
+// extraction cost of compression is measured by inflating the data here.
+fn compare_compression(state: &AppState, cancel: &AtomicBool) -> Result<CompressionComparison, String> {
+    use std::io::Read;
+
+    let base = match &state.base_exe {
+        Some(base_path) => {
+            let base_bytes = fs::read(base_path)
+                .map_err(|e| format!("Failed to read packed EXE {:?}: {}", base_path, e))?;
+            Some(parse_archive(&base_bytes)?)
+        }
+        None => None,
+    };
+    let stub_size = match &base {
+        Some(parsed) => parsed.archive_start as u64,
+        None => state.read_stub()?.len() as u64,
+    };
+    let packer = pack_resources(state, base.as_ref(), cancel)?;
+    let header_size = serde_json::to_vec(&packer.entries)
+        .map_err(|e| format!("Failed to serialize header: {}", e))?
+        .len() as u64;
+    let overhead = stub_size + header_size + FOOTER_SIZE as u64;
+
+    let (compressed, _) = compress_resource_data(&packer.data, state.seekable_compression, cancel)?;
+    // MultiGzDecoder reads seekable (multi-block) data as well as a single stream
+    let started = std::time::Instant::now();
+    let mut inflated = Vec::with_capacity(packer.data.len());
+    flate2::read::MultiGzDecoder::new(&compressed[..]).read_to_end(&mut inflated)
+        .map_err(|e| format!("Compressed data failed to decompress: {}", e))?;
+    let decompress_time = started.elapsed();
+
+    Ok(CompressionComparison {
+        uncompressed_size: overhead + packer.data.len() as u64,
+        compressed_size: overhead + compressed.len() as u64,
+        decompress_time,
+    })
+}
+
+// Trial-compress every resource and total the results per extension, largest first.
+fn compression_report(resources: &[PathBuf]) -> Result<Vec<CompressionStat>, String> {
+    let mut stats: BTreeMap<String, CompressionStat> = BTreeMap::new();
+    for path in resources {
+        let data = fs::read(path)
+            .map_err(|e| format!("Failed to read resource {:?}: {}", path, e))?;
+        let compressed = gzip_compress(&data)?;
+        let extension = path.extension()
+            .map_or_else(|| "(none)".to_string(), |e| e.to_string_lossy().to_lowercase());
+        let stat = stats.entry(extension.clone()).or_insert(CompressionStat {
+            extension,
+            files: 0,
+            original_size: 0,
+            compressed_size: 0,
+        });
+        stat.files += 1;
+        stat.original_size += data.len() as u64;
+        stat.compressed_size += compressed.len() as u64;
+    }
+    let mut stats: Vec<CompressionStat> = stats.into_values().collect();
+    stats.sort_by_key(|s| std::cmp::Reverse(s.original_size));
+    Ok(stats)
+}
+
+// Human readable byte count, e.g. "1.5 MB"
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+// The console build of a stub sits next to it with a _console suffix,
+// e.g. stub.exe -> stub_console.exe
+fn console_stub_path(stub_path: &str) -> String {
+    let path = PathBuf::from(stub_path);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    let file_name = match path.extension() {
+        Some(ext) => format!("{}_console.{}", stem, ext.to_string_lossy()),
+        None => format!("{}_console", stem),
+    };
+    path.with_file_name(file_name).to_string_lossy().to_string()
+}
+
+// The sidecar pack lives next to the output EXE with the same name and a .rscpack extension.
+fn sidecar_path(output_exe: &str) -> PathBuf {
+    PathBuf::from(output_exe).with_extension("rscpack")
+}
+
+// Overwrite the Subsystem field of a PE image, e.g. to give the packed EXE a console.
+fn set_pe_subsystem(exe: &mut [u8], subsystem: u16) -> Result<(), String> {
+    if !exe.starts_with(b"MZ") || exe.len() < 0x40 {
+        return Err("Stub is not a Windows executable (no MZ header)".to_string());
+    }
+    let pe_offset = u32::from_le_bytes([exe[0x3C], exe[0x3D], exe[0x3E], exe[0x3F]]) as usize;
+    if exe.get(pe_offset..pe_offset + 4) != Some(&b"PE\0\0"[..]) {
+        return Err("Stub is not a Windows executable (no PE header)".to_string());
+    }
+    // Signature (4 bytes) + file header (20 bytes), then Subsystem sits at offset
+    // 68 of the optional header in both PE32 and PE32+ images
+    let field = pe_offset + 4 + 20 + 68;
+    exe.get_mut(field..field + 2)
+        .ok_or_else(|| "Stub PE header is truncated".to_string())?
+        .copy_from_slice(&subsystem.to_le_bytes());
+    Ok(())
+}
+
+// Features listed in a stub's capabilities tag, or None for stubs without one
+fn read_stub_capabilities(stub: &[u8]) -> Option<Vec<String>> {
+    let start = stub.windows(CAPABILITIES_MARKER.len())
+        .position(|w| w == CAPABILITIES_MARKER)? + CAPABILITIES_MARKER.len();
+    let length = stub[start..].iter().take(1024).position(|&b| b == b';')?;
+    let list = std::str::from_utf8(&stub[start..start + length]).ok()?;
+    Some(list.split(',').map(str::trim).filter(|f| !f.is_empty()).map(str::to_string).collect())
+}
+
+// Icon and label for a file's type: by extension, or by its first bytes when
+// the extension says nothing (no extension, .bin, .dat, ...)
+fn detect_file_type(path: &Path) -> (&'static str, &'static str) {
+    use std::io::Read;
+
+    const IMAGE: (&str, &str) = ("🖼", "Image");
+    const TEXT: (&str, &str) = ("📄", "Text");
+    const DOCUMENT: (&str, &str) = ("📑", "Document");
+    const EXECUTABLE: (&str, &str) = ("⚙", "Executable");
+    const LIBRARY: (&str, &str) = ("🔧", "Library");
+    const ARCHIVE: (&str, &str) = ("📦", "Archive");
+    const AUDIO: (&str, &str) = ("🎵", "Audio");
+    const VIDEO: (&str, &str) = ("🎞", "Video");
+    const DATA: (&str, &str) = ("🗋", "Data");
+
+    let extension = path.extension().map(|ext| ext.to_string_lossy().to_lowercase()).unwrap_or_default();
+    match extension.as_str() {
+        "png" | "jpg" | "jpeg" | "gif" | "bmp" | "ico" | "svg" | "webp" | "tif" | "tiff" => return IMAGE,
+        "txt" | "md" | "ini" | "cfg" | "conf" | "json" | "xml" | "yaml" | "yml" | "toml" | "csv" | "log" | "html" | "htm" | "css" | "js" => return TEXT,
+        "pdf" | "doc" | "docx" | "rtf" | "xls" | "xlsx" | "ppt" | "pptx" | "odt" | "chm" => return DOCUMENT,
+        "exe" | "com" | "bat" | "cmd" | "ps1" | "vbs" | "msi" | "jar" | "py" => return EXECUTABLE,
+        "dll" | "sys" | "ocx" | "so" | "dylib" => return LIBRARY,
+        "zip" | "7z" | "rar" | "gz" | "tar" | "xz" | "bz2" | "cab" | "rscpack" => return ARCHIVE,
+        "wav" | "mp3" | "ogg" | "flac" | "aac" | "m4a" | "wma" => return AUDIO,
+        "mp4" | "mkv" | "avi" | "mov" | "wmv" | "webm" => return VIDEO,
+        _ => {}
+    }
+
+    let mut head = [0u8; 512];
+    let len = fs::File::open(path).and_then(|mut file| file.read(&mut head)).unwrap_or(0);
+    let head = &head[..len];
+    if head.starts_with(b"MZ") {
+        EXECUTABLE
+    } else if [&b"\x89PNG"[..], b"GIF8", b"\xFF\xD8\xFF", b"BM"].iter().any(|magic| head.starts_with(magic)) {
+        IMAGE
+    } else if [&b"PK\x03\x04"[..], b"7z\xBC\xAF", b"\x1F\x8B", b"Rar!"].iter().any(|magic| head.starts_with(magic)) {
+        ARCHIVE
+    } else if head.starts_with(b"%PDF") {
+        DOCUMENT
+    } else if [&b"ID3"[..], b"OggS", b"fLaC"].iter().any(|magic| head.starts_with(magic)) {
+        AUDIO
+    } else if !head.is_empty() && looks_like_text(head) {
+        TEXT
+    } else {
+        DATA
+    }
+}
+
+// UTF-8 without NUL bytes; `head` may end part way through a character
+fn looks_like_text(head: &[u8]) -> bool {
+    let valid = match std::str::from_utf8(head) {
+        Ok(text) => text,
+        Err(e) if e.error_len().is_none() => std::str::from_utf8(&head[..e.valid_up_to()]).unwrap_or_default(),
+        Err(_) => return false,
+    };
+    !valid.contains('\0')
+}
+
+// Archive name of a zip entry: its path inside the zip joined with '/'. Folders
+// and entries whose path would escape the extraction directory are skipped.
+fn zip_entry_name(entry: &zip::read::ZipFile) -> Option<String> {
+    if entry.is_dir() {
+        return None;
+    }
+    let parts: Vec<String> = entry.enclosed_name()?
+        .components()
+        .filter_map(|c| match c {
+            std::path::Component::Normal(part) => Some(part.to_string_lossy().to_string()),
+            _ => None,
+        })
+        .collect();
+    if parts.is_empty() { None } else { Some(parts.join("/")) }
+}
 
-                    // --- Main File (by filename) ---
-                    ui.horizontal(|ui| {
-                        ui.label("Main File:");
-                        ui.text_edit_singleline(&mut self.main_file);
-                        ui.label("(Select a resource below to set)");
-                    });
+// Zip timestamps are stored in local time without a zone
+fn zip_modified_millis(time: zip::DateTime) -> Option<u64> {
+    let local = chrono::NaiveDate::from_ymd_opt(time.year() as i32, time.month() as u32, time.day() as u32)?
+        .and_hms_opt(time.hour() as u32, time.minute() as u32, time.second() as u32)?
+        .and_local_timezone(chrono::Local)
+        .single()?;
+    u64::try_from(local.timestamp_millis()).ok()
+}
 
-                    // --- Execution Style Selection ---
-                    ui.horizontal(|ui| {
-                        ui.label("Execution Style:");
-                        egui::ComboBox::from_label("")
-                            .selected_text(match self.execution_style.as_str() {
-                                "no-window" => "No Window",
-                                "minimized" => "Minimized",
-                                "normal" => "Normal",
-                                "maximized" => "Maximized",
-                                _ => "Normal"
-                            })
-                            .show_ui(ui, |ui| {
-                                ui.selectable_value(&mut self.execution_style, "no-window".to_string(), "No Window");
-                                ui.selectable_value(&mut self.execution_style, "minimized".to_string(), "Minimized");
-                                ui.selectable_value(&mut self.execution_style, "normal".to_string(), "Normal");
-                                ui.selectable_value(&mut self.execution_style, "maximized".to_string(), "Maximized");
-                            });
-                    });
+// `path` relative to the folder `base` (both absolute), going up with ".." where
+// needed. None when they share no root, e.g. different drives.
+fn relative_path(path: &Path, base: &Path) -> Option<PathBuf> {
+    use std::path::Component;
+    if !path.is_absolute() {
+        return None;
+    }
+    let mut path_parts = path.components().peekable();
+    let mut base_parts = base.components().peekable();
+    let mut shared = 0;
+    while let (Some(a), Some(b)) = (path_parts.peek(), base_parts.peek()) {
+        if a != b {
+            break;
+        }
+        path_parts.next();
+        base_parts.next();
+        shared += 1;
+    }
+    // Only the drive/root in common is no better than the absolute path
+    if path.components().take(shared).all(|c| matches!(c, Component::Prefix(_) | Component::RootDir)) {
+        return None;
+    }
+    let mut relative: PathBuf = base_parts.map(|_| Component::ParentDir).collect();
+    relative.extend(path_parts);
+    Some(relative)
+}
 
-                    // --- Run as Administrator Toggle ---
-                    ui.horizontal(|ui| {
-                        ui.checkbox(&mut self.run_as_admin, "Run as Administrator");
-                    });
-                });
+// A path read from a project file: absolute ones as stored, relative ones joined
+// to the project's folder with "." and ".." resolved
+fn resolve_project_path(project_dir: &Path, stored: &str) -> PathBuf {
+    use std::path::Component;
+    let stored = Path::new(stored);
+    if stored.is_absolute() {
+        return stored.to_path_buf();
+    }
+    let mut resolved = project_dir.to_path_buf();
+    for component in stored.components() {
+        match component {
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::CurDir => {}
+            other => resolved.push(other),
+        }
+    }
+    resolved
+}
 
-            ui.add_space(10.0);
-            
-            // Resources section with improved appearance
-            egui::Frame::default()
-                .fill(ui.style().visuals.faint_bg_color)
-                .rounding(10.0)
-                .inner_margin(10.0)
-                .show(ui, |ui| {
-                    ui.horizontal(|ui| {
-                        ui.heading("Resources");
-                        
-                        // Move the search to the header row
-                        if !self.resources.is_empty() {
-                            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                                if ui.button("🔍 Search").clicked() {
-                                    // Handle search button click if needed
-                                }
-                                ui.text_edit_singleline(&mut self.search_query);
-                                ui.label("Search:");
-                            });
-                        }
-                    });
-                    
-                    ui.add_space(5.0);
-                    
-                    // Always show the Add Resource button at the top
-                    if ui.button("📂 Add Resource").clicked() {
-                        if let Some(file) = rfd::FileDialog::new().pick_file() {
-                            if !self.resources.contains(&file) {
-                                self.resources.push(file);
-                            }
-                        }
-                    }
-                    
-                    ui.label("Drag & drop files here or use the Add Resource button above:");
+// Expand a glob pattern relative to `base`, returning only regular files plus
+// the number of files dropped by the ignore list (see `is_ignored`).
+fn expand_glob(base: &std::path::Path, pattern: &str, ignore: &str) -> Result<(Vec<PathBuf>, usize), String> {
+    let ignore = ignore.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| glob::Pattern::new(line.trim_end_matches('/'))
+            .map_err(|e| format!("Invalid ignore pattern {}: {}", line, e)))
+        .collect::<Result<Vec<_>, _>>()?;
 
-                    if self.resources.is_empty() {
-                        ui.add_space(10.0);
-                        ui.centered_and_justified(|ui| {
-                            ui.label("No resources added yet.");
-                        });
-                        ui.add_space(10.0);
-                    } else {
-                        // Create a scrollable area for resources
-                        egui::ScrollArea::vertical().show(ui, |ui| {
-                            // Filter resources based on search query
-                            let search_query_lower = self.search_query.to_lowercase();
-                            let mut resources_to_remove = Vec::new();
-                            
-                            // Iterate through resources
-                            for i in 0..self.resources.len() {
-                                let resource_name = self.resources[i].file_name()
-                                    .map_or_else(|| "Unknown".to_string(), |n| n.to_string_lossy().to_string());
-                                
-                                let resource_path = self.resources[i].to_string_lossy().to_string();
-                                
-                                // Skip resources that don't match search query
-                                if !self.search_query.is_empty() && 
-                                   !resource_name.to_lowercase().contains(&search_query_lower) && 
-                                   !resource_path.to_lowercase().contains(&search_query_lower) {
-                                    continue;
-                                }
-                                
-                                let is_selected = Some(i) == self.selected_resource;
-                                
-                                // Create a frame for each resource with conditional highlighting
-                                let mut frame = egui::Frame::default()
-                                    .inner_margin(egui::style::Margin::same(8.0))
-                                    .rounding(egui::Rounding::same(4.0));
-                                
-                                if is_selected {
-                                    frame = frame.fill(ui.style().visuals.selection.bg_fill);
-                                }
-                                
-                                frame.show(ui, |ui| {
-                                    ui.horizontal(|ui| {
-                                        if ui.selectable_label(is_selected, &resource_name).clicked() {
-                                            // Single click selects the resource
-                                            if Some(i) == self.selected_resource {
-                                                // If already selected, set as main file
-                                                self.main_file = resource_name.clone();
-                                            }
-                                            self.selected_resource = Some(i);
-                                        }
-                                        
-                                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                                            if ui.button("✖").clicked() {
-                                                if self.selected_resource == Some(i) {
-                                                    self.selected_resource = None;
-                                                }
-                                                resources_to_remove.push(i);
-                                            }
-                                            
-                                            if ui.button("Set as Main").clicked() {
-                                                self.main_file = resource_name;
-                                            }
-                                        });
-                                    });
-                                    
-                                    ui.add_space(2.0);
-                                    ui.label(format!("Path: {}", resource_path));
-                                });
-                                
-                                ui.add_space(4.0);
-                            }
-                            
-                            // Remove resources marked for removal
-                            for &i in resources_to_remove.iter().rev() {
-                                self.resources.remove(i);
-                            }
-                        });
-                        
-                        // Resource reordering buttons - moved inside the resources container
-                        if self.selected_resource.is_some() {
-                            ui.horizontal(|ui| {
-                                ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui| {
-                                    if ui.button("⬆ Move Up").clicked() && self.selected_resource.unwrap() > 0 {
-                                        let idx = self.selected_resource.unwrap();
-                                        self.resources.swap(idx, idx - 1);
-                                        self.selected_resource = Some(idx - 1);
-                                    }
-                                    
-                                    if ui.button("⬇ Move Down").clicked() && self.selected_resource.unwrap() < self.resources.len() - 1 {
-                                        let idx = self.selected_resource.unwrap();
-                                        self.resources.swap(idx, idx + 1);
-                                        self.selected_resource = Some(idx + 1);
-                                    }
-                                });
-                            });
-                        }
-                    }
-                });
+    let full_pattern = base.join(pattern);
+    let paths = glob::glob(&full_pattern.to_string_lossy())
+        .map_err(|e| format!("Invalid glob pattern {}: {}", pattern, e))?;
+    let mut skipped = 0;
+    let mut files = Vec::new();
+    for path in paths.filter_map(Result::ok).filter(|p| p.is_file()) {
+        if is_ignored(path.strip_prefix(base).unwrap_or(&path), &ignore) {
+            skipped += 1;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok((files, skipped))
+}
 
-            ui.add_space(10.0);
-            
-            // Action buttons section
-            ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
-                if ui.button("📦 Compile EXE").clicked() {
-                    match compile_exe(self) {
-                        Ok(msg) => self.message = msg,
-                        Err(e) => self.message = format!("❌ Error: {}", e),
-                    }
-                }
-            });
-            
-            // Message area
-            if (!self.message.is_empty()) {
-                ui.add_space(10.0);
-                let (bg_color, text_color) = if self.message.starts_with("❌") {
-                    // Error message
-                    (
-                        egui::Color32::from_rgba_premultiplied(180, 0, 0, 25),
-                        if self.dark_mode { egui::Color32::from_rgb(255, 200, 200) } else { egui::Color32::from_rgb(120, 0, 0) }
-                    )
-                } else {
-                    // Success message
-                    (
-                        egui::Color32::from_rgba_premultiplied(0, 180, 0, 25),
-                        if self.dark_mode { egui::Color32::from_rgb(200, 255, 200) } else { egui::Color32::from_rgb(0, 100, 0) }
-                    )
-                };
+// A simple .gitignore: plain entries (`node_modules`, `*.log`) match any file or
+// folder name along the path, entries containing '/' (`build/*.tmp`) match the
+// whole path relative to the base folder.
+fn is_ignored(relative: &std::path::Path, ignore: &[glob::Pattern]) -> bool {
+    let relative_str = relative.to_string_lossy().replace('\\', "/");
+    ignore.iter().any(|pattern| {
+        if pattern.as_str().contains('/') {
+            pattern.matches(&relative_str)
+        } else {
+            relative.iter().any(|name| pattern.matches(&name.to_string_lossy()))
+        }
+    })
+}
 
-                egui::Frame::default()
-                    .rounding(8.0)
-                    .fill(bg_color)
-                    .inner_margin(8.0)
-                    .show(ui, |ui| {
-                        ui.colored_label(text_color, &self.message);
-                    });
-            }
+// Store the archive as an RCDATA resource in the already-written EXE, so the stub
+// reads it through FindResource/LoadResource instead of re-opening its own file.
+fn embed_archive_resource(output_path: &str, pack_data: &[u8]) -> Result<(), String> {
+    #[cfg(windows)]
+    {
+        use std::ffi::OsStr;
+        use std::iter;
+        use std::os::windows::ffi::OsStrExt;
+        use winapi::um::winbase::{BeginUpdateResourceW, EndUpdateResourceW, UpdateResourceW};
+        use winapi::um::winuser::RT_RCDATA;
 
-            // Keyboard shortcuts help
-            ui.with_layout(egui::Layout::bottom_up(egui::Align::LEFT), |ui| {
-                ui.horizontal(|ui| {
-                    ui.spacing_mut().item_spacing.x = 10.0;
-                    ui.label("Keyboard Shortcuts:");
-                    ui.label("Ctrl+N: New Project");
-                    ui.label("Ctrl+S: Save Project");
-                    ui.label("Ctrl+O: Open Project");
-                    ui.label("Ctrl+B: Compile EXE");
-                    ui.label("Delete: Remove Selected Resource");
-                });
-            });
+        let wide_path: Vec<u16> = OsStr::new(output_path)
+            .encode_wide()
+            .chain(iter::once(0))
+            .collect();
+        // Must match ARCHIVE_RESOURCE_NAME in the stub
+        let wide_name: Vec<u16> = OsStr::new("RSCARCHIVE")
+            .encode_wide()
+            .chain(iter::once(0))
+            .collect();
 
-            // Settings panel (if enabled)
-            if self.show_settings {
-                egui::Window::new("Settings")
-                    .collapsible(false)
-                    .resizable(false)
-                    .show(ctx, |ui| {
-                        ui.heading("Application Settings");
-                        
-                        ui.checkbox(&mut self.compress_resources, "Compress resources");
-                        ui.add_space(5.0);
-                        
-                        ui.horizontal(|ui| {
-                            ui.label("Custom Icon:");
-                            if let Some(ref path) = self.icon_path {
-                                ui.label(path.file_name().unwrap_or_default().to_string_lossy().to_string());
-                                if ui.button("Clear").clicked() {
-                                    self.icon_path = None;
-                                }
-                            } else {
-                                if ui.button("Select Icon").clicked() {
-                                    if let Some(path) = rfd::FileDialog::new()
-                                        .add_filter("Icon", &["ico"])
-                                        .pick_file() {
-                                        self.icon_path = Some(path);
-                                    }
-                                }
-                            }
-                        });
-                        
-                        ui.add_space(10.0);
-                        if ui.button("Close").clicked() {
-                            self.show_settings = false;
-                        }
-                    });
+        unsafe {
+            let handle = BeginUpdateResourceW(wide_path.as_ptr(), 0);
+            if handle.is_null() {
+                return Err(format!("Failed to open {} for resource update: {}", output_path, std::io::Error::last_os_error()));
             }
-        });
-        
-        // Handle keyboard shortcuts
-        ctx.input(|i| {
-            if i.modifiers.ctrl {
-                if i.key_pressed(egui::Key::N) {
-                    // New project
-                    self.resources.clear();
-                    self.main_file.clear();
-                    self.extraction_path = "rc_extracted".to_string();
-                    self.output_exe = "packed.exe".to_string();
-                    self.message = "Started new project".to_string();
-                }
-                else if i.key_pressed(egui::Key::S) {
-                    // Save project logic - simplified, should open a file dialog
-                    self.message = "Use File menu to save project".to_string();
-                }
-                else if i.key_pressed(egui::Key::O) {
-                    // Open project logic - simplified, should open a file dialog
-                    self.message = "Use File menu to open project".to_string();
-                }
-                else if i.key_pressed(egui::Key::B) {
-                    // Compile EXE
-                    match compile_exe(self) {
-                        Ok(msg) => self.message = msg,
-                        Err(e) => self.message = format!("❌ Error: {}", e),
-                    }
-                }
+
+            // Language 0 is LANG_NEUTRAL, which FindResource falls back to on any locale
+            let updated = UpdateResourceW(
+                handle,
+                RT_RCDATA,
+                wide_name.as_ptr(),
+                0,
+                pack_data.as_ptr() as *mut _,
+                pack_data.len() as u32,
+            );
+            if updated == 0 {
+                let err = std::io::Error::last_os_error();
+                EndUpdateResourceW(handle, 1);
+                return Err(format!("Failed to add archive resource: {}", err));
             }
-            
-            if i.key_pressed(egui::Key::Delete) && self.selected_resource.is_some() {
-                let idx = self.selected_resource.unwrap();
-                self.resources.remove(idx);
-                if idx >= self.resources.len() {
-                    self.selected_resource = if self.resources.is_empty() { 
-                        None 
-                    } else { 
-                        Some(self.resources.len() - 1) 
-                    };
-                }
+
+            if EndUpdateResourceW(handle, 0) == 0 {
+                return Err(format!("Failed to write archive resource: {}", std::io::Error::last_os_error()));
             }
-        });
+        }
+        Ok(())
+    }
+
+    #[cfg(not(windows))]
+    {
+        let _ = (output_path, pack_data);
+        Err("PE resource storage is only supported on Windows".to_string())
     }
 }
 
-/// compile_exe builds the new EXE by:
-/// 1. Verifying the main file is among the resources.
-/// 2. Reading a pre-built stub (stub.exe must exist in the same folder).
-/// 3. Building a JSON header that includes extraction_path, main_file, resources, execution_style, and run_as_admin.
-/// 4. Appending the resource files' bytes.
-/// 5. Adding a footer containing the header length, archive data length, and a fixed marker.
-fn compile_exe(state: &AppState) -> Result<String, String> {
-    // Verify that the main file (by filename) is among the added resources.
-    let main_file_found = state.resources.iter().any(|p| {
-        p.file_name()
-            .map(|f| f.to_string_lossy().to_string() == state.main_file)
-            .unwrap_or(false)
-    });
-    if (!main_file_found) {
-        return Err("Main file must be one of the added resources (by filename)".to_string());
+// Scratch folder for one build step, unique per process and call so concurrent
+// builds never share files. Removed with everything in it when dropped, on
+// success and error paths alike.
+struct BuildTempDir {
+    path: PathBuf,
+}
+
+impl BuildTempDir {
+    fn new(purpose: &str) -> Result<Self, String> {
+        static NEXT_ID: AtomicU32 = AtomicU32::new(0);
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let path = std::env::temp_dir()
+            .join("resource_compiler_temp")
+            .join(format!("{}_{}_{}", purpose, std::process::id(), id));
+        fs::create_dir_all(&path)
+            .map_err(|e| format!("Failed to create temp directory {:?}: {}", path, e))?;
+        Ok(Self { path })
     }
 
-    // Read the stub binary.
-    let stub_bytes = fs::read("stub.exe")
-        .map_err(|e| format!("Failed to read stub.exe: {}", e))?;
+    fn join(&self, name: &str) -> PathBuf {
+        self.path.join(name)
+    }
+}
 
-    // Build the header with the extra fields.
-    let mut header = ArchiveHeader {
-        extraction_path: state.extraction_path.clone(),
-        main_file: state.main_file.clone(),
-        resources: Vec::new(),
-        execution_style: state.execution_style.clone(),
-        run_as_admin: state.run_as_admin,
-        is_compressed: state.compress_resources,  // Set the compression flag
-    };
+impl Drop for BuildTempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+        // Only succeeds once no other build is using the shared parent
+        if let Some(parent) = self.path.parent() {
+            let _ = fs::remove_dir(parent);
+        }
+    }
+}
 
-    // Read each resource file and accumulate the data.
-    let mut resource_data = Vec::new();
-    for res_path in &state.resources {
-        let data = fs::read(res_path)
-            .map_err(|e| format!("Failed to read resource {:?}: {}", res_path, e))?;
-        let filename = res_path.file_name()
-            .ok_or("Invalid resource file name")?
-            .to_string_lossy().to_string();
-        header.resources.push(ResourceEntry {
-            filename,
-            size: data.len() as u32,
-        });
-        resource_data.extend_from_slice(&data);
+// Run UPX over a copy of the stub and return the compressed stub. Resources
+// stay uncompressed so the stub's icon and version info remain readable.
+fn compress_stub_with_upx(stub: &[u8]) -> Result<Vec<u8>, String> {
+    use std::process::Command;
+
+    let temp_dir = BuildTempDir::new("upx")?;
+    let temp_stub_path = temp_dir.join("stub.exe");
+    fs::write(&temp_stub_path, stub)
+        .map_err(|e| format!("Failed to write temp stub: {}", e))?;
+
+    match Command::new("upx")
+        .args(["--best", "-q", "--compress-resources=0"])
+        .arg(&temp_stub_path)
+        .output()
+    {
+        Ok(output) if output.status.success() => fs::read(&temp_stub_path)
+            .map_err(|e| format!("Failed to read compressed stub: {}", e)),
+        Ok(output) => {
+            let message = String::from_utf8_lossy(&output.stderr);
+            Err(format!("upx failed: {}", message.lines().last().unwrap_or("").trim()))
+        }
+        Err(e) => Err(format!("upx could not be run (is it on PATH?): {}", e)),
     }
+}
 
-    // Serialize the header to JSON.
-    let header_json = serde_json::to_string(&header)
-        .map_err(|e| format!("Failed to serialize header: {}", e))?;
-    let header_bytes = header_json.as_bytes();
-    let header_length = header_bytes.len();
+// Authenticode-sign the written output EXE with signtool, using a .pfx file or
+// a certificate from the user's store by thumbprint. The archive still opens
+// afterwards: the lookup skips the certificate table signing appends.
+fn sign_output_exe(state: &AppState) -> Result<(), String> {
+    use std::process::Command;
 
-    // Build the archive data: header JSON followed by resource file bytes.
-    let mut archive_data = Vec::new();
-    archive_data.extend_from_slice(header_bytes);
-    
-    // Apply compression ONLY to resource data if enabled
-    let final_resource_data = if state.compress_resources {
-        use flate2::write::GzEncoder;
-        use flate2::Compression;
-        use std::io::Write;
-        
-        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
-        if let Err(e) = encoder.write_all(&resource_data) {
-            return Err(format!("Failed to compress data: {}", e));
+    let certificate = state.sign_certificate.trim();
+    let mut command = Command::new("signtool");
+    command.args(["sign", "/fd", "SHA256"]);
+    // signtool only takes a .pfx password as /p, visible to every process on the
+    // machine, so a .pfx is put in the user's store and picked by thumbprint instead
+    #[cfg(windows)]
+    let _imported;
+    if is_thumbprint(certificate) {
+        command.arg("/sha1").arg(certificate.replace(char::is_whitespace, ""));
+    } else {
+        if !Path::new(certificate).is_file() {
+            return Err(format!("Signing certificate {:?} does not exist", certificate));
         }
-        
-        match encoder.finish() {
-            Ok(compressed) => compressed,
-            Err(e) => return Err(format!("Failed to finish compression: {}", e))
+        #[cfg(windows)]
+        {
+            let imported = ImportedCertificate::import(certificate, &state.sign_password)?;
+            command.args(["/s", "MY", "/sha1", &imported.thumbprint]);
+            _imported = imported;
         }
-    } else {
-        resource_data
-    };
-    
-    // Add the (possibly compressed) resource data after the header
-    archive_data.extend_from_slice(&final_resource_data);
-    let archive_data_length = archive_data.len();
+        #[cfg(not(windows))]
+        return Err("Signing with a .pfx file is only supported on Windows".to_string());
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        use winapi::um::winbase::CREATE_NO_WINDOW;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+    let timestamp_url = state.sign_timestamp_url.trim();
+    if !timestamp_url.is_empty() {
+        command.args(["/tr", timestamp_url, "/td", "SHA256"]);
+    }
+    command.arg(&state.output_exe);
 
-    // Build the footer: header length (4 bytes) + archive data length (4 bytes) + marker (16 bytes).
-    let mut footer = Vec::new();
-    footer.extend_from_slice(&(header_length as u32).to_le_bytes());
-    footer.extend_from_slice(&(archive_data_length as u32).to_le_bytes());
-    footer.extend_from_slice(FOOTER_MARKER);
+    match command.output() {
+        Ok(output) if output.status.success() => Ok(()),
+        Ok(output) => {
+            // signtool explains failures in "SignTool Error: ..." lines, on either stream
+            let text = format!("{}\n{}", String::from_utf8_lossy(&output.stderr), String::from_utf8_lossy(&output.stdout));
+            let errors: Vec<&str> = text.lines().map(str::trim).filter(|line| line.starts_with("SignTool Error:")).collect();
+            let reason = if errors.is_empty() {
+                text.lines().map(str::trim).rfind(|line| !line.is_empty()).unwrap_or("no output").to_string()
+            } else {
+                errors.join(" ")
+            };
+            Err(format!("Signing failed, {} was left unsigned: {}", state.output_exe, reason))
+        }
+        Err(e) => Err(format!("signtool could not be run (is the Windows SDK on PATH?): {}", e)),
+    }
+}
 
-    // Final output: [stub binary] + [archive data] + [footer]
-    let mut output_data = Vec::new();
-    output_data.extend_from_slice(&stub_bytes);
-    output_data.extend_from_slice(&archive_data);
-    output_data.extend_from_slice(&footer);
+// A .pfx loaded into the current user's "MY" store for one signtool run.
+// Dropping removes the certificate and the private key the import created.
+#[cfg(windows)]
+struct ImportedCertificate {
+    pfx_store: winapi::um::wincrypt::HCERTSTORE, // in-memory store PFXImportCertStore returned
+    pfx_cert: winapi::um::wincrypt::PCCERT_CONTEXT, // the certificate with a private key in it
+    user_store: winapi::um::wincrypt::HCERTSTORE,
+    added: winapi::um::wincrypt::PCCERT_CONTEXT, // our copy in user_store, null if it was already there
+    thumbprint: String,
+}
 
-    // Apply custom icon if specified
-    if let Some(icon_path) = &state.icon_path {
-        if (!icon_path.exists()) {
-            return Err(format!("Icon file does not exist: {:?}", icon_path));
+#[cfg(windows)]
+impl ImportedCertificate {
+    fn import(pfx_path: &str, password: &str) -> Result<Self, String> {
+        use std::ffi::OsStr;
+        use std::iter;
+        use std::os::windows::ffi::OsStrExt;
+        use std::ptr;
+        use winapi::shared::winerror::CRYPT_E_EXISTS;
+        use winapi::um::wincrypt::{
+            CertAddCertificateContextToStore, CertCloseStore, CertEnumCertificatesInStore,
+            CertGetCertificateContextProperty, CertOpenSystemStoreW, PFXImportCertStore, CERT_KEY_PROV_INFO_PROP_ID,
+            CERT_SHA1_HASH_PROP_ID, CERT_STORE_ADD_NEW, CRYPT_DATA_BLOB, CRYPT_USER_KEYSET,
+        };
+
+        let mut pfx = fs::read(pfx_path)
+            .map_err(|e| format!("Failed to read signing certificate {:?}: {}", pfx_path, e))?;
+        let wide_password: Vec<u16> = OsStr::new(password)
+            .encode_wide()
+            .chain(iter::once(0))
+            .collect();
+        let wide_store: Vec<u16> = OsStr::new("MY")
+            .encode_wide()
+            .chain(iter::once(0))
+            .collect();
+        let mut blob = CRYPT_DATA_BLOB { cbData: pfx.len() as u32, pbData: pfx.as_mut_ptr() };
+
+        unsafe {
+            let pfx_store = PFXImportCertStore(&mut blob, wide_password.as_ptr(), CRYPT_USER_KEYSET);
+            if pfx_store.is_null() {
+                return Err(format!("Failed to open signing certificate {:?} (wrong password?): {}", pfx_path, std::io::Error::last_os_error()));
+            }
+            // A .pfx may carry the whole chain; the signing certificate is the one with a key
+            let mut cert = ptr::null();
+            loop {
+                cert = CertEnumCertificatesInStore(pfx_store, cert);
+                let mut size = 0;
+                if cert.is_null() || CertGetCertificateContextProperty(cert, CERT_KEY_PROV_INFO_PROP_ID, ptr::null_mut(), &mut size) != 0 {
+                    break;
+                }
+            }
+            if cert.is_null() {
+                CertCloseStore(pfx_store, 0);
+                return Err(format!("Signing certificate {:?} contains no private key", pfx_path));
+            }
+            // Cleaned up by Drop from here on
+            let mut imported = ImportedCertificate {
+                pfx_store,
+                pfx_cert: cert,
+                user_store: ptr::null_mut(),
+                added: ptr::null(),
+                thumbprint: String::new(),
+            };
+
+            let mut hash = [0u8; 20];
+            let mut size = hash.len() as u32;
+            if CertGetCertificateContextProperty(cert, CERT_SHA1_HASH_PROP_ID, hash.as_mut_ptr() as *mut _, &mut size) == 0 {
+                return Err(format!("Failed to read the thumbprint of {:?}: {}", pfx_path, std::io::Error::last_os_error()));
+            }
+            imported.thumbprint = hash.iter().map(|b| format!("{:02X}", b)).collect();
+
+            imported.user_store = CertOpenSystemStoreW(0, wide_store.as_ptr());
+            if imported.user_store.is_null() {
+                return Err(format!("Failed to open the certificate store: {}", std::io::Error::last_os_error()));
+            }
+            // Already imported by the user: sign with their copy and leave it in place
+            if CertAddCertificateContextToStore(imported.user_store, cert, CERT_STORE_ADD_NEW, &mut imported.added) == 0 {
+                let err = std::io::Error::last_os_error();
+                if err.raw_os_error() != Some(CRYPT_E_EXISTS) {
+                    return Err(format!("Failed to add {:?} to the certificate store: {}", pfx_path, err));
+                }
+            }
+            Ok(imported)
         }
-        
-        // Read the icon file
-        let icon_data = match fs::read(icon_path) {
-            Ok(data) => data,
-            Err(e) => return Err(format!("Failed to read icon file: {}", e))
+    }
+}
+
+#[cfg(windows)]
+impl Drop for ImportedCertificate {
+    fn drop(&mut self) {
+        use std::ptr;
+        use winapi::um::wincrypt::{
+            CertCloseStore, CertDeleteCertificateFromStore, CertFreeCertificateContext,
+            CertGetCertificateContextProperty, CryptAcquireContextW, CERT_KEY_PROV_INFO_PROP_ID, CRYPT_DELETEKEYSET,
+            CRYPT_KEY_PROV_INFO, CRYPT_MACHINE_KEYSET,
         };
-        
-        // Use resource_builder to inject the icon into the PE file
-        // This is a simplified approach; in a real application, you would use a proper
-        // Windows resource editor library to modify the PE resources
-        if let Err(e) = embed_icon_in_exe(&state.output_exe, &output_data, &icon_data) {
-            return Err(e);
+
+        unsafe {
+            if !self.added.is_null() {
+                CertDeleteCertificateFromStore(self.added);
+                // The key container PFXImportCertStore created. Only when the certificate
+                // was ours, so a key the user's existing copy relies on is never touched.
+                let mut size = 0;
+                if CertGetCertificateContextProperty(self.pfx_cert, CERT_KEY_PROV_INFO_PROP_ID, ptr::null_mut(), &mut size) != 0 {
+                    let mut buffer = vec![0u64; (size as usize).div_ceil(8)];
+                    if CertGetCertificateContextProperty(self.pfx_cert, CERT_KEY_PROV_INFO_PROP_ID, buffer.as_mut_ptr() as *mut _, &mut size) != 0 {
+                        let info = &*(buffer.as_ptr() as *const CRYPT_KEY_PROV_INFO);
+                        let mut provider = 0;
+                        CryptAcquireContextW(
+                            &mut provider,
+                            info.pwszContainerName,
+                            info.pwszProvName,
+                            info.dwProvType,
+                            (info.dwFlags & CRYPT_MACHINE_KEYSET) | CRYPT_DELETEKEYSET,
+                        );
+                    }
+                }
+            }
+            CertFreeCertificateContext(self.pfx_cert);
+            CertCloseStore(self.pfx_store, 0);
+            if !self.user_store.is_null() {
+                CertCloseStore(self.user_store, 0);
+            }
         }
-        
-        Ok(format!("✅ Successfully created {} with custom icon", state.output_exe))
-    } else {
-        // No custom icon, just write the file directly
-        fs::write(&state.output_exe, output_data)
-            .map_err(|e| format!("Failed to write output exe: {}", e))?;
-        
-        Ok(format!("✅ Successfully created {}", state.output_exe))
     }
 }
 
+// A SHA-1 certificate thumbprint as copied from certmgr, spaces allowed
+fn is_thumbprint(certificate: &str) -> bool {
+    let hex: String = certificate.chars().filter(|c| !c.is_whitespace()).collect();
+    hex.len() == 40 && hex.chars().all(|c| c.is_ascii_hexdigit())
+}
+
 // Function to embed an icon in the output EXE
 fn embed_icon_in_exe(output_path: &str, exe_data: &[u8], icon_data: &[u8]) -> Result<(), String> {
     // First, write the EXE data to the output path
@@ -647,12 +4874,8 @@ fn embed_icon_in_exe(output_path: &str, exe_data: &[u8], icon_data: &[u8]) -> Re
     {
         use std::process::Command;
         
-        // Create a temporary directory for resource compilation
-        let temp_dir = std::env::temp_dir().join("resource_compiler_temp");
-        if !temp_dir.exists() {
-            fs::create_dir_all(&temp_dir)
-                .map_err(|e| format!("Failed to create temp directory: {}", e))?;
-        }
+        // Create a temporary directory for resource compilation, removed on every return path
+        let temp_dir = BuildTempDir::new("icon")?;
         
         // Copy the icon to the temp directory
         let temp_icon_path = temp_dir.join("temp_icon.ico");
@@ -681,9 +4904,6 @@ fn embed_icon_in_exe(output_path: &str, exe_data: &[u8], icon_data: &[u8]) -> Re
         match status {
             Ok(exit) => {
                 if exit.success() {
-                    // Clean up temp files
-                    let _ = fs::remove_file(&temp_icon_path);
-                    let _ = fs::remove_file(&rc_path);
                     Ok(())
                 } else {
                     Err(format!("Failed to set icon with exit code: {:?}", exit.code()))
@@ -705,6 +4925,21 @@ fn embed_icon_in_exe(output_path: &str, exe_data: &[u8], icon_data: &[u8]) -> Re
     }
 }
 
+// Check that icon data is a real .ico the image crate can decode, so a renamed
+// PNG or a damaged file is reported before it reaches rcedit.
+fn validate_icon(icon_data: &[u8]) -> Result<(), String> {
+    match image::guess_format(icon_data) {
+        Ok(image::ImageFormat::Ico) => image::load_from_memory_with_format(icon_data, image::ImageFormat::Ico)
+            .map(|_| ())
+            .map_err(|e| format!("the .ico file is damaged ({})", e)),
+        Ok(format) => Err(format!(
+            "it is a {:?} image, not an icon. Convert it to .ico first (renaming is not enough)",
+            format
+        )),
+        Err(_) => Err("it is not an icon or any known image format".to_string()),
+    }
+}
+
 // Helper function to load an icon from memory
 fn load_icon_from_memory(icon_data: &[u8]) -> Result<eframe::IconData, String> {
     // Use the image crate to properly decode the .ico file
@@ -757,6 +4992,11 @@ fn create_fallback_icon() -> Result<eframe::IconData, String> {
 
 #[cfg(windows)]
 fn main() {
+    // Command line mode (e.g. `--inspect packed.exe`) exits without opening a window
+    if let Some(code) = run_cli() {
+        std::process::exit(code);
+    }
+
     // Load application icon for the window
     let icon_data = include_bytes!("../assets/app_icon.ico");
     
@@ -778,12 +5018,20 @@ fn main() {
     eframe::run_native(
         "Resource Compiler",
         native_options,
-        Box::new(|_cc| Box::new(AppState::default())),
+        Box::new(|cc| {
+            install_fallback_fonts(&cc.egui_ctx);
+            Box::new(initial_state())
+        }),
     );
 }
 
 #[cfg(not(windows))]
 fn main() {
+    // Command line mode (e.g. `--inspect packed.exe`) exits without opening a window
+    if let Some(code) = run_cli() {
+        std::process::exit(code);
+    }
+
     // Load application icon for the window
     let icon_data = include_bytes!("../assets/app_icon.ico");
     
@@ -804,6 +5052,9 @@ fn main() {
     eframe::run_native(
         "Resource Compiler",
         native_options,
-        Box::new(|_cc| Box::new(AppState::default())),
+        Box::new(|cc| {
+            install_fallback_fonts(&cc.egui_ctx);
+            Box::new(initial_state())
+        }),
     );
 }
@@ -0,0 +1,1248 @@
+//! The packed archive format shared by the compiler and the stub: the header the
+//! stub is configured by, the footer that locates an archive, resource data
+//! (de)compression and the resource layout.
+//!
+//! An archive is `[header JSON][resource data][footer]`, appended to the stub EXE,
+//! stored as a PE resource or written to a sidecar `.rscpack` file.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+
+use flate2::read::GzDecoder;
+use serde::{Deserialize, Serialize};
+
+/// Everything that can go wrong packing or reading an archive. The compiler
+/// and the stub show these as text, which `?` into a `String` error gives them.
+#[derive(Debug, thiserror::Error)]
+pub enum ArchiveError {
+    #[error("No stub to pack into. Build the stub or select one in Settings.")]
+    StubMissing,
+    #[error("The stub is not a valid Windows executable: {0}. Check the stub path in Settings.")]
+    InvalidStub(String),
+    #[error("Failed to read resource {path:?}: {source}")]
+    ResourceRead { path: PathBuf, source: std::io::Error },
+    #[error("{0:?} is not a valid resource name: it must be a relative path inside the extraction folder.")]
+    InvalidResourceName(String),
+    #[error("Refusing to extract {0:?} outside the extraction directory.")]
+    UnsafePath(String),
+    #[error("Main file {0} is not one of the resources.")]
+    MainFileNotFound(String),
+    #[error("More than {} resources. Put small files into a .zip added as a single resource instead.", MAX_RESOURCE_COUNT)]
+    TooManyResources,
+    #[error("{filename} is {size} bytes, a single resource can be at most 4 GB.")]
+    ResourceTooLarge { filename: String, size: usize },
+    #[error("The archive header is {size} bytes ({resources} resources), more than the {} byte limit. Pack fewer files or use shorter names.", MAX_HEADER_SIZE)]
+    HeaderTooLarge { size: usize, resources: usize },
+    #[error("Archive size {0} exceeds the {limit} byte limit of the archive format, whose footer stores 32-bit lengths. Turn on compression or pack fewer files.", limit = MAX_ARCHIVE_SIZE)]
+    ArchiveTooLarge(usize),
+    #[error("Failed to compress data: {0}")]
+    CompressionFailed(std::io::Error),
+    #[error("Failed to decompress resource data: {0}")]
+    DecompressionFailed(std::io::Error),
+    #[error("Decompressed data exceeds the {0} byte extraction limit.")]
+    ExtractLimitExceeded(u64),
+    #[error("Truncated {0}.")]
+    Truncated(&'static str),
+    #[error("Invalid resource archive marker.")]
+    MarkerNotFound,
+    #[error("{0}")]
+    InvalidFooter(String),
+    #[error("Invalid archive header: {0}")]
+    InvalidHeader(serde_json::Error),
+    #[error("Checksum mismatch (expected {expected:08x}, found {found:08x}).")]
+    ChecksumMismatch { expected: u32, found: u32 },
+    #[error("Resource {filename} ({size} bytes at offset {offset}) runs past the end of the data ({data_len} bytes).")]
+    ResourceOutOfRange { filename: String, size: usize, offset: usize, data_len: usize },
+    #[error("Resource data is incomplete.")]
+    Incomplete,
+    #[error("{0}")]
+    CorruptData(String),
+    #[error("No resource {index} (the archive has {count}).")]
+    NoSuchResource { index: usize, count: usize },
+}
+
+impl From<ArchiveError> for String {
+    fn from(error: ArchiveError) -> Self {
+        error.to_string()
+    }
+}
+
+/// Marks the end of an archive. `RSC_FOOTER_MARKER` at build time replaces the
+/// default; the stub and the compiler must be built with the same value.
+pub const FOOTER_MARKER: &[u8; 16] = footer_marker();
+
+const fn footer_marker() -> &'static [u8; 16] {
+    match option_env!("RSC_FOOTER_MARKER") {
+        Some(marker) => match marker.as_bytes().first_chunk::<16>() {
+            Some(bytes) if marker.len() == 16 => bytes,
+            _ => panic!("RSC_FOOTER_MARKER must be exactly 16 bytes"),
+        },
+        None => b"RSCARCHIVE_V1___",
+    }
+}
+
+/// Header length (u32) + archive data length (u32) + marker
+pub const FOOTER_SIZE: usize = 4 + 4 + 16;
+
+/// Split (multi-volume) builds end the EXE with this trailer instead of the footer:
+/// volume count including the EXE (u32) + marker. The rest of the archive is in
+/// `<name>.part2`, `<name>.part3`, ... next to the EXE.
+pub const SPLIT_MARKER: &[u8; 16] = b"RSCSPLIT_V1_____";
+pub const SPLIT_TRAILER_SIZE: usize = 4 + 16;
+
+/// Largest archive (header + resource data) the stub accepts. Lengths are stored
+/// as u32, and 32-bit stubs have to fit the archive in their address space.
+pub const MAX_ARCHIVE_SIZE: usize = 2 * 1024 * 1024 * 1024;
+// Raising the limit past u32 needs a new footer, not just a bigger number
+const _: () = assert!(MAX_ARCHIVE_SIZE <= u32::MAX as usize);
+
+/// Limits that keep an archive within what the stub can handle: it parses the
+/// whole header in memory before extracting anything.
+pub const MAX_RESOURCE_COUNT: usize = 100_000;
+pub const MAX_HEADER_SIZE: usize = 32 * 1024 * 1024;
+
+/// Uncompressed size of each independently gzipped block in seekable compression
+pub const COMPRESSION_BLOCK_SIZE: usize = 1024 * 1024;
+
+/// Bytes the stub writes to disk (after decompression) unless the header sets
+/// its own max_extract_size.
+pub const DEFAULT_MAX_EXTRACT_SIZE: u64 = 8 * 1024 * 1024 * 1024;
+
+/// One packed file. Filenames are UTF-8 in the JSON header and may include
+/// subfolders ("config/app.ini"); see [`is_safe_resource_path`].
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct ResourceEntry {
+    pub filename: String,
+    pub size: u32,
+    #[serde(default)]
+    pub modified: Option<u64>, // last-modified time of the source file, ms since the Unix epoch
+    #[serde(default)]
+    pub attributes: Option<u32>, // Windows file attributes (read-only, hidden, ...) to restore
+    #[serde(default)]
+    pub offset: Option<u64>, // start in the resource data; None in older archives, which pack entries back to back
+    #[serde(default)]
+    pub executable: bool, // set the executable bit after extracting (no-op on Windows)
+    #[serde(default)]
+    pub stored_size: Option<u32>, // the entry is its own gzip stream of this many bytes, inflating to `size`; None = stored as is
+}
+
+impl ResourceEntry {
+    /// Bytes the entry takes up in the resource data
+    pub fn stored_len(&self) -> usize {
+        self.stored_size.unwrap_or(self.size) as usize
+    }
+}
+
+/// Everything the stub needs to extract and launch: written as JSON in front of
+/// the resource data. New options must default to the old behaviour so archives
+/// from older compilers keep working, and are listed in the stub's capabilities.
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct ArchiveHeader {
+    pub extraction_path: String,
+    pub main_file: String,
+    #[serde(default)]
+    pub main_is_external: bool, // main_file is a path/command on the target, not an extracted resource
+    pub resources: Vec<ResourceEntry>,
+    pub execution_style: String, // "no-window", "minimized", "normal", or "maximized"
+    pub run_as_admin: bool,
+    pub is_compressed: bool, // the resource data is gzipped, see compressed_blocks
+    #[serde(default)]
+    pub show_progress: bool, // report "Extracting file X of N" while extracting
+    #[serde(default)]
+    pub target_arch: String, // "x64" or "x86", the stub architecture this archive was packed with
+    #[serde(default)]
+    pub log_level: String, // "off", "error", "info" or "debug"; the stub writes a log file to the temp dir
+    #[serde(default)]
+    pub max_extract_size: u64, // total bytes the stub may write to disk, 0 = stub default
+    #[serde(default)]
+    pub io_retries: u32, // retries of creating folders and the first write, with growing delays (network shares); 0 = none
+    #[serde(default)]
+    pub clean_before_extract: bool, // remove extraction_path before extracting (clean install)
+    #[serde(default)]
+    pub overwrite_policy: String, // "allow", "ask" or "refuse" when extraction_path holds files the stub didn't put there; empty = "allow"
+    #[serde(default)]
+    pub update_mode: String, // per file already on disk: "newer" = replace only if the bundled copy is newer, "skip" = keep it; empty = always replace
+    #[serde(default)]
+    pub launch_delay_ms: u32, // wait this long after extracting before launching, 0 = launch immediately
+    #[serde(default)]
+    pub allow_uninstall: bool, // running the packed EXE with --uninstall removes the extracted files
+    #[serde(default)]
+    pub shortcut: Option<ShortcutOptions>, // create shortcuts to the main file on first run
+    #[serde(default)]
+    pub service: Option<ServiceOptions>, // install the main file as a Windows service instead of launching it
+    #[serde(default)]
+    pub single_instance: bool, // focus the running app instead of extracting/launching again
+    #[serde(default)]
+    pub wait_for_exit: bool, // the stub waits for the main file to exit and exits with its exit code
+    #[serde(default)]
+    pub data_crc32: Option<u32>, // CRC32 of the resource data as stored (after compression)
+    #[serde(default)]
+    pub admin_title: String, // title of the "run as administrator" message, empty = stub default
+    #[serde(default)]
+    pub admin_message: String, // text of the "run as administrator" message, empty = stub default
+    #[serde(default)]
+    pub messages: StubMessages, // wording of the stub's error dialogs
+    #[serde(default)]
+    pub eula_title: String, // title of the license dialog, empty = stub default
+    #[serde(default)]
+    pub eula_text: String, // license the user must accept on first run, empty = no prompt
+    #[serde(default)]
+    pub capture_output: bool, // run the main file without a console and show its output in a window
+    #[serde(default)]
+    pub build_time: String, // ISO 8601 time the archive was packed, informational
+    #[serde(default)]
+    pub package_version: String, // free-form version of the packaged app, informational
+    #[serde(default)]
+    pub launch_verb: String, // ShellExecute verb for the main file ("open", "runas", "print", ...), empty = "open"
+    #[serde(default)]
+    pub launch_args: String, // command line passed to the main file as is
+    #[serde(default)]
+    pub dependencies: Vec<Dependency>, // checked before extracting, the first missing one is reported
+    #[serde(default)]
+    pub post_extract_command: String, // run through cmd /c in the extraction path after extracting, empty = none
+    #[serde(default)]
+    pub post_extract_style: String, // window state of post_extract_command, same values as execution_style
+    #[serde(default)]
+    pub post_extract_abort_on_failure: bool, // don't launch the main file if post_extract_command fails
+    #[serde(default)]
+    pub working_dir: String, // folder the main file starts in, relative to extraction_path; empty = stub default
+    #[serde(default)]
+    pub main_file_last: bool, // extract the main file after every other resource
+    #[serde(default)]
+    pub compression_block_size: u32, // uncompressed bytes per block in seekable compression
+    #[serde(default)]
+    pub compressed_blocks: Vec<u32>, // compressed length of each gzip block, empty = one gzip stream
+}
+
+/// Error dialog texts shown by the stub. Packers can translate or rebrand them;
+/// empty strings fall back to the stub's English defaults.
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct StubMessages {
+    pub title: String,
+    pub extraction_failed: String,
+    pub corrupt_archive: String,
+    pub launch_failed: String,
+}
+
+/// A runtime or other prerequisite the stub checks for before extracting
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct Dependency {
+    pub kind: String, // "registry" (the key must exist) or "file" (the path must exist, %VARS% expanded)
+    pub path: String,
+    pub name: String, // shown to the user, e.g. ".NET 8 Desktop Runtime"
+    pub message: String, // dialog text when it is missing, empty = a default naming `name`
+}
+
+/// Shortcut (.lnk) to the extracted main file, created by the stub on first run
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct ShortcutOptions {
+    pub name: String, // without ".lnk", empty = main file name
+    pub arguments: String, // command line arguments passed to the main file
+    pub icon: String, // extracted resource to take the icon from, empty = the main file's icon
+    pub desktop: bool,
+    pub start_menu: bool,
+}
+
+/// Windows service the stub installs for the extracted main file on first run
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct ServiceOptions {
+    pub name: String, // service (key) name, empty = main file name
+    pub display_name: String, // shown in services.msc, empty = name
+    pub description: String,
+    pub start_type: String, // "auto", "manual" or "disabled", empty = auto
+    pub arguments: String, // appended to the service's command line
+    pub start_now: bool, // start the service once it is installed
+}
+
+impl ArchiveHeader {
+    /// Most bytes extracting this archive may write: max_extract_size, or the
+    /// default when the header sets none.
+    pub fn extract_limit(&self) -> u64 {
+        if self.max_extract_size == 0 { DEFAULT_MAX_EXTRACT_SIZE } else { self.max_extract_size }
+    }
+
+    /// Where each resource starts in the uncompressed data. Archives from older
+    /// versions only record offsets for deduplicated entries and pack
+    /// everything else back to back.
+    pub fn resource_starts(&self) -> Vec<usize> {
+        let mut offset = 0usize;
+        self.resources.iter().map(|resource| match resource.offset {
+            Some(start) => start as usize,
+            None => {
+                offset += resource.stored_len();
+                offset - resource.stored_len()
+            }
+        }).collect()
+    }
+
+    /// The resources in the order they are extracted: header order, except that
+    /// main_file_last moves the main file to the end so its dependencies are on
+    /// disk first. Each comes with its start in the uncompressed data and where it
+    /// is written under `root`; fails before anything is written if any name
+    /// would land outside `root`.
+    pub fn extraction_plan(&self, root: &Path) -> Result<Vec<PlannedResource<'_>>, ArchiveError> {
+        let mut plan = self.resources.iter().zip(self.resource_starts())
+            .map(|(resource, start)| Ok(PlannedResource { resource, start, path: sandboxed_path(root, &resource.filename)? }))
+            .collect::<Result<Vec<_>, ArchiveError>>()?;
+        if self.main_file_last && !self.main_is_external {
+            plan.sort_by_key(|planned| planned.resource.filename == self.main_file);
+        }
+        Ok(plan)
+    }
+
+    /// Compare the resource data as stored against data_crc32, if one was recorded.
+    pub fn check_data_crc(&self, resource_bytes: &[u8]) -> Result<(), ArchiveError> {
+        if let Some(expected) = self.data_crc32 {
+            let mut crc = flate2::Crc::new();
+            crc.update(resource_bytes);
+            if crc.sum() != expected {
+                return Err(ArchiveError::ChecksumMismatch { expected, found: crc.sum() });
+            }
+        }
+        Ok(())
+    }
+
+    /// Open the resource data that follows this header according to its compression fields.
+    /// `limit` caps how many bytes a single gzip stream may inflate to.
+    pub fn open_resource_data<'a>(&self, resource_bytes: &'a [u8], limit: u64) -> Result<ResourceData<'a>, ArchiveError> {
+        ResourceData::open(resource_bytes, self.is_compressed, self.compression_block_size, &self.compressed_blocks, limit)
+    }
+
+    /// Check that every resource lies within `data_len` uncompressed bytes and
+    /// that the data holds nothing past the last one.
+    pub fn check_layout(&self, data_len: usize) -> Result<(), ArchiveError> {
+        // Aligned archives pad between entries, so check where the last one ends
+        let mut end = 0usize;
+        for (resource, start) in self.resources.iter().zip(self.resource_starts()) {
+            let size = resource.stored_len();
+            if start.checked_add(size).is_none_or(|resource_end| resource_end > data_len) {
+                return Err(ArchiveError::ResourceOutOfRange {
+                    filename: resource.filename.clone(),
+                    size,
+                    offset: start,
+                    data_len,
+                });
+            }
+            end = end.max(start + size);
+        }
+        if end != data_len {
+            return Err(ArchiveError::CorruptData(format!("Resource data is {} bytes but entries end at {}.", data_len, end)));
+        }
+        Ok(())
+    }
+}
+
+/// One step of [`ArchiveHeader::extraction_plan`].
+pub struct PlannedResource<'h> {
+    pub resource: &'h ResourceEntry,
+    pub start: usize, // where the resource starts in the uncompressed data, shared by deduplicated copies
+    pub path: PathBuf, // target under the extraction root, already checked with sandboxed_path
+}
+
+impl PlannedResource<'_> {
+    /// The resource's uncompressed bytes, see [`ResourceData::read_entry`].
+    pub fn read<'d>(&self, data: &'d mut ResourceData<'_>) -> Result<Cow<'d, [u8]>, ArchiveError> {
+        data.read_entry(self.resource, self.start)
+    }
+}
+
+/// Accumulates resource bytes for an archive. Byte-identical resources are
+/// stored once: later copies point at the first copy's offset instead.
+#[derive(Default)]
+pub struct ResourcePacker {
+    pub entries: Vec<ResourceEntry>,
+    pub data: Vec<u8>,
+    pub deduplicated_bytes: u64,
+    alignment: usize, // stored data starts on a multiple of this, 0/1 = back to back
+    stored_by_hash: HashMap<u64, Vec<(usize, usize)>>, // hash -> (offset, len)
+}
+
+impl ResourcePacker {
+    pub fn new(alignment: usize) -> Self {
+        Self { alignment, ..Default::default() }
+    }
+
+    pub fn add(&mut self, filename: String, data: &[u8], modified: Option<u64>, attributes: Option<u32>) -> Result<(), ArchiveError> {
+        self.add_from_reader(filename, &mut &data[..], data.len(), modified, attributes)
+    }
+
+    /// Read a resource straight into the archive buffer, so a file is never held
+    /// in memory twice while packing. A duplicate of an earlier resource is
+    /// dropped again and the entry points at the first copy instead.
+    pub fn add_from_reader(&mut self, filename: String, reader: &mut dyn Read, size_hint: usize, modified: Option<u64>, attributes: Option<u32>) -> Result<(), ArchiveError> {
+        // The stub refuses these anyway, better to fail the build than the install
+        let filename = checked_resource_name(filename)?;
+        if self.entries.len() >= MAX_RESOURCE_COUNT {
+            return Err(ArchiveError::TooManyResources);
+        }
+        let unpadded_len = self.data.len();
+        if self.alignment > 1 {
+            self.data.resize(self.data.len().next_multiple_of(self.alignment), 0);
+        }
+        let start = self.data.len();
+        self.data.reserve_exact(size_hint);
+        if let Err(e) = reader.read_to_end(&mut self.data) {
+            self.data.truncate(unpadded_len);
+            return Err(ArchiveError::ResourceRead { path: filename.into(), source: e });
+        }
+        let len = self.data.len() - start;
+        if len > u32::MAX as usize {
+            self.data.truncate(unpadded_len);
+            return Err(ArchiveError::ResourceTooLarge { filename, size: len });
+        }
+
+        let hash = {
+            use std::hash::{Hash, Hasher};
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            self.data[start..].hash(&mut hasher);
+            hasher.finish()
+        };
+        // Compare the bytes too, a matching hash alone isn't proof
+        let candidates = self.stored_by_hash.entry(hash).or_default();
+        let shared_offset = candidates.iter()
+            .find(|&&(shared, shared_len)| shared_len == len && self.data[shared..shared + len] == self.data[start..])
+            .map(|&(shared, _)| shared as u64);
+        let offset = match shared_offset {
+            Some(shared) => {
+                self.data.truncate(unpadded_len);
+                self.deduplicated_bytes += len as u64;
+                shared
+            }
+            None => {
+                candidates.push((start, len));
+                start as u64
+            }
+        };
+
+        self.entries.push(ResourceEntry {
+            filename,
+            size: len as u32,
+            modified,
+            attributes,
+            offset: Some(offset),
+            executable: false,
+            stored_size: None,
+        });
+        Ok(())
+    }
+
+    /// Add a resource compressed on its own (see [`ResourceEntry::stored_size`]),
+    /// for archives whose data as a whole is stored uncompressed.
+    pub fn add_compressed(&mut self, filename: String, data: &[u8], modified: Option<u64>, attributes: Option<u32>, compression: EntryCompression) -> Result<(), ArchiveError> {
+        let compressed = match compression {
+            EntryCompression::Store => None,
+            EntryCompression::Gzip => Some(gzip_compress(data)?),
+            EntryCompression::Auto => Some(gzip_compress(data)?).filter(|compressed| compressed.len() < data.len()),
+        };
+        let Some(compressed) = compressed else {
+            return self.add(filename, data, modified, attributes);
+        };
+        if data.len() > u32::MAX as usize {
+            return Err(ArchiveError::ResourceTooLarge { filename, size: data.len() });
+        }
+        self.add(filename, &compressed, modified, attributes)?;
+        if let Some(added) = self.entries.last_mut() {
+            added.stored_size = Some(added.size);
+            added.size = data.len() as u32;
+        }
+        Ok(())
+    }
+}
+
+/// Little-endian u32 at `offset`, or an error naming `what` when `bytes` is too short.
+/// Footers are always little-endian, whatever machine built them.
+pub fn read_u32_le(bytes: &[u8], offset: usize, what: &'static str) -> Result<u32, ArchiveError> {
+    offset.checked_add(4)
+        .and_then(|end| bytes.get(offset..end))
+        .and_then(|b| b.try_into().ok())
+        .map(u32::from_le_bytes)
+        .ok_or(ArchiveError::Truncated(what))
+}
+
+/// Validate a footer and return the header length and archive data length it declares.
+pub fn parse_footer(footer: &[u8]) -> Result<(usize, usize), ArchiveError> {
+    if footer.len() != FOOTER_SIZE {
+        return Err(ArchiveError::InvalidFooter(format!("Footer is {} bytes, expected {}.", footer.len(), FOOTER_SIZE)));
+    }
+    let header_length = read_u32_le(footer, 0, "footer")? as usize;
+    let archive_data_length = read_u32_le(footer, 4, "footer")? as usize;
+    let marker = &footer[8..];
+
+    if marker != FOOTER_MARKER {
+        return Err(ArchiveError::MarkerNotFound);
+    }
+    if header_length == 0 {
+        return Err(ArchiveError::InvalidFooter("Archive header is empty.".to_string()));
+    }
+    if header_length > archive_data_length {
+        return Err(ArchiveError::InvalidFooter(format!("Header length {} exceeds the archive length {}.", header_length, archive_data_length)));
+    }
+    if archive_data_length > MAX_ARCHIVE_SIZE {
+        return Err(ArchiveError::ArchiveTooLarge(archive_data_length));
+    }
+    Ok((header_length, archive_data_length))
+}
+
+/// Locate the archive in `bytes`, which end with our footer or, once the EXE
+/// is Authenticode signed, with the certificate table that signing appends
+/// after it (and 0-7 bytes of zero padding before the table).
+/// Returns the header length and where the archive data lies in `bytes`.
+pub fn archive_range(bytes: &[u8]) -> Result<(usize, Range<usize>), ArchiveError> {
+    match archive_range_ending_at(bytes, bytes.len()) {
+        Err(e) => {
+            let Some(signature) = pe_signature_range(bytes).filter(|range| range.end == bytes.len()) else {
+                return Err(e);
+            };
+            (0..8)
+                .map_while(|pad| signature.start.checked_sub(pad))
+                .take_while(|&end| bytes[end..signature.start].iter().all(|&b| b == 0))
+                .find_map(|end| archive_range_ending_at(bytes, end).ok())
+                .ok_or(e)
+        }
+        found => found,
+    }
+}
+
+fn archive_range_ending_at(bytes: &[u8], end: usize) -> Result<(usize, Range<usize>), ArchiveError> {
+    let bytes = &bytes[..end];
+    if bytes.len() < FOOTER_SIZE {
+        return Err(ArchiveError::Truncated("resource archive"));
+    }
+    let footer_start = bytes.len() - FOOTER_SIZE;
+    let (header_length, archive_data_length) = parse_footer(&bytes[footer_start..])?;
+    if archive_data_length > footer_start {
+        return Err(ArchiveError::InvalidFooter(format!("Declared archive length {} exceeds the {} bytes before the footer.", archive_data_length, footer_start)));
+    }
+    let archive_start = footer_start - archive_data_length;
+    // Appended to an EXE, the archive can't start inside the EXE's own image
+    if bytes.starts_with(b"MZ")
+        && let Some(image_end) = pe_image_end(bytes)
+        && archive_start < image_end {
+        return Err(ArchiveError::InvalidFooter(format!("Archive start {} lies inside the EXE image (which ends at {}).", archive_start, image_end)));
+    }
+    Ok((header_length, archive_start..footer_start))
+}
+
+/// End of the last section's raw data in a PE file, i.e. where appended data
+/// may begin. None when the headers can't be read.
+pub fn pe_image_end(bytes: &[u8]) -> Option<usize> {
+    let pe_offset = read_u32_le(bytes, 0x3c, "DOS header").ok()? as usize;
+    if bytes.get(pe_offset..pe_offset.checked_add(4)?)? != b"PE\0\0" {
+        return None;
+    }
+    let section_count = u16::from_le_bytes(bytes.get(pe_offset + 6..pe_offset + 8)?.try_into().ok()?) as usize;
+    let optional_header_size = u16::from_le_bytes(bytes.get(pe_offset + 20..pe_offset + 22)?.try_into().ok()?) as usize;
+    let section_table = pe_offset + 24 + optional_header_size;
+    let mut end = 0usize;
+    for section in 0..section_count {
+        let entry = section_table + section * 40;
+        let raw_size = read_u32_le(bytes, entry + 16, "section table").ok()? as usize;
+        let raw_pointer = read_u32_le(bytes, entry + 20, "section table").ok()? as usize;
+        end = end.max(raw_pointer.checked_add(raw_size)?);
+    }
+    Some(end)
+}
+
+/// Where the Authenticode certificate table of a signed PE lies in `bytes`, read
+/// from the optional header's security data directory. None for unsigned files
+/// or when the headers can't be read.
+pub fn pe_signature_range(bytes: &[u8]) -> Option<Range<usize>> {
+    let security = security_directory(bytes)?;
+    let offset = read_u32_le(bytes, security, "data directories").ok()? as usize;
+    let size = read_u32_le(bytes, security + 4, "data directories").ok()? as usize;
+    if offset == 0 || size == 0 || offset.checked_add(size)? > bytes.len() {
+        return None;
+    }
+    Some(offset..offset + size)
+}
+
+/// Remove the Authenticode signature from a PE: clear the security data
+/// directory and drop the certificate table (and the padding before it) where
+/// it ends the file. Appending
+/// to a signed PE breaks its signature, stripping it first leaves the result
+/// cleanly unsigned instead. Returns whether there was anything to remove,
+/// including a directory entry left pointing past the end of the file.
+pub fn strip_signature(pe: &mut Vec<u8>) -> bool {
+    let Some(security) = security_directory(pe) else {
+        return false;
+    };
+    if pe[security..security + 8].iter().all(|&b| b == 0) {
+        return false;
+    }
+    if let Some(signature) = pe_signature_range(pe).filter(|range| range.end == pe.len()) {
+        // Also drop the zero padding signing adds, but never cut into a section
+        let image_end = pe_image_end(pe).unwrap_or(signature.start);
+        let mut end = signature.start;
+        while end > image_end && signature.start - end < 7 && pe[end - 1] == 0 {
+            end -= 1;
+        }
+        pe.truncate(end);
+    }
+    pe[security..security + 8].fill(0);
+    true
+}
+
+// File offset of the security data directory entry (certificate table offset
+// and size) in a PE's optional header, if the headers have one
+fn security_directory(bytes: &[u8]) -> Option<usize> {
+    let pe_offset = read_u32_le(bytes, 0x3c, "DOS header").ok()? as usize;
+    if bytes.get(pe_offset..pe_offset.checked_add(4)?)? != b"PE\0\0" {
+        return None;
+    }
+    let optional_header = pe_offset + 24;
+    let magic = u16::from_le_bytes(bytes.get(optional_header..optional_header + 2)?.try_into().ok()?);
+    // Data directory count and the directories themselves sit further in for PE32+
+    let directory_count_offset = match magic {
+        0x10b => optional_header + 92,
+        0x20b => optional_header + 108,
+        _ => return None,
+    };
+    if read_u32_le(bytes, directory_count_offset, "optional header").ok()? <= 4 {
+        return None;
+    }
+    // The security directory is entry 4, and unlike the others holds a file offset
+    let security = directory_count_offset + 4 + 4 * 8;
+    bytes.get(security..security + 8)?;
+    Some(security)
+}
+
+/// Check that `stub` looks like a runnable PE before an archive is appended to
+/// it: `MZ` signature, a PE header and sections that lie inside the file.
+pub fn check_stub(stub: &[u8]) -> Result<(), ArchiveError> {
+    if stub.is_empty() {
+        return Err(ArchiveError::StubMissing);
+    }
+    if !stub.starts_with(b"MZ") {
+        return Err(ArchiveError::InvalidStub("no MZ signature".to_string()));
+    }
+    match pe_image_end(stub) {
+        None => Err(ArchiveError::InvalidStub("no readable PE header".to_string())),
+        Some(0) => Err(ArchiveError::InvalidStub("the PE header lists no sections".to_string())),
+        Some(end) if end > stub.len() => Err(ArchiveError::InvalidStub(format!(
+            "truncated, its sections end at {} but the file is {} bytes", end, stub.len()
+        ))),
+        Some(_) => Ok(()),
+    }
+}
+
+/// Serialize `header` and assemble the archive: `[header JSON][resource data][footer]`,
+/// ready to append to a stub. `resource_data` is stored as given, so it must
+/// already be compressed if the header says so.
+pub fn pack_archive(header: &ArchiveHeader, resource_data: &[u8]) -> Result<Vec<u8>, ArchiveError> {
+    pack_archive_aligned(header, resource_data, 0, 1)
+}
+
+/// Like [`pack_archive`], but pads the header with trailing spaces (still valid
+/// JSON) so the resource data starts on a multiple of `alignment` once the
+/// archive is written after `prefix_len` bytes (the stub, for appended archives).
+pub fn pack_archive_aligned(header: &ArchiveHeader, resource_data: &[u8], prefix_len: usize, alignment: usize) -> Result<Vec<u8>, ArchiveError> {
+    let mut header_json = serde_json::to_string(header).map_err(ArchiveError::InvalidHeader)?;
+    if alignment > 1 {
+        let misalignment = (prefix_len + header_json.len()) % alignment;
+        if misalignment != 0 {
+            header_json.push_str(&" ".repeat(alignment - misalignment));
+        }
+    }
+    if header_json.len() > MAX_HEADER_SIZE {
+        return Err(ArchiveError::HeaderTooLarge { size: header_json.len(), resources: header.resources.len() });
+    }
+    write_archive(header_json.as_bytes(), resource_data)
+}
+
+/// Parse header JSON as found at the start of an archive.
+pub fn parse_header(header_json: &[u8]) -> Result<ArchiveHeader, ArchiveError> {
+    serde_json::from_slice(header_json).map_err(ArchiveError::InvalidHeader)
+}
+
+/// Locate the archive at the end of `bytes` (a packed EXE, pack file or PE
+/// resource) and parse its header. Returns the header, where the archive starts
+/// and where its resource data lies in `bytes`.
+pub fn unpack_header(bytes: &[u8]) -> Result<(ArchiveHeader, usize, Range<usize>), ArchiveError> {
+    let (header_length, range) = archive_range(bytes)?;
+    let header = parse_header(&bytes[range.start..range.start + header_length])?;
+    Ok((header, range.start, range.start + header_length..range.end))
+}
+
+/// What a [`LayoutRegion`] of a packed file holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionKind {
+    Stub,
+    Header,
+    Resource,
+    Padding, // alignment gap between resources
+    CompressedData, // the whole data stream or one seekable block, resources can't be told apart
+    Footer,
+    Signature, // Authenticode certificate table (and its padding) appended by signing
+}
+
+/// One byte range of a packed file, see [`archive_layout`].
+#[derive(Debug, Clone)]
+pub struct LayoutRegion {
+    pub kind: RegionKind,
+    pub label: String, // "Stub", a resource's filename, "Compressed block 2", ...
+    pub range: Range<usize>,
+}
+
+/// Map the packed file in `bytes` into consecutive regions covering all of it:
+/// the stub (if any), header, each resource or compressed block, alignment
+/// padding, the footer and a trailing signature. Resources deduplicated to the same bytes share one
+/// region. Fails if the header's entries don't fit the footer's recorded lengths.
+pub fn archive_layout(bytes: &[u8]) -> Result<Vec<LayoutRegion>, ArchiveError> {
+    let (header, archive_start, data_range) = unpack_header(bytes)?;
+    let region = |kind, label: String, range| LayoutRegion { kind, label, range };
+    let mut regions = Vec::new();
+    if archive_start > 0 {
+        regions.push(region(RegionKind::Stub, "Stub".to_string(), 0..archive_start));
+    }
+    regions.push(region(RegionKind::Header, "Header".to_string(), archive_start..data_range.start));
+
+    if !header.is_compressed {
+        // Resource offsets only describe the stored bytes when they aren't compressed as a whole
+        header.check_layout(data_range.len())?;
+        let mut blocks: Vec<LayoutRegion> = Vec::new();
+        for (resource, start) in header.resources.iter().zip(header.resource_starts()) {
+            let start = data_range.start + start;
+            let range = start..start + resource.stored_len();
+            match blocks.iter_mut().find(|block| block.range == range) {
+                Some(block) => block.label.push_str(&format!(", {}", resource.filename)),
+                None => blocks.push(region(RegionKind::Resource, resource.filename.clone(), range)),
+            }
+        }
+        blocks.sort_by_key(|block| block.range.start);
+        let mut offset = data_range.start;
+        for block in blocks {
+            if block.range.start > offset {
+                regions.push(region(RegionKind::Padding, "Padding".to_string(), offset..block.range.start));
+            }
+            offset = offset.max(block.range.end);
+            regions.push(block);
+        }
+    } else if !header.compressed_blocks.is_empty() {
+        let mut offset = data_range.start;
+        for (index, &length) in header.compressed_blocks.iter().enumerate() {
+            let end = offset + length as usize;
+            if end > data_range.end {
+                return Err(ArchiveError::CorruptData(format!("Compressed block {} ends past the resource data.", index + 1)));
+            }
+            regions.push(region(RegionKind::CompressedData, format!("Compressed block {}", index + 1), offset..end));
+            offset = end;
+        }
+        if offset != data_range.end {
+            return Err(ArchiveError::CorruptData(format!("Resource data is {} bytes but compressed blocks end at {}.", data_range.len(), offset - data_range.start)));
+        }
+    } else {
+        regions.push(region(RegionKind::CompressedData, "Compressed resource data".to_string(), data_range.clone()));
+    }
+
+    let footer_end = data_range.end + FOOTER_SIZE;
+    regions.push(region(RegionKind::Footer, "Footer".to_string(), data_range.end..footer_end));
+    if footer_end < bytes.len() {
+        regions.push(region(RegionKind::Signature, "Signature".to_string(), footer_end..bytes.len()));
+    }
+    Ok(regions)
+}
+
+/// Unpack every resource in the archive at the end of `bytes`: the header and
+/// each resource's uncompressed bytes, in header order. The data is checked
+/// against the header's checksum and layout first. `limit` caps decompression.
+pub fn unpack_archive(bytes: &[u8], limit: u64) -> Result<(ArchiveHeader, Vec<Vec<u8>>), ArchiveError> {
+    let mut reader = ArchiveReader::with_limit(bytes, limit)?;
+    let data_len = reader.data.uncompressed_len()?;
+    reader.header.check_layout(data_len)?;
+    let resources = reader.resources()
+        .map(|resource| resource.map(|(_, data)| data))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((reader.header, resources))
+}
+
+/// How [`ResourcePacker::add_compressed`] stores one resource
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum EntryCompression {
+    /// Whichever of stored and gzipped is smaller
+    #[default]
+    Auto,
+    Store,
+    Gzip,
+}
+
+/// How [`ArchiveBuilder`] stores resource data
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Compression {
+    #[default]
+    None,
+    /// One gzip stream, inflated in full by the stub before extracting
+    Gzip,
+    /// Independent [`COMPRESSION_BLOCK_SIZE`] gzip blocks, inflated as resources are read
+    Seekable,
+}
+
+/// Packs resources into an EXE without the compiler GUI:
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use resource_archive::{ArchiveBuilder, Compression};
+///
+/// let mut builder = ArchiveBuilder::new("%TEMP%\\my_app");
+/// builder.add_file("build/my_app.exe")?.add_file_as("assets/logo.png", "assets/logo.png")?;
+/// builder.set_main("my_app.exe").set_compression(Compression::Gzip);
+/// let stub = std::fs::read("stub.exe")?;
+/// std::fs::write("my_app_packed.exe", builder.build(&stub)?)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Other stub options are set through [`ArchiveBuilder::header_mut`].
+pub struct ArchiveBuilder {
+    header: ArchiveHeader,
+    packer: ResourcePacker,
+    compression: Compression,
+}
+
+impl ArchiveBuilder {
+    /// `extraction_path` is where the stub extracts to, relative paths being
+    /// relative to the packed EXE's working directory.
+    pub fn new(extraction_path: impl Into<String>) -> Self {
+        Self {
+            header: ArchiveHeader {
+                extraction_path: extraction_path.into(),
+                execution_style: "normal".to_string(),
+                ..Default::default()
+            },
+            packer: ResourcePacker::new(0),
+            compression: Compression::None,
+        }
+    }
+
+    /// The header written into the archive. Its resources and compression
+    /// fields are filled in by [`ArchiveBuilder::build`].
+    pub fn header_mut(&mut self) -> &mut ArchiveHeader {
+        &mut self.header
+    }
+
+    /// Add a file from disk, stored under its file name.
+    pub fn add_file(&mut self, path: impl AsRef<Path>) -> Result<&mut Self, ArchiveError> {
+        let path = path.as_ref();
+        let filename = path.file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| ArchiveError::InvalidResourceName(path.to_string_lossy().into_owned()))?
+            .to_string();
+        self.add_file_as(path, filename)
+    }
+
+    /// Add a file from disk, stored as `filename` (which may include subfolders).
+    /// Its modified time and executable bit are kept.
+    pub fn add_file_as(&mut self, path: impl AsRef<Path>, filename: impl Into<String>) -> Result<&mut Self, ArchiveError> {
+        let path = path.as_ref();
+        let filename = checked_resource_name(filename.into())?;
+        let read_error = |source| ArchiveError::ResourceRead { path: path.to_path_buf(), source };
+        let mut file = std::fs::File::open(path).map_err(read_error)?;
+        let metadata = file.metadata().map_err(read_error)?;
+        let modified = metadata.modified().ok()
+            .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|since_epoch| since_epoch.as_millis() as u64);
+        self.packer.add_from_reader(filename, &mut file, metadata.len() as usize, modified, None)
+            .map_err(|e| match e {
+                // Name the file on disk rather than its name in the archive
+                ArchiveError::ResourceRead { source, .. } => read_error(source),
+                other => other,
+            })?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            if let Some(added) = self.packer.entries.last_mut() {
+                added.executable = metadata.permissions().mode() & 0o111 != 0;
+            }
+        }
+        Ok(self)
+    }
+
+    /// Add a resource from memory, stored as `filename` (which may include subfolders).
+    pub fn add_bytes(&mut self, filename: impl Into<String>, data: &[u8]) -> Result<&mut Self, ArchiveError> {
+        let filename = checked_resource_name(filename.into())?;
+        self.packer.add(filename, data, None, None)?;
+        Ok(self)
+    }
+
+    /// The resource the stub launches after extracting, empty for none.
+    pub fn set_main(&mut self, filename: impl Into<String>) -> &mut Self {
+        self.header.main_file = filename.into();
+        self.header.main_is_external = false;
+        self
+    }
+
+    pub fn set_compression(&mut self, compression: Compression) -> &mut Self {
+        self.compression = compression;
+        self
+    }
+
+    /// The packed EXE: `stub` followed by the archive. The stub is checked with
+    /// [`check_stub`] first.
+    pub fn build(self, stub: &[u8]) -> Result<Vec<u8>, ArchiveError> {
+        check_stub(stub)?;
+        let pack = self.build_pack()?;
+        let mut exe = Vec::with_capacity(stub.len() + pack.len());
+        exe.extend_from_slice(stub);
+        exe.extend_from_slice(&pack);
+        Ok(exe)
+    }
+
+    /// Just the archive, for a sidecar `.rscpack` file or a PE resource.
+    pub fn build_pack(self) -> Result<Vec<u8>, ArchiveError> {
+        let mut header = self.header;
+        header.resources = self.packer.entries;
+        if !header.main_file.is_empty() && !header.main_is_external {
+            let main = header.resources.iter_mut()
+                .find(|entry| entry.filename == header.main_file)
+                .ok_or_else(|| ArchiveError::MainFileNotFound(header.main_file.clone()))?;
+            main.executable = true;
+        }
+
+        let data = self.packer.data;
+        let compressed = match self.compression {
+            Compression::None => None,
+            Compression::Gzip => Some((gzip_compress(&data)?, Vec::new())),
+            Compression::Seekable => Some(compress_blocks(&data, COMPRESSION_BLOCK_SIZE)?),
+        };
+        let stored = finish_resource_data(&mut header, data, compressed);
+        pack_archive(&header, &stored)
+    }
+}
+
+fn checked_resource_name(filename: String) -> Result<String, ArchiveError> {
+    if is_safe_resource_path(&filename) {
+        Ok(filename)
+    } else {
+        Err(ArchiveError::InvalidResourceName(filename))
+    }
+}
+
+/// Reads a packed EXE (or pack file) without extracting it: the header, and
+/// each resource's uncompressed bytes on demand. Seekable archives only inflate
+/// the blocks a resource lies in.
+pub struct ArchiveReader<'a> {
+    header: ArchiveHeader,
+    data: ResourceData<'a>,
+    starts: Vec<usize>,
+}
+
+impl<'a> ArchiveReader<'a> {
+    /// Parse the archive at the end of `bytes`, checking the footer and the
+    /// data checksum. Decompression is capped at the header's extraction limit.
+    pub fn new(bytes: &'a [u8]) -> Result<Self, ArchiveError> {
+        let (header, _, _) = unpack_header(bytes)?;
+        Self::with_limit(bytes, header.extract_limit())
+    }
+
+    /// Like [`ArchiveReader::new`], with decompression capped at `limit` bytes.
+    pub fn with_limit(bytes: &'a [u8], limit: u64) -> Result<Self, ArchiveError> {
+        let (header, _, data_range) = unpack_header(bytes)?;
+        let resource_bytes = &bytes[data_range];
+        header.check_data_crc(resource_bytes)?;
+        let data = header.open_resource_data(resource_bytes, limit)?;
+        let starts = header.resource_starts();
+        Ok(Self { header, data, starts })
+    }
+
+    pub fn header(&self) -> &ArchiveHeader {
+        &self.header
+    }
+
+    /// Uncompressed bytes of the resource at `index` in the header
+    pub fn read(&mut self, index: usize) -> Result<Vec<u8>, ArchiveError> {
+        let resource = self.header.resources.get(index)
+            .ok_or(ArchiveError::NoSuchResource { index, count: self.header.resources.len() })?;
+        Ok(self.data.read_entry(resource, self.starts[index])?.into_owned())
+    }
+
+    /// Uncompressed bytes of the resource stored as `filename`, None if there is none
+    pub fn read_file(&mut self, filename: &str) -> Result<Option<Vec<u8>>, ArchiveError> {
+        match self.header.resources.iter().position(|resource| resource.filename == filename) {
+            Some(index) => self.read(index).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Every resource with its uncompressed bytes, in header order
+    pub fn resources(&mut self) -> Resources<'_, 'a> {
+        Resources {
+            entries: self.header.resources.iter().zip(self.starts.iter()),
+            data: &mut self.data,
+        }
+    }
+}
+
+/// Iterator returned by [`ArchiveReader::resources`]
+pub struct Resources<'r, 'a> {
+    entries: std::iter::Zip<std::slice::Iter<'r, ResourceEntry>, std::slice::Iter<'r, usize>>,
+    data: &'r mut ResourceData<'a>,
+}
+
+impl<'r> Iterator for Resources<'r, '_> {
+    type Item = Result<(&'r ResourceEntry, Vec<u8>), ArchiveError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (resource, &start) = self.entries.next()?;
+        Some(self.data.read_entry(resource, start).map(|data| (resource, data.into_owned())))
+    }
+}
+
+/// Assemble an archive from its header JSON and (possibly compressed) resource
+/// data: `[header JSON][resource data][footer]`, ready to append to a stub.
+pub fn write_archive(header_json: &[u8], resource_data: &[u8]) -> Result<Vec<u8>, ArchiveError> {
+    let archive_data_length = header_json.len() + resource_data.len();
+    if archive_data_length > MAX_ARCHIVE_SIZE {
+        return Err(ArchiveError::ArchiveTooLarge(archive_data_length));
+    }
+    let mut pack = Vec::with_capacity(archive_data_length + FOOTER_SIZE);
+    pack.extend_from_slice(header_json);
+    pack.extend_from_slice(resource_data);
+    // Checked even under the limit, a truncated length would be misread by the stub
+    let footer_length = |length: usize| u32::try_from(length).map_err(|_| ArchiveError::ArchiveTooLarge(archive_data_length));
+    pack.extend_from_slice(&footer_length(header_json.len())?.to_le_bytes());
+    pack.extend_from_slice(&footer_length(archive_data_length)?.to_le_bytes());
+    pack.extend_from_slice(FOOTER_MARKER);
+    Ok(pack)
+}
+
+/// Gzip `data`. The gzip header fields are pinned (no timestamp, "unknown" OS)
+/// so identical inputs always compress to identical bytes and builds are reproducible.
+pub fn gzip_compress(data: &[u8]) -> Result<Vec<u8>, ArchiveError> {
+    let mut encoder = flate2::GzBuilder::new()
+        .mtime(0)
+        .operating_system(255)
+        .write(Vec::new(), flate2::Compression::default());
+    encoder.write_all(data).map_err(ArchiveError::CompressionFailed)?;
+    encoder.finish().map_err(ArchiveError::CompressionFailed)
+}
+
+/// Gzip `data` in independent `block_size` blocks (seekable compression),
+/// returning each block's compressed length so any block can be found and
+/// inflated on its own.
+pub fn compress_blocks(data: &[u8], block_size: usize) -> Result<(Vec<u8>, Vec<u32>), ArchiveError> {
+    let mut compressed = Vec::new();
+    let mut blocks = Vec::new();
+    for block in data.chunks(block_size) {
+        let block = gzip_compress(block)?;
+        blocks.push(block.len() as u32);
+        compressed.extend_from_slice(&block);
+    }
+    Ok((compressed, blocks))
+}
+
+/// Settle how resource `data` is stored: `compressed` (from [`gzip_compress`], or
+/// [`compress_blocks`] with [`COMPRESSION_BLOCK_SIZE`] blocks) is only kept when it
+/// is actually smaller. Sets the header's compression fields and checksum to
+/// match and returns the bytes to pack.
+pub fn finish_resource_data(header: &mut ArchiveHeader, data: Vec<u8>, compressed: Option<(Vec<u8>, Vec<u32>)>) -> Vec<u8> {
+    header.compression_block_size = 0;
+    header.compressed_blocks = Vec::new();
+    let stored = match compressed {
+        Some((compressed, blocks)) if compressed.len() < data.len() => {
+            header.is_compressed = true;
+            if !blocks.is_empty() {
+                header.compression_block_size = COMPRESSION_BLOCK_SIZE as u32;
+                header.compressed_blocks = blocks;
+            }
+            compressed
+        }
+        _ => {
+            header.is_compressed = false;
+            data
+        }
+    };
+    let mut crc = flate2::Crc::new();
+    crc.update(&stored);
+    header.data_crc32 = Some(crc.sum());
+    stored
+}
+
+/// Inflate gzip'd resource data, failing instead of writing past `limit` bytes.
+pub fn decompress_resources(resource_bytes: &[u8], limit: u64) -> Result<Vec<u8>, ArchiveError> {
+    // Cap the decoder one byte past the limit so a gzip bomb is detected, not inflated
+    let mut decompressor = GzDecoder::new(resource_bytes).take(limit.saturating_add(1));
+    let mut decompressed = Vec::new();
+    decompressor.read_to_end(&mut decompressed)
+        .map_err(ArchiveError::DecompressionFailed)?;
+    if decompressed.len() as u64 > limit {
+        return Err(ArchiveError::ExtractLimitExceeded(limit));
+    }
+    Ok(decompressed)
+}
+
+/// Random access into block-compressed resource data (seekable compression).
+/// Each block is an independent gzip stream, so only the blocks covering a
+/// requested range are inflated; the last block is kept for the next read.
+pub struct BlockReader<'a> {
+    data: &'a [u8],
+    block_size: usize,
+    bounds: Vec<usize>, // start of each compressed block in `data`, plus the end
+    cached: Option<(usize, Vec<u8>)>,
+}
+
+impl<'a> BlockReader<'a> {
+    pub fn new(data: &'a [u8], block_size: u32, blocks: &[u32]) -> Result<Self, ArchiveError> {
+        if block_size == 0 {
+            return Err(ArchiveError::CorruptData("Compressed block size is zero.".to_string()));
+        }
+        let mut bounds = vec![0usize];
+        for &length in blocks {
+            bounds.push(bounds[bounds.len() - 1] + length as usize);
+        }
+        if bounds[bounds.len() - 1] != data.len() {
+            return Err(ArchiveError::CorruptData(format!("Compressed blocks account for {} bytes but the data is {}.", bounds[bounds.len() - 1], data.len())));
+        }
+        Ok(Self { data, block_size: block_size as usize, bounds, cached: None })
+    }
+
+    fn block(&mut self, index: usize) -> Result<&[u8], ArchiveError> {
+        if self.cached.as_ref().map(|(cached, _)| *cached) != Some(index) {
+            if index + 1 >= self.bounds.len() {
+                return Err(ArchiveError::Incomplete);
+            }
+            let compressed = &self.data[self.bounds[index]..self.bounds[index + 1]];
+            let block = decompress_resources(compressed, self.block_size as u64)?;
+            // Every block but the last holds exactly block_size bytes, or offsets would shift
+            let is_last = index + 2 == self.bounds.len();
+            if block.is_empty() || (!is_last && block.len() != self.block_size) {
+                return Err(ArchiveError::CorruptData(format!("Compressed block {} has the wrong size ({} bytes).", index, block.len())));
+            }
+            self.cached = Some((index, block));
+        }
+        Ok(&self.cached.as_ref().unwrap().1)
+    }
+
+    pub fn read(&mut self, start: usize, size: usize) -> Result<Vec<u8>, ArchiveError> {
+        let mut out = Vec::with_capacity(size);
+        let mut position = start;
+        while out.len() < size {
+            let within = position % self.block_size;
+            let block = self.block(position / self.block_size)?;
+            if within >= block.len() {
+                return Err(ArchiveError::Incomplete);
+            }
+            let take = (size - out.len()).min(block.len() - within);
+            out.extend_from_slice(&block[within..within + take]);
+            position += take;
+        }
+        Ok(out)
+    }
+
+    /// Total decompressed size, inflating every block once
+    pub fn decompressed_len(&mut self) -> Result<usize, ArchiveError> {
+        let mut total = 0;
+        for index in 0..self.bounds.len() - 1 {
+            total += self.block(index)?.len();
+        }
+        Ok(total)
+    }
+}
+
+/// Resource data as stored after the header, opened according to the header's
+/// compression fields: stored as-is, one gzip stream (inflated up front) or
+/// independent gzip blocks (inflated as they are read).
+pub enum ResourceData<'a> {
+    Stored(&'a [u8]),
+    Inflated(Vec<u8>),
+    Blocks(BlockReader<'a>),
+}
+
+impl<'a> ResourceData<'a> {
+    /// `limit` caps how many bytes a single gzip stream may inflate to.
+    pub fn open(resource_bytes: &'a [u8], is_compressed: bool, block_size: u32, blocks: &[u32], limit: u64) -> Result<Self, ArchiveError> {
+        if is_compressed && !blocks.is_empty() {
+            BlockReader::new(resource_bytes, block_size, blocks).map(ResourceData::Blocks)
+        } else if is_compressed {
+            decompress_resources(resource_bytes, limit).map(ResourceData::Inflated)
+        } else {
+            Ok(ResourceData::Stored(resource_bytes))
+        }
+    }
+
+    /// `size` bytes of uncompressed data starting at `start`
+    pub fn read(&mut self, start: usize, size: usize) -> Result<Cow<'_, [u8]>, ArchiveError> {
+        let bytes = match self {
+            ResourceData::Stored(bytes) => *bytes,
+            ResourceData::Inflated(bytes) => &bytes[..],
+            ResourceData::Blocks(reader) => return reader.read(start, size).map(Cow::Owned),
+        };
+        start.checked_add(size)
+            .and_then(|end| bytes.get(start..end))
+            .map(Cow::Borrowed)
+            .ok_or(ArchiveError::Incomplete)
+    }
+
+    /// The bytes of `entry`, which starts at `start`, inflated if the entry is
+    /// compressed on its own (see [`ResourceEntry::stored_size`])
+    pub fn read_entry(&mut self, entry: &ResourceEntry, start: usize) -> Result<Cow<'_, [u8]>, ArchiveError> {
+        let stored = self.read(start, entry.stored_len())?;
+        if entry.stored_size.is_none() {
+            return Ok(stored);
+        }
+        let inflated = decompress_resources(&stored, entry.size as u64)?;
+        if inflated.len() != entry.size as usize {
+            return Err(ArchiveError::CorruptData(format!("{} inflates to {} bytes, expected {}.", entry.filename, inflated.len(), entry.size)));
+        }
+        Ok(Cow::Owned(inflated))
+    }
+
+    /// Uncompressed length of the data. Block-compressed data is inflated to find out.
+    pub fn uncompressed_len(&mut self) -> Result<usize, ArchiveError> {
+        match self {
+            ResourceData::Stored(bytes) => Ok(bytes.len()),
+            ResourceData::Inflated(bytes) => Ok(bytes.len()),
+            ResourceData::Blocks(reader) => reader.decompressed_len(),
+        }
+    }
+
+    /// All of the uncompressed data
+    pub fn into_bytes(mut self) -> Result<Vec<u8>, ArchiveError> {
+        match self {
+            ResourceData::Stored(bytes) => Ok(bytes.to_vec()),
+            ResourceData::Inflated(bytes) => Ok(bytes),
+            ResourceData::Blocks(_) => {
+                let len = self.uncompressed_len()?;
+                self.read(0, len).map(Cow::into_owned)
+            }
+        }
+    }
+}
+
+/// Resource filenames may include subfolders ("config/app.ini") but must stay
+/// inside the extraction directory: no absolute paths, drive prefixes or "..".
+/// Both separators are checked whatever the host, since the stub extracts on
+/// Windows; ':' is refused too (drive letters and NTFS alternate data streams),
+/// as are parts made of only dots and spaces, which Windows trims down to "..".
+pub fn is_safe_resource_path(filename: &str) -> bool {
+    !filename.is_empty()
+        && !filename.starts_with(['/', '\\'])
+        && !filename.contains(':')
+        && filename.split(['/', '\\']).all(|part| part.is_empty() || part == "." || !part.trim_end_matches(['.', ' ']).is_empty())
+        && Path::new(filename).components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+}
+
+/// Whether recursively deleting `target` could take out something important:
+/// a drive root, anything inside or containing one of the system `trees`
+/// (Windows, Program Files, ...), or one of the `folders` (profile, temp, the
+/// EXE's own folder) or a folder containing them. Subfolders of `folders` may
+/// be deleted. Paths are compared as given, so resolve them first.
+pub fn is_dangerous_to_delete(target: &Path, trees: &[PathBuf], folders: &[PathBuf]) -> bool {
+    target.parent().is_none()
+        || trees.iter().any(|tree| target.starts_with(tree) || tree.starts_with(target))
+        || folders.iter().any(|folder| folder.starts_with(target))
+}
+
+/// Where `filename` is extracted under `root`, or an error if it would land
+/// outside it (see [`is_safe_resource_path`]).
+pub fn sandboxed_path(root: &Path, filename: &str) -> Result<PathBuf, ArchiveError> {
+    if !is_safe_resource_path(filename) {
+        return Err(ArchiveError::UnsafePath(filename.to_string()));
+    }
+    Ok(root.join(filename))
+}
@@ -0,0 +1,123 @@
+// ArchiveBuilder and ArchiveReader: packing without the GUI and reading the result back.
+
+use std::fs;
+
+use resource_archive::*;
+
+// Smallest image check_stub accepts: DOS header, PE header, one section
+fn stub() -> Vec<u8> {
+    let mut stub = vec![0u8; 0x200];
+    stub[..2].copy_from_slice(b"MZ");
+    stub[0x3c..0x40].copy_from_slice(&0x40u32.to_le_bytes());
+    stub[0x40..0x44].copy_from_slice(b"PE\0\0");
+    stub[0x46..0x48].copy_from_slice(&1u16.to_le_bytes());
+    // No optional header, so the section table follows the file header at 0x58
+    stub[0x58..0x5d].copy_from_slice(b".text");
+    stub[0x68..0x6c].copy_from_slice(&0x100u32.to_le_bytes());
+    stub[0x6c..0x70].copy_from_slice(&0x100u32.to_le_bytes());
+    stub
+}
+
+fn text(len: usize) -> Vec<u8> {
+    "the quick brown fox jumps over the lazy dog ".bytes().cycle().take(len).collect()
+}
+
+#[test]
+fn builds_from_files_and_reads_back() {
+    let dir = std::env::temp_dir().join(format!("rsc_builder_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("app.exe"), text(3000)).unwrap();
+    fs::write(dir.join("logo.png"), b"not really a png").unwrap();
+
+    let mut builder = ArchiveBuilder::new("out");
+    builder.add_file(dir.join("app.exe")).unwrap()
+        .add_file_as(dir.join("logo.png"), "assets/logo.png").unwrap();
+    builder.set_main("app.exe").set_compression(Compression::Gzip);
+    builder.header_mut().launch_args = "--fast".to_string();
+    let exe = builder.build(&stub()).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(exe.starts_with(&stub()));
+
+    let mut reader = ArchiveReader::new(&exe).unwrap();
+    let header = reader.header();
+    assert_eq!(header.main_file, "app.exe");
+    assert_eq!(header.launch_args, "--fast");
+    assert!(header.is_compressed);
+    assert!(header.resources[0].executable);
+    assert!(header.resources[0].modified.is_some());
+
+    let resources: Vec<(String, Vec<u8>)> = reader.resources()
+        .map(|resource| resource.map(|(entry, data)| (entry.filename.clone(), data)))
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(resources, vec![
+        ("app.exe".to_string(), text(3000)),
+        ("assets/logo.png".to_string(), b"not really a png".to_vec()),
+    ]);
+}
+
+#[test]
+fn seekable_archives_read_single_files() {
+    let big = text(COMPRESSION_BLOCK_SIZE + 1000);
+    let mut builder = ArchiveBuilder::new("out");
+    builder.add_bytes("big.txt", &big).unwrap().add_bytes("small.txt", b"small").unwrap();
+    builder.set_compression(Compression::Seekable);
+    let exe = builder.build(&stub()).unwrap();
+
+    let mut reader = ArchiveReader::new(&exe).unwrap();
+    assert_eq!(reader.header().compressed_blocks.len(), 2);
+    assert_eq!(reader.read_file("small.txt").unwrap(), Some(b"small".to_vec()));
+    assert_eq!(reader.read_file("big.txt").unwrap(), Some(big));
+    assert_eq!(reader.read_file("missing.txt").unwrap(), None);
+    assert!(reader.read(2).is_err());
+}
+
+#[test]
+fn incompressible_data_is_stored() {
+    let mut builder = ArchiveBuilder::new("out");
+    builder.add_bytes("tiny.txt", b"x").unwrap().set_compression(Compression::Gzip);
+    let pack = builder.build_pack().unwrap();
+    let (header, resources) = unpack_archive(&pack, 1024).unwrap();
+    assert!(!header.is_compressed);
+    assert_eq!(resources, vec![b"x".to_vec()]);
+}
+
+#[test]
+fn main_file_must_be_packed() {
+    let mut builder = ArchiveBuilder::new("out");
+    builder.add_bytes("a.txt", b"a").unwrap().set_main("b.exe");
+    let err = builder.build(&stub()).unwrap_err();
+    assert!(matches!(err, ArchiveError::MainFileNotFound(ref name) if name == "b.exe"));
+    assert_eq!(err.to_string(), "Main file b.exe is not one of the resources.");
+}
+
+#[test]
+fn unsafe_names_are_rejected() {
+    let mut builder = ArchiveBuilder::new("out");
+    assert!(builder.add_bytes("../outside.txt", b"x").is_err());
+    assert!(builder.add_bytes("", b"x").is_err());
+    assert!(matches!(builder.add_file("/definitely/missing/file.txt"), Err(ArchiveError::ResourceRead { .. })));
+}
+
+#[test]
+fn empty_stub_is_rejected() {
+    let mut builder = ArchiveBuilder::new("out");
+    builder.add_bytes("a.txt", b"a").unwrap();
+    assert!(matches!(builder.build(&[]), Err(ArchiveError::StubMissing)));
+}
+
+#[test]
+fn stub_must_be_a_pe() {
+    check_stub(&stub()).unwrap();
+    let mut truncated = stub();
+    truncated.truncate(0x100);
+    let mut no_pe = stub();
+    no_pe[0x40] = b'X';
+    for bad in [b"just some text".to_vec(), b"MZ but nothing else".to_vec(), no_pe, truncated] {
+        assert!(matches!(check_stub(&bad), Err(ArchiveError::InvalidStub(_))));
+    }
+
+    let mut builder = ArchiveBuilder::new("out");
+    builder.add_bytes("a.txt", b"a").unwrap();
+    assert!(builder.build(b"not an exe").is_err());
+}
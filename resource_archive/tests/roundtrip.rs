@@ -0,0 +1,495 @@
+// Round trips through the archive format: pack resources the way compile_exe does,
+// append them to a stand-in stub, then locate, parse and extract them the way the
+// stub does and compare the extracted files byte for byte.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use resource_archive::*;
+
+const STUB: &[u8] = b"MZ stand-in stub, not a real PE image";
+
+#[derive(Clone, Copy)]
+enum Compression {
+    None,
+    Stream,
+    Blocks(usize),
+}
+
+// [stub][header JSON][resource data][footer], packed with a ResourcePacker
+fn pack(files: &[(&str, &[u8])], main_file: Option<&str>, compression: Compression) -> Vec<u8> {
+    let mut packer = ResourcePacker::new(0);
+    for (filename, bytes) in files {
+        packer.add(filename.to_string(), bytes, None, None).unwrap();
+    }
+    pack_entries(packer.entries, packer.data, main_file, compression)
+}
+
+fn pack_entries(resources: Vec<ResourceEntry>, data: Vec<u8>, main_file: Option<&str>, compression: Compression) -> Vec<u8> {
+    let mut header = ArchiveHeader {
+        extraction_path: "out".to_string(),
+        main_file: main_file.unwrap_or_default().to_string(),
+        resources,
+        is_compressed: !matches!(compression, Compression::None),
+        ..Default::default()
+    };
+    let stored = match compression {
+        Compression::None => data,
+        Compression::Stream => gzip_compress(&data).unwrap(),
+        Compression::Blocks(block_size) => {
+            let (stored, blocks) = compress_blocks(&data, block_size).unwrap();
+            header.compressed_blocks = blocks;
+            header.compression_block_size = block_size as u32;
+            stored
+        }
+    };
+    let mut crc = flate2::Crc::new();
+    crc.update(&stored);
+    header.data_crc32 = Some(crc.sum());
+
+    let mut exe = STUB.to_vec();
+    exe.extend_from_slice(&pack_archive(&header, &stored).unwrap());
+    exe
+}
+
+// Locate and parse the archive at the end of `exe` and extract every resource
+// into a fresh folder following the stub's extraction plan. Returns the header
+// and the folder.
+fn extract(exe: &[u8], name: &str) -> Result<(ArchiveHeader, PathBuf), String> {
+    let (header, _, data_range) = unpack_header(exe)?;
+    let resource_bytes = &exe[data_range];
+    header.check_data_crc(resource_bytes)?;
+    let mut data = header.open_resource_data(resource_bytes, 1024 * 1024)?;
+
+    let dir = std::env::temp_dir().join(format!("rsc_roundtrip_{}_{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    for planned in header.extraction_plan(&dir)? {
+        fs::create_dir_all(planned.path.parent().unwrap()).unwrap();
+        fs::write(&planned.path, planned.read(&mut data)?).unwrap();
+    }
+    Ok((header, dir))
+}
+
+fn assert_round_trip(files: &[(&str, &[u8])], main_file: Option<&str>, compression: Compression, name: &str) {
+    let exe = pack(files, main_file, compression);
+    let (header, dir) = extract(&exe, name).unwrap();
+    assert_eq!(header.main_file, main_file.unwrap_or_default());
+    for (filename, bytes) in files {
+        assert_eq!(fs::read(dir.join(filename)).unwrap(), *bytes, "{} differs after extraction", filename);
+    }
+    fs::remove_dir_all(dir).unwrap();
+
+    // unpack_archive sees the same bytes without touching the disk
+    let (_, unpacked) = unpack_archive(&exe, 1024 * 1024).unwrap();
+    let expected: Vec<Vec<u8>> = files.iter().map(|(_, bytes)| bytes.to_vec()).collect();
+    assert_eq!(unpacked, expected);
+}
+
+// Deterministic, poorly compressible bytes
+fn noise(len: usize, seed: u32) -> Vec<u8> {
+    let mut state = seed.wrapping_mul(2654435761).max(1);
+    (0..len).map(|_| {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state as u8
+    }).collect()
+}
+
+#[test]
+fn uncompressed_with_main_file() {
+    let app = noise(5000, 1);
+    assert_round_trip(
+        &[("app.exe", &app), ("config/settings.ini", b"[main]\nvalue=1\n"), ("empty.txt", b"")],
+        Some("app.exe"),
+        Compression::None,
+        "uncompressed_main",
+    );
+}
+
+#[test]
+fn uncompressed_without_main_file() {
+    assert_round_trip(&[("readme.txt", b"hello"), ("data.bin", &noise(300, 2))], None, Compression::None, "uncompressed");
+}
+
+#[test]
+fn extraction_plan_orders_and_sandboxes() {
+    let exe = pack(&[("app.exe", b"main"), ("lib.dll", b"dep"), ("copy.dll", b"dep")], Some("app.exe"), Compression::None);
+    let (mut header, _, _) = unpack_header(&exe).unwrap();
+    let root = Path::new("out");
+    let order = |header: &ArchiveHeader| -> Vec<String> {
+        header.extraction_plan(root).unwrap().iter().map(|planned| planned.resource.filename.clone()).collect()
+    };
+    assert_eq!(order(&header), ["app.exe", "lib.dll", "copy.dll"]);
+    header.main_file_last = true;
+    assert_eq!(order(&header), ["lib.dll", "copy.dll", "app.exe"]);
+
+    let plan = header.extraction_plan(root).unwrap();
+    assert_eq!(plan[2].path, root.join("app.exe"));
+    // The deduplicated copy reads the bytes of the first one
+    assert_eq!(plan[0].start, plan[1].start);
+
+    header.resources[1].filename = r"..\evil.dll".to_string();
+    assert!(matches!(header.extraction_plan(root), Err(ArchiveError::UnsafePath(_))));
+}
+
+#[test]
+fn non_ascii_filenames() {
+    let files: &[(&str, &[u8])] = &[
+        ("日本語.txt", "こんにちは".as_bytes()),
+        ("🚀 launch.exe", &noise(700, 5)),
+        ("données/résumé 📄.md", b"# caf\xc3\xa9"),
+    ];
+    assert_round_trip(files, Some("🚀 launch.exe"), Compression::None, "non_ascii");
+    assert_round_trip(files, Some("🚀 launch.exe"), Compression::Stream, "non_ascii_compressed");
+
+    // The names come back as packed, not mangled into another encoding
+    let (header, _) = unpack_archive(&pack(files, None, Compression::None), u64::MAX).unwrap();
+    let names: Vec<&str> = header.resources.iter().map(|resource| resource.filename.as_str()).collect();
+    assert_eq!(names, ["日本語.txt", "🚀 launch.exe", "données/résumé 📄.md"]);
+}
+
+#[test]
+fn compressed_with_main_file() {
+    let text = "repeated text compresses well ".repeat(200);
+    assert_round_trip(
+        &[("app.exe", &noise(4096, 3)), ("notes.txt", text.as_bytes())],
+        Some("app.exe"),
+        Compression::Stream,
+        "compressed_main",
+    );
+}
+
+#[test]
+fn compressed_without_main_file() {
+    assert_round_trip(&[("a.txt", b"aaaa"), ("b/c.txt", b"bbbb")], None, Compression::Stream, "compressed");
+}
+
+#[test]
+fn seekable_compression_reads_across_blocks() {
+    // Small blocks so resources start and end mid-block and span several blocks
+    let big = noise(1000, 4);
+    assert_round_trip(
+        &[("small.txt", b"tiny"), ("big.bin", &big), ("tail.txt", b"last one")],
+        Some("big.bin"),
+        Compression::Blocks(64),
+        "seekable",
+    );
+}
+
+#[test]
+fn duplicates_are_stored_once() {
+    let shared = noise(100, 5);
+    let mut packer = ResourcePacker::new(0);
+    packer.add("first.bin".to_string(), &shared, None, None).unwrap();
+    packer.add("other.bin".to_string(), b"different", None, None).unwrap();
+    packer.add("copy.bin".to_string(), &shared, None, None).unwrap();
+    assert_eq!(packer.data.len(), 100 + 9);
+    assert_eq!(packer.deduplicated_bytes, 100);
+    assert_eq!(packer.entries[2].offset, Some(0));
+    assert_round_trip(
+        &[("first.bin", &shared), ("other.bin", b"different"), ("copy.bin", &shared)],
+        None,
+        Compression::None,
+        "dedup",
+    );
+}
+
+#[test]
+fn aligned_resources_round_trip() {
+    let mut packer = ResourcePacker::new(16);
+    packer.add("a.bin".to_string(), b"abc", None, None).unwrap();
+    packer.add("b.bin".to_string(), b"defgh", None, None).unwrap();
+    assert_eq!(packer.entries[1].offset, Some(16));
+    let resources = packer.entries.clone();
+
+    let header = ArchiveHeader { resources, ..Default::default() };
+    let pack = pack_archive_aligned(&header, &packer.data, STUB.len(), 16).unwrap();
+    let mut exe = STUB.to_vec();
+    exe.extend_from_slice(&pack);
+    let (header, _, data_range) = unpack_header(&exe).unwrap();
+    assert_eq!(data_range.start % 16, 0);
+    header.check_layout(data_range.len()).unwrap();
+    assert_eq!(unpack_archive(&exe, 1024).unwrap().1, vec![b"abc".to_vec(), b"defgh".to_vec()]);
+}
+
+#[test]
+fn entries_compressed_on_their_own() {
+    let text = "compressible text ".repeat(100);
+    let random = noise(500, 8);
+    let mut packer = ResourcePacker::new(0);
+    packer.add_compressed("auto_text.txt".to_string(), text.as_bytes(), None, None, EntryCompression::Auto).unwrap();
+    packer.add_compressed("auto_noise.bin".to_string(), &random, None, None, EntryCompression::Auto).unwrap();
+    packer.add_compressed("stored.txt".to_string(), text.as_bytes(), None, None, EntryCompression::Store).unwrap();
+    packer.add_compressed("forced.bin".to_string(), &random, None, None, EntryCompression::Gzip).unwrap();
+    assert!(packer.entries[0].stored_size.is_some_and(|stored| (stored as usize) < text.len()));
+    assert_eq!(packer.entries[1].stored_size, None);
+    assert_eq!(packer.entries[2].stored_size, None);
+    assert!(packer.entries[3].stored_size.is_some_and(|stored| stored as usize > random.len()));
+    assert_eq!(packer.entries[3].size as usize, random.len());
+
+    // Also under whole-archive compression, as when adding to such a packed EXE
+    let expected = vec![text.as_bytes().to_vec(), random.clone(), text.as_bytes().to_vec(), random.clone()];
+    for (compression, name) in [(Compression::None, "entry_stored"), (Compression::Stream, "entry_gzip")] {
+        let exe = pack_entries(packer.entries.clone(), packer.data.clone(), None, compression);
+        let (_, dir) = extract(&exe, name).unwrap();
+        assert_eq!(fs::read(dir.join("auto_text.txt")).unwrap(), text.as_bytes());
+        assert_eq!(fs::read(dir.join("forced.bin")).unwrap(), random);
+        fs::remove_dir_all(dir).unwrap();
+        assert_eq!(unpack_archive(&exe, 1024 * 1024).unwrap().1, expected);
+    }
+}
+
+#[test]
+fn entries_without_offsets_are_back_to_back() {
+    // Older archives only recorded offsets for deduplicated entries
+    let first = noise(100, 6);
+    let second = noise(50, 7);
+    let mut data = first.clone();
+    data.extend_from_slice(&second);
+    let entry = |filename: &str, size: u32, offset: Option<u64>| ResourceEntry { filename: filename.to_string(), size, offset, ..Default::default() };
+    let resources = vec![entry("first.bin", 100, None), entry("second.bin", 50, None), entry("copy.bin", 100, Some(0))];
+    let exe = pack_entries(resources, data, None, Compression::None);
+    let (_, dir) = extract(&exe, "back_to_back").unwrap();
+    assert_eq!(fs::read(dir.join("first.bin")).unwrap(), first);
+    assert_eq!(fs::read(dir.join("second.bin")).unwrap(), second);
+    assert_eq!(fs::read(dir.join("copy.bin")).unwrap(), first);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn headers_from_older_versions_parse() {
+    // Only the fields the first archive version wrote
+    let json = br#"{"extraction_path":"out","main_file":"a.exe","resources":[{"filename":"a.exe","size":3}],"execution_style":"normal","run_as_admin":false,"is_compressed":false}"#;
+    let header = parse_header(json).unwrap();
+    assert_eq!(header.resources[0].offset, None);
+    assert!(header.data_crc32.is_none());
+    assert!(parse_header(b"{}").is_err());
+}
+
+#[test]
+fn unsafe_paths_are_refused() {
+    let windows_names = [r"..\..\Windows\System32\evil.dll", r"C:\evil.dll", "C:evil.dll", r"\\server\share\x", r"a\..\..\b", "notes.txt:hidden", r".. \x", ".../x"];
+    for name in ["../escape.txt", "/etc/passwd", "a/../../b", ""].into_iter().chain(windows_names) {
+        assert!(!is_safe_resource_path(name), "{:?} should be unsafe", name);
+        assert!(ResourcePacker::new(0).add(name.to_string(), b"x", None, None).is_err(), "{:?} was packed", name);
+        // A crafted archive gets past the packer, the extractor still refuses it
+        let entry = ResourceEntry { filename: name.to_string(), size: 1, ..Default::default() };
+        let exe = pack_entries(vec![entry], b"x".to_vec(), None, Compression::None);
+        assert!(extract(&exe, "unsafe").is_err(), "{:?} was extracted", name);
+    }
+    assert!(is_safe_resource_path("config/./app.ini"));
+    assert!(is_safe_resource_path(r"config\app.ini"));
+    assert!(is_safe_resource_path(".hidden/..data"));
+    assert_eq!(sandboxed_path(Path::new("out"), "a/b.txt").unwrap(), Path::new("out").join("a/b.txt"));
+    assert!(matches!(sandboxed_path(Path::new("out"), r"..\x"), Err(ArchiveError::UnsafePath(_))));
+}
+
+#[test]
+fn missing_or_truncated_footer() {
+    assert!(archive_range(STUB).is_err());
+    assert!(archive_range(&[]).is_err());
+    let exe = pack(&[("a.txt", b"abc")], None, Compression::None);
+    assert!(archive_range(&exe[..exe.len() - 1]).is_err());
+    assert!(parse_footer(&exe[exe.len() - FOOTER_SIZE + 1..]).is_err());
+}
+
+#[test]
+fn corrupt_footer_fields() {
+    let exe = pack(&[("a.txt", b"abc")], None, Compression::None);
+    let footer_start = exe.len() - FOOTER_SIZE;
+
+    let mut bad_marker = exe.clone();
+    bad_marker[footer_start + 8] ^= 0xff;
+    assert!(archive_range(&bad_marker).is_err());
+
+    // Header longer than the archive, empty header, archive longer than the file
+    for (field, value) in [(0, u32::MAX), (0, 0), (4, exe.len() as u32)] {
+        let mut corrupt = exe.clone();
+        corrupt[footer_start + field..footer_start + field + 4].copy_from_slice(&value.to_le_bytes());
+        assert!(archive_range(&corrupt).is_err(), "footer field {} = {} was accepted", field, value);
+    }
+
+    let mut oversized = exe.clone();
+    oversized[footer_start + 4..footer_start + 8].copy_from_slice(&(MAX_ARCHIVE_SIZE as u32 + 1).to_le_bytes());
+    assert!(parse_footer(&oversized[footer_start..]).is_err());
+}
+
+#[test]
+fn corrupt_data_fails_the_checksum() {
+    let mut exe = pack(&[("a.txt", b"abcdef")], None, Compression::None);
+    let data_start = unpack_header(&exe).unwrap().2.start;
+    exe[data_start] ^= 0xff;
+    assert!(unpack_archive(&exe, 1024).is_err());
+}
+
+#[test]
+fn resource_past_the_data_is_an_error() {
+    let resources = vec![ResourceEntry { filename: "a.txt".to_string(), size: 10, offset: Some(0), ..Default::default() }];
+    let exe = pack_entries(resources, b"short".to_vec(), None, Compression::None);
+    assert!(extract(&exe, "past_end").is_err());
+    assert!(unpack_archive(&exe, 1024).is_err());
+}
+
+#[test]
+fn decompression_stops_at_the_limit() {
+    let compressed = gzip_compress(&vec![0u8; 10_000]).unwrap();
+    assert!(decompress_resources(&compressed, 9_999).is_err());
+    assert_eq!(decompress_resources(&compressed, 10_000).unwrap().len(), 10_000);
+}
+
+#[test]
+fn compression_is_reproducible() {
+    let data = noise(2000, 7);
+    assert_eq!(gzip_compress(&data).unwrap(), gzip_compress(&data).unwrap());
+}
+
+// Pack files from disk the way compile_exe does: read with their modified times,
+// compress, keep whichever form is smaller and append the archive to the stub
+fn compile(dir: &Path, names: &[&str], seekable: bool) -> Vec<u8> {
+    let mut packer = ResourcePacker::new(0);
+    for name in names {
+        let mut file = fs::File::open(dir.join(name)).unwrap();
+        let metadata = file.metadata().unwrap();
+        let modified = metadata.modified().ok()
+            .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|since_epoch| since_epoch.as_millis() as u64);
+        packer.add_from_reader(name.to_string(), &mut file, metadata.len() as usize, modified, None).unwrap();
+    }
+    let mut header = ArchiveHeader {
+        extraction_path: "out".to_string(),
+        main_file: names[0].to_string(),
+        resources: packer.entries,
+        ..Default::default()
+    };
+    let compressed = if seekable {
+        compress_blocks(&packer.data, COMPRESSION_BLOCK_SIZE).unwrap()
+    } else {
+        (gzip_compress(&packer.data).unwrap(), Vec::new())
+    };
+    let stored = finish_resource_data(&mut header, packer.data, Some(compressed));
+    let mut exe = STUB.to_vec();
+    exe.extend_from_slice(&pack_archive_aligned(&header, &stored, STUB.len(), 1).unwrap());
+    exe
+}
+
+#[test]
+fn builds_are_reproducible() {
+    let dir = std::env::temp_dir().join(format!("rsc_reproducible_{}", std::process::id()));
+    fs::create_dir_all(dir.join("data")).unwrap();
+    fs::write(dir.join("app.exe"), noise(3000, 11)).unwrap();
+    fs::write(dir.join("data/readme.txt"), "the same text again and again ".repeat(200)).unwrap();
+    let names = ["app.exe", "data/readme.txt"];
+
+    for seekable in [false, true] {
+        let first = compile(&dir, &names, seekable);
+        let second = compile(&dir, &names, seekable);
+        assert!(first == second, "two builds differ (seekable: {})", seekable);
+        assert!(unpack_header(&first).unwrap().0.is_compressed);
+    }
+
+    let build = || {
+        let mut builder = ArchiveBuilder::new("out");
+        builder.add_file(dir.join("app.exe")).unwrap()
+            .add_file_as(dir.join("data/readme.txt"), "data/readme.txt").unwrap();
+        builder.set_main("app.exe").set_compression(resource_archive::Compression::Gzip);
+        builder.build_pack().unwrap()
+    };
+    assert!(build() == build(), "two ArchiveBuilder builds differ");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn layout_covers_the_file_in_order() {
+    let exe = pack(&[("a.txt", b"first"), ("b.txt", b"second"), ("copy.txt", b"first")], None, Compression::None);
+    let regions = archive_layout(&exe).unwrap();
+    let kinds: Vec<RegionKind> = regions.iter().map(|region| region.kind).collect();
+    assert_eq!(kinds, [RegionKind::Stub, RegionKind::Header, RegionKind::Resource, RegionKind::Resource, RegionKind::Footer]);
+    assert_eq!(regions[0].range, 0..STUB.len());
+    assert_eq!(regions[2].label, "a.txt, copy.txt");
+    assert_eq!(&exe[regions[3].range.clone()], b"second");
+    assert_eq!(regions[4].range.len(), FOOTER_SIZE);
+    for pair in regions.windows(2) {
+        assert_eq!(pair[0].range.end, pair[1].range.start);
+    }
+    assert_eq!(regions.last().unwrap().range.end, exe.len());
+
+    let blocks = archive_layout(&pack(&[("a.bin", &noise(300, 3))], None, Compression::Blocks(128))).unwrap();
+    assert_eq!(blocks.iter().filter(|region| region.kind == RegionKind::CompressedData).count(), 3);
+}
+
+#[test]
+fn signed_exe_still_finds_its_archive() {
+    // A PE32+ header whose security directory points at a certificate table
+    // appended after the footer, padded to 8 bytes first the way signtool does
+    let mut stub = vec![0u8; 0x200];
+    stub[..2].copy_from_slice(b"MZ");
+    stub[0x3c..0x40].copy_from_slice(&0x40u32.to_le_bytes());
+    stub[0x40..0x44].copy_from_slice(b"PE\0\0");
+    stub[0x54..0x56].copy_from_slice(&240u16.to_le_bytes());
+    stub[0x58..0x5a].copy_from_slice(&0x20bu16.to_le_bytes());
+    stub[0x58 + 108..0x58 + 112].copy_from_slice(&16u32.to_le_bytes());
+    let unsigned = pack(&[("a.txt", b"signed payload"), ("b.bin", &noise(77, 9))], None, Compression::None);
+    let mut exe = stub.clone();
+    exe.extend_from_slice(&unsigned[STUB.len()..]);
+    assert_eq!(pe_signature_range(&exe), None);
+    let (_, unsigned_range) = archive_range(&exe).unwrap();
+
+    exe.resize(exe.len().next_multiple_of(8), 0);
+    let table_start = exe.len();
+    exe.extend_from_slice(&[0x5a; 40]);
+    let security = 0x58 + 112 + 4 * 8;
+    exe[security..security + 4].copy_from_slice(&(table_start as u32).to_le_bytes());
+    exe[security + 4..security + 8].copy_from_slice(&40u32.to_le_bytes());
+    assert_eq!(pe_signature_range(&exe), Some(table_start..exe.len()));
+
+    assert_eq!(archive_range(&exe).unwrap().1, unsigned_range);
+    let (header, files) = unpack_archive(&exe, u64::MAX).unwrap();
+    assert_eq!(header.resources.len(), 2);
+    assert_eq!(files[0], b"signed payload");
+    let regions = archive_layout(&exe).unwrap();
+    assert_eq!(regions.last().unwrap().kind, RegionKind::Signature);
+    assert_eq!(regions.last().unwrap().range.end, exe.len());
+
+    // Stripping drops the table and its padding and clears the directory
+    let mut stripped = exe.clone();
+    assert!(strip_signature(&mut stripped));
+    assert_eq!(stripped.len(), unsigned_range.end + FOOTER_SIZE);
+    assert_eq!(pe_signature_range(&stripped), None);
+    assert_eq!(archive_range(&stripped).unwrap().1, unsigned_range);
+    assert!(!strip_signature(&mut stripped));
+
+    // A directory entry left pointing past the end (the stub part of a signed
+    // packed EXE) is cleared as well
+    let mut stub_part = exe[..unsigned_range.start].to_vec();
+    assert_eq!(pe_signature_range(&stub_part), None);
+    assert!(strip_signature(&mut stub_part));
+    assert!(!strip_signature(&mut stub_part));
+
+    // A signature that isn't at the end of the file doesn't hide a broken footer
+    exe.push(0);
+    assert!(archive_range(&exe).is_err());
+}
+
+// Windows-style absolute path on the platform running the tests
+fn windows_path(path: &str) -> PathBuf {
+    if cfg!(windows) {
+        path.into()
+    } else {
+        path.replacen("C:\\", "/", 1).replace('\\', "/").into()
+    }
+}
+
+#[test]
+fn refuses_to_delete_system_and_profile_folders() {
+    let trees = [windows_path(r"C:\Windows"), windows_path(r"C:\Program Files")];
+    let folders = [windows_path(r"C:\Users\me"), windows_path(r"C:\Users\me\AppData\Local")];
+    let dangerous = |path: &str| is_dangerous_to_delete(&windows_path(path), &trees, &folders);
+
+    for path in [r"C:\", r"C:\Windows", r"C:\Windows\System32", r"C:\Program Files\SomeVendor", r"C:\Users", r"C:\Users\me", r"C:\Users\me\AppData"] {
+        assert!(dangerous(path), "{} may be deleted", path);
+    }
+    for path in [r"C:\Users\me\AppData\Local\MyApp", r"C:\Users\me\Desktop\out", r"C:\Tools\MyApp"] {
+        assert!(!dangerous(path), "{} is refused", path);
+    }
+}
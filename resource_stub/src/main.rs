@@ -1,47 +1,229 @@
+// Without the `console` feature the stub is a GUI-subsystem app, so no console
+// window flashes up when the packed EXE runs. Build with `--features console`
+// to get a debugging stub that shows its output.
+#![cfg_attr(not(feature = "console"), windows_subsystem = "windows")]
+
 use std::env;
 use std::fs;
-use std::io::{Read, Seek, SeekFrom, Cursor};
-use std::path::Path;
-use serde::{Deserialize, Serialize};
-use flate2::read::GzDecoder;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use resource_archive::{
+    archive_range, parse_footer, parse_header, read_u32_le, ArchiveHeader, Dependency,
+    ResourceData, ResourceEntry, ServiceOptions, ShortcutOptions, StubMessages, FOOTER_SIZE, MAX_ARCHIVE_SIZE, SPLIT_MARKER,
+    SPLIT_TRAILER_SIZE,
+};
 
 // Windows API items
-use windows::Win32::Foundation::{HANDLE, CloseHandle, HWND};
-use windows::Win32::UI::Shell::ShellExecuteW;
+use windows::Win32::Foundation::{HANDLE, CloseHandle, HWND, LPARAM, WPARAM, GetLastError, ERROR_ALREADY_EXISTS,
+    ERROR_SERVICE_ALREADY_RUNNING, ERROR_SERVICE_DOES_NOT_EXIST, ERROR_SERVICE_EXISTS};
+use windows::Win32::UI::Shell::{
+    ShellExecuteExW, SHELLEXECUTEINFOW, SEE_MASK_NOCLOSEPROCESS, IShellLinkW, ShellLink, SHGetKnownFolderPath, KF_FLAG_DEFAULT,
+    FOLDERID_Desktop, FOLDERID_Programs,
+};
+use windows::Win32::System::Com::{
+    CoInitializeEx, CoUninitialize, CoCreateInstance, CoTaskMemFree, IPersistFile,
+    CLSCTX_INPROC_SERVER, COINIT_APARTMENTTHREADED,
+};
 use windows::Win32::UI::WindowsAndMessaging::{
     SW_HIDE, SW_SHOWMINIMIZED, SW_SHOWNORMAL, SW_SHOWMAXIMIZED,
-    MessageBoxW, MB_OK,
+    MessageBoxW, MB_OK, MB_YESNO, MB_ICONQUESTION, MB_ICONINFORMATION, MB_ICONERROR, MB_ICONWARNING, MB_DEFBUTTON2, IDYES,
+    MESSAGEBOX_STYLE, MESSAGEBOX_RESULT,
+    CreateWindowExW, DestroyWindow, SetWindowTextW, PeekMessageW, TranslateMessage, DispatchMessageW,
+    EnumWindows, GetWindowThreadProcessId, IsWindowVisible, IsIconic, ShowWindow, SetForegroundWindow, SW_RESTORE,
+    IsWindow, SendMessageW, GetWindowTextLengthW, WS_OVERLAPPEDWINDOW, WS_VSCROLL, CW_USEDEFAULT,
+    ES_MULTILINE, ES_READONLY, ES_AUTOVSCROLL, WM_SETFONT, WINDOW_EX_STYLE,
+    GetSystemMetrics, SM_CXSCREEN, SM_CYSCREEN, MSG, PM_REMOVE,
+    WINDOW_STYLE, WS_POPUP, WS_VISIBLE, WS_BORDER, WS_EX_TOPMOST, WS_EX_TOOLWINDOW,
+};
+use windows::Win32::UI::Controls::{EM_SETSEL, EM_REPLACESEL};
+use windows::Win32::Graphics::Gdi::{GetStockObject, ANSI_FIXED_FONT};
+use windows::Win32::System::Console::{GetConsoleWindow, AttachConsole, ATTACH_PARENT_PROCESS};
+use windows::Win32::System::SystemInformation::GetLocalTime;
+use windows::Win32::Storage::FileSystem::{SetFileAttributesW, FILE_FLAGS_AND_ATTRIBUTES, FILE_ATTRIBUTE_NORMAL};
+use windows::Win32::System::LibraryLoader::{FindResourceW, LoadResource, LockResource, SizeofResource};
+use windows::Win32::System::SystemServices::{SS_CENTER, SS_CENTERIMAGE};
+use windows::Win32::System::Services::{
+    ChangeServiceConfig2W, ChangeServiceConfigW, CloseServiceHandle, ControlService, CreateServiceW, DeleteService,
+    OpenSCManagerW, OpenServiceW, QueryServiceStatus, StartServiceW, SC_HANDLE, SC_MANAGER_CONNECT, SC_MANAGER_CREATE_SERVICE, SERVICE_ALL_ACCESS,
+    SERVICE_AUTO_START, SERVICE_CONFIG_DESCRIPTION, SERVICE_CONTROL_STOP, SERVICE_DEMAND_START, SERVICE_DESCRIPTIONW,
+    SERVICE_DISABLED, SERVICE_ERROR_NORMAL, SERVICE_STATUS, SERVICE_STOPPED, SERVICE_WIN32_OWN_PROCESS,
+};
+use windows::Win32::System::Registry::{
+    RegCloseKey, RegGetValueW, RegOpenKeyExW, HKEY, HKEY_CLASSES_ROOT, HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE, HKEY_USERS,
+    KEY_READ, RRF_RT_REG_SZ,
 };
 use windows::core::PCWSTR;
 use windows::Win32::Security::{TOKEN_ELEVATION, TOKEN_QUERY, TokenElevation};
-use windows::Win32::System::Threading::{OpenProcessToken, GetCurrentProcess};
+use windows::Win32::System::Threading::{
+    OpenProcessToken, GetCurrentProcess, CreateMutexW, OpenProcess, QueryFullProcessImageNameW,
+    WaitForSingleObject, GetExitCodeProcess, INFINITE, PROCESS_QUERY_LIMITED_INFORMATION, PROCESS_NAME_WIN32,
+};
 use windows::Win32::Security::GetTokenInformation;
 use std::ptr::null_mut;
 use std::mem::size_of;
-use windows::core::w;
+use windows::core::{w, BOOL, GUID, HSTRING, Interface, PWSTR};
+
+// Header features this stub understands. The packer finds the tag in stub.exe and
+// refuses options an older stub would silently ignore. Add to it with every new
+// header option; the list ends at the ';'.
+#[used]
+static CAPABILITIES: &[u8] = b"RSC_CAPABILITIES:compress,seekable_compression,main_file_last,capture_output,launch_verb,launch_options,post_extract,dependencies,registry_path,shortcuts,service,single_instance,wait_for_exit,io_retries,overwrite_policy,update_mode,eula,entry_compression,uninstall,split_volumes,pe_resource,sidecar,external_main,subfolders,progress,log;";
+
+// Command line flag that checks the archive and prints PASS/FAIL instead of extracting
+const VERIFY_ARG: &str = "--verify";
+
+// Command line flag that runs the uninstall routine (when enabled in the header)
+const UNINSTALL_ARG: &str = "--uninstall";
+
+// Written to the extraction directory once shortcuts have been created; lists
+// the .lnk files (one per line) so they are only created on first run and
+// can be removed again by --uninstall
+const SHORTCUT_MARKER_FILE: &str = ".rc_shortcuts";
+
+// Left in the extraction directory once we've extracted there, so later runs
+// (updates) overwrite our own files without asking again
+const EXTRACTED_MARKER_FILE: &str = ".rc_extracted";
+
+// Written next to the extracted files once the user accepts the header's EULA,
+// so it is only shown on first run
+const EULA_MARKER_FILE: &str = ".rc_eula_accepted";
+
+// How long --uninstall waits for a stopping service to release its files
+const SERVICE_STOP_TIMEOUT_MS: u64 = 30_000;
+const SERVICE_STOP_POLL_MS: u64 = 250;
+
+// Passed to the elevated copy of the stub so it doesn't try to relaunch again
+const ELEVATED_RELAUNCH_ARG: &str = "--rc-elevated";
+
+// Name of the RCDATA resource holding the archive (plus footer) in PE resource mode
+const ARCHIVE_RESOURCE_NAME: PCWSTR = w!("RSCARCHIVE");
+// RT_RCDATA, the standard resource type for raw application data
+const RT_RCDATA: PCWSTR = PCWSTR(10 as _);
+
+// Only restore attributes that make sense on a freshly written file:
+// READONLY | HIDDEN | SYSTEM | ARCHIVE
+const RESTORABLE_ATTRIBUTES: u32 = 0x1 | 0x2 | 0x4 | 0x20;
+
+#[derive(Clone, Copy)]
+enum StubFailure {
+    Extraction,
+    CorruptArchive,
+    Launch,
+}
+
+/// Log a fatal error and tell the user about it in a dialog, worded by the
+/// header's messages (empty ones fall back to the English defaults).
+fn report_failure(log: &mut StubLog, messages: &StubMessages, failure: StubFailure, detail: &str) {
+    log.error(detail);
+    let (custom, default) = match failure {
+        StubFailure::Extraction => (&messages.extraction_failed, "The application files could not be extracted."),
+        StubFailure::CorruptArchive => (&messages.corrupt_archive, "This file is damaged or incomplete. Please download it again."),
+        StubFailure::Launch => (&messages.launch_failed, "The application could not be started."),
+    };
+    let title = if messages.title.is_empty() { "Error" } else { &messages.title };
+    let text = if custom.is_empty() { default } else { custom };
+    message_box(title, &format!("{}\n\n{}", text, detail), MB_OK | MB_ICONERROR);
+}
+
+/// Whether a dependency the header lists is installed on this machine
+fn is_dependency_present(dependency: &Dependency) -> bool {
+    match dependency.kind.as_str() {
+        "registry" => registry_key_exists(&dependency.path),
+        _ => Path::new(&expand_env_vars(&dependency.path)).exists(),
+    }
+}
+
+/// Verbosity of the stub log, ordered from quietest to noisiest.
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+enum LogLevel {
+    Off,
+    Error,
+    Info,
+    Debug,
+}
+
+impl LogLevel {
+    fn from_header(level: &str) -> Self {
+        match level.to_lowercase().as_str() {
+            "error" => LogLevel::Error,
+            "info"  => LogLevel::Info,
+            "debug" => LogLevel::Debug,
+            _       => LogLevel::Off,
+        }
+    }
 
-// Archive footer format (total 24 bytes):
-//   - 4 bytes: header length (u32, little-endian)
-//   - 4 bytes: total archive data length (u32, little-endian)
-//   - 16 bytes: fixed marker (must equal FOOTER_MARKER)
-const FOOTER_SIZE: usize = 4 + 4 + 16;
-const FOOTER_MARKER: &[u8; 16] = b"RSCARCHIVE_V1___";
+    fn label(self) -> &'static str {
+        match self {
+            LogLevel::Off   => "OFF",
+            LogLevel::Error => "ERROR",
+            LogLevel::Info  => "INFO",
+            LogLevel::Debug => "DEBUG",
+        }
+    }
+}
 
-// Structures matching the header created by the packer
-#[derive(Serialize, Deserialize)]
-struct ResourceEntry {
-    filename: String,
-    size: u32,
+/// Log file in the temp dir that users can send in when extraction fails on their
+/// machine. Lines logged before the header is parsed are buffered until we know
+/// the configured level.
+struct StubLog {
+    level: Option<LogLevel>,
+    pending: Vec<(LogLevel, String)>,
+    file: Option<fs::File>,
 }
 
-#[derive(Serialize, Deserialize)]
-struct ArchiveHeader {
-    extraction_path: String,
-    main_file: String,
-    resources: Vec<ResourceEntry>,
-    execution_style: String, // "no-window", "minimized", "normal", or "maximized"
-    run_as_admin: bool,
-    is_compressed: bool,  // Add this field to match the GUI program
+impl StubLog {
+    fn new() -> Self {
+        Self { level: None, pending: Vec::new(), file: None }
+    }
+
+    /// Errors also go to stderr so console users see them without a log file.
+    fn error(&mut self, message: &str) {
+        eprintln!("{}", message);
+        self.write(LogLevel::Error, message);
+    }
+
+    fn info(&mut self, message: &str) {
+        self.write(LogLevel::Info, message);
+    }
+
+    fn debug(&mut self, message: &str) {
+        self.write(LogLevel::Debug, message);
+    }
+
+    /// Apply the level from the header, open the log file and flush buffered lines.
+    fn set_level(&mut self, level: LogLevel, path: &Path) {
+        self.level = Some(level);
+        if level != LogLevel::Off {
+            self.file = fs::OpenOptions::new().create(true).append(true).open(path).ok();
+        }
+        for (line_level, line) in std::mem::take(&mut self.pending) {
+            self.write_line(line_level, &line);
+        }
+    }
+
+    fn write(&mut self, level: LogLevel, message: &str) {
+        let time = unsafe { GetLocalTime() };
+        let line = format!(
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:03} [{}] {}",
+            time.wYear, time.wMonth, time.wDay,
+            time.wHour, time.wMinute, time.wSecond, time.wMilliseconds,
+            level.label(), message
+        );
+        if self.level.is_none() {
+            self.pending.push((level, line));
+        } else {
+            self.write_line(level, &line);
+        }
+    }
+
+    fn write_line(&mut self, level: LogLevel, line: &str) {
+        if level > self.level.unwrap_or(LogLevel::Off) {
+            return;
+        }
+        if let Some(file) = &mut self.file {
+            let _ = writeln!(file, "{}", line);
+        }
+    }
 }
 
 fn is_elevated() -> Result<bool, windows::core::Error> {
@@ -78,11 +260,174 @@ fn is_elevated() -> Result<bool, windows::core::Error> {
     Ok(elevation.TokenIsElevated != 0)
 }
 
-fn show_message_box(message: &str) {
+/// Set the executable bits on an extracted file. `fs::write` creates files as
+/// 0644 on Unix; Windows has no such bit, so there this does nothing.
+fn make_executable(path: &Path) -> Result<(), String> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mut permissions = fs::metadata(path)
+            .map_err(|e| format!("Failed to read permissions of {:?}: {}", path, e))?
+            .permissions();
+        permissions.set_mode(permissions.mode() | 0o111);
+        fs::set_permissions(path, permissions)
+            .map_err(|e| format!("Failed to make {:?} executable: {}", path, e))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        Ok(())
+    }
+}
+
+/// Whether the header's update mode keeps the copy of `resource` already at `path`
+/// instead of replacing it. "newer" needs the bundled modified time to compare;
+/// without one the file is replaced as before.
+fn keep_existing_file(update_mode: &str, path: &Path, resource: &ResourceEntry) -> bool {
+    use std::time::UNIX_EPOCH;
+
+    let Ok(metadata) = fs::metadata(path) else {
+        return false;
+    };
+    match update_mode {
+        "skip" => true,
+        "newer" => {
+            let (Some(bundled), Ok(on_disk)) = (resource.modified, metadata.modified()) else {
+                return false;
+            };
+            let on_disk = on_disk.duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0);
+            bundled <= on_disk
+        }
+        _ => false,
+    }
+}
+
+/// Restore the original modified time and attributes recorded for a resource.
+/// Attributes go last since a read-only file can't have its time changed.
+fn restore_file_metadata(path: &Path, resource: &ResourceEntry) -> Result<(), String> {
+    use std::ffi::OsStr;
+    use std::iter;
+    use std::os::windows::ffi::OsStrExt;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    if let Some(modified) = resource.modified {
+        let file = fs::File::options().write(true).open(path)
+            .map_err(|e| format!("Failed to open {:?} to set its time: {}", path, e))?;
+        file.set_modified(UNIX_EPOCH + Duration::from_millis(modified))
+            .map_err(|e| format!("Failed to set modified time on {:?}: {}", path, e))?;
+    }
+
+    if let Some(attributes) = resource.attributes {
+        let attributes = attributes & RESTORABLE_ATTRIBUTES;
+        if attributes != 0 {
+            let wide_path: Vec<u16> = OsStr::new(path)
+                .encode_wide()
+                .chain(iter::once(0))
+                .collect();
+            unsafe { SetFileAttributesW(PCWSTR(wide_path.as_ptr()), FILE_FLAGS_AND_ATTRIBUTES(attributes)) }
+                .map_err(|e| format!("Failed to set attributes on {:?}: {}", path, e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Clear attributes on a previously extracted copy so it can be overwritten
+/// (e.g. it was restored read-only on the last run).
+fn clear_file_attributes(path: &Path) {
+    use std::ffi::OsStr;
+    use std::iter;
+    use std::os::windows::ffi::OsStrExt;
+
+    let wide_path: Vec<u16> = OsStr::new(path)
+        .encode_wide()
+        .chain(iter::once(0))
+        .collect();
+    unsafe { let _ = SetFileAttributesW(PCWSTR(wide_path.as_ptr()), FILE_ATTRIBUTE_NORMAL); }
+}
+
+/// Whether deleting `path` recursively could take out something important: a drive
+/// root, anything in a system folder (Windows, Program Files, ProgramData), a
+/// profile folder (or any folder containing one), or the stub's own directory.
+fn is_dangerous_to_delete(path: &Path, exe_path: &Path) -> bool {
+    let Ok(target) = fs::canonicalize(path) else {
+        // If it exists but can't be resolved we can't tell what it is, so play safe
+        return path.exists();
+    };
+    let resolve = |paths: Vec<PathBuf>| -> Vec<PathBuf> {
+        paths.iter().filter_map(|p| fs::canonicalize(p).ok()).collect()
+    };
+
+    let trees = ["SystemRoot", "windir", "ProgramFiles", "ProgramFiles(x86)", "ProgramW6432", "ProgramData"]
+        .into_iter()
+        .filter_map(env::var_os)
+        .map(PathBuf::from)
+        .collect();
+    let mut folders: Vec<PathBuf> = ["USERPROFILE", "APPDATA", "LOCALAPPDATA", "PUBLIC"]
+        .into_iter()
+        .filter_map(env::var_os)
+        .map(PathBuf::from)
+        .collect();
+    folders.push(env::temp_dir());
+    if let Some(exe_dir) = exe_path.parent() {
+        folders.push(exe_dir.to_path_buf());
+    }
+
+    resource_archive::is_dangerous_to_delete(&target, &resolve(trees), &resolve(folders))
+}
+
+/// Number of entries in `dir` when it exists, isn't empty and has no sign of an
+/// earlier extraction by us, i.e. when extracting there could overwrite the user's files.
+fn foreign_entry_count(dir: &Path) -> usize {
+    if dir.join(EXTRACTED_MARKER_FILE).exists() {
+        return 0;
+    }
+    fs::read_dir(dir).map(|entries| entries.count()).unwrap_or(0)
+}
+
+/// Apply the header's overwrite policy before touching an extraction directory
+/// that already holds files we didn't create. Returns false to stop.
+fn confirm_overwrite(header: &ArchiveHeader, log: &mut StubLog) -> bool {
+    let dir = Path::new(&header.extraction_path);
+    let existing = foreign_entry_count(dir);
+    if existing == 0 {
+        return true;
+    }
+    log.info(&format!("{:?} already holds {} entries we did not create (overwrite policy: {:?})", dir, existing, header.overwrite_policy));
+    let title = if header.messages.title.is_empty() { "Existing Files" } else { &header.messages.title };
+    let action = if header.clean_before_extract {
+        "Its contents will be deleted"
+    } else {
+        "Files with the same names as the application's files will be overwritten"
+    };
+    match header.overwrite_policy.as_str() {
+        "refuse" => {
+            message_box(title, &format!("{} already contains {} file(s) or folder(s) and was not created by this application, so nothing was extracted.\nChoose an empty folder or remove its contents and try again.", dir.display(), existing), MB_OK | MB_ICONERROR);
+            false
+        }
+        "ask" => {
+            let prompt = format!("{} already contains {} file(s) or folder(s) that were not created by this application.\n{}. Continue?", dir.display(), existing, action);
+            message_box(title, &prompt, MB_YESNO | MB_ICONWARNING) == IDYES
+        }
+        _ => true,
+    }
+}
+
+/// Tell the user admin rights are needed, in the packer's own wording when set.
+fn show_admin_message(header: &ArchiveHeader, default_message: &str) {
+    let title = if header.admin_title.is_empty() { "Admin Required" } else { &header.admin_title };
+    let message = if header.admin_message.is_empty() { default_message } else { &header.admin_message };
+    message_box(title, message, MB_OK);
+}
+
+fn message_box(title: &str, message: &str, style: MESSAGEBOX_STYLE) -> MESSAGEBOX_RESULT {
     use std::ffi::OsStr;
     use std::iter;
     use std::os::windows::ffi::OsStrExt;
 
+    let wide_title: Vec<u16> = OsStr::new(title)
+        .encode_wide()
+        .chain(iter::once(0))
+        .collect();
     let wide_message: Vec<u16> = OsStr::new(message)
         .encode_wide()
         .chain(iter::once(0))
@@ -92,145 +437,1496 @@ fn show_message_box(message: &str) {
         MessageBoxW(
             None,
             PCWSTR(wide_message.as_ptr()),
-            w!("Admin Required"),
-            MB_OK,
-        );
+            PCWSTR(wide_title.as_ptr()),
+            style,
+        )
     }
 }
 
-fn main() {
-    // Open our own executable to read appended data
-    let exe_path = env::current_exe().expect("Failed to get current exe path");
-    let mut file = fs::File::open(&exe_path).expect("Failed to open exe file");
-    let file_size = file.metadata().expect("Failed to get metadata").len();
+/// Handle `--uninstall`: after confirmation, remove everything the packed EXE
+/// created on this machine instead of extracting and launching.
+fn uninstall(header: &ArchiveHeader, exe_path: &Path, log: &mut StubLog) {
+    let extraction_path = Path::new(&header.extraction_path);
+    let prompt = format!("Remove all files extracted to {}?", extraction_path.display());
+    if message_box("Uninstall", &prompt, MB_YESNO | MB_ICONQUESTION) != IDYES {
+        log.info("Uninstall cancelled by user");
+        return;
+    }
 
-    if file_size < FOOTER_SIZE as u64 {
-        eprintln!("No appended resource archive found.");
+    // The service holds its binary open, so it has to go before the files do
+    if let Some(service) = &header.service {
+        let name = service_name(service, &header.main_file);
+        match remove_service(&name, log) {
+            Ok(()) => log.info(&format!("Removed service {}", name)),
+            Err(e) => {
+                log.error(&e);
+                message_box("Uninstall", &e, MB_OK | MB_ICONERROR);
+                return;
+            }
+        }
+    }
+
+    // Shortcuts live outside the extraction directory, so remove them first
+    // while the marker listing them still exists
+    if let Ok(created) = fs::read_to_string(extraction_path.join(SHORTCUT_MARKER_FILE)) {
+        for link in created.lines().filter(|line| !line.is_empty()) {
+            match fs::remove_file(link) {
+                Ok(()) => log.info(&format!("Removed shortcut {}", link)),
+                Err(e) => log.error(&format!("Failed to remove shortcut {}: {}", link, e)),
+            }
+        }
+    }
+
+    if extraction_path.exists() {
+        if is_dangerous_to_delete(extraction_path, exe_path) {
+            log.error(&format!("Refusing to uninstall {:?}, it looks like a protected folder", extraction_path));
+            message_box("Uninstall", "The extraction folder looks like a protected folder and was not removed.", MB_OK | MB_ICONERROR);
+            return;
+        }
+        if let Err(e) = fs::remove_dir_all(extraction_path) {
+            log.error(&format!("Failed to remove {:?}: {}", extraction_path, e));
+            message_box("Uninstall", &format!("Failed to remove {}: {}\nClose the application and try again.", extraction_path.display(), e), MB_OK | MB_ICONERROR);
+            return;
+        }
+    }
+
+    log.info(&format!("Uninstalled {:?}", extraction_path));
+    message_box("Uninstall", "Uninstall complete.", MB_OK | MB_ICONINFORMATION);
+}
+
+/// Resolve a shell known folder (Desktop, Start Menu Programs, ...) for the current user.
+fn known_folder(id: &GUID) -> Option<PathBuf> {
+    unsafe {
+        let raw = SHGetKnownFolderPath(id, KF_FLAG_DEFAULT, None).ok()?;
+        let path = raw.to_string().ok().map(PathBuf::from);
+        CoTaskMemFree(Some(raw.0 as _));
+        path
+    }
+}
+
+/// Write a .lnk file at `link_path` pointing to `target` through the IShellLink COM API.
+fn create_shortcut(link_path: &Path, target: &Path, arguments: &str, working_dir: &Path, icon: Option<&Path>) -> Result<(), String> {
+    unsafe {
+        // S_FALSE (already initialised on this thread) still needs a matching CoUninitialize
+        let initialized = CoInitializeEx(None, COINIT_APARTMENTTHREADED).is_ok();
+        let result = (|| -> windows::core::Result<()> {
+            let link: IShellLinkW = CoCreateInstance(&ShellLink, None, CLSCTX_INPROC_SERVER)?;
+            link.SetPath(&HSTRING::from(target.as_os_str()))?;
+            link.SetArguments(&HSTRING::from(arguments))?;
+            link.SetWorkingDirectory(&HSTRING::from(working_dir.as_os_str()))?;
+            if let Some(icon) = icon {
+                link.SetIconLocation(&HSTRING::from(icon.as_os_str()), 0)?;
+            }
+            let file: IPersistFile = link.cast()?;
+            file.Save(&HSTRING::from(link_path.as_os_str()), true)
+        })();
+        if initialized {
+            CoUninitialize();
+        }
+        result.map_err(|e| format!("Failed to create shortcut {:?}: {}", link_path, e))
+    }
+}
+
+/// Create the requested shortcuts to `target` unless a previous run already did,
+/// then record them in the marker file.
+fn create_shortcuts(options: &ShortcutOptions, target: &Path, extraction_path: &Path, log: &mut StubLog) {
+    let marker = extraction_path.join(SHORTCUT_MARKER_FILE);
+    if marker.exists() {
+        log.debug("Shortcuts were created on a previous run");
+        return;
+    }
+
+    let name = if options.name.is_empty() {
+        target.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default()
+    } else {
+        options.name.clone()
+    };
+    let icon = if options.icon.is_empty() { None } else { Some(extraction_path.join(&options.icon)) };
+
+    let mut folders = Vec::new();
+    if options.desktop {
+        folders.push(known_folder(&FOLDERID_Desktop));
+    }
+    if options.start_menu {
+        folders.push(known_folder(&FOLDERID_Programs));
+    }
+
+    let mut created = Vec::new();
+    for folder in folders {
+        let Some(folder) = folder else {
+            log.error("Failed to locate a shortcut folder");
+            continue;
+        };
+        let link_path = folder.join(format!("{}.lnk", name));
+        match create_shortcut(&link_path, target, &options.arguments, extraction_path, icon.as_deref()) {
+            Ok(()) => {
+                log.info(&format!("Created shortcut {:?}", link_path));
+                created.push(link_path.to_string_lossy().to_string());
+            }
+            Err(e) => log.error(&e),
+        }
+    }
+
+    if let Err(e) = fs::write(&marker, created.join("\n")) {
+        log.error(&format!("Failed to write shortcut marker {:?}: {}", marker, e));
+    }
+}
+
+/// Key name of the service the header asks for: its own name, or the main file's stem.
+fn service_name(options: &ServiceOptions, main_file: &str) -> String {
+    if options.name.is_empty() {
+        Path::new(main_file).file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default()
+    } else {
+        options.name.clone()
+    }
+}
+
+/// Register `binary` with the Service Control Manager, or update the service a
+/// previous run registered to match, then start it when the header asks to.
+fn install_service(options: &ServiceOptions, name: &str, binary: &Path, log: &mut StubLog) -> Result<(), String> {
+    let start_type = match options.start_type.as_str() {
+        "manual" => SERVICE_DEMAND_START,
+        "disabled" => SERVICE_DISABLED,
+        _ => SERVICE_AUTO_START,
+    };
+    let display_name = if options.display_name.is_empty() { name } else { options.display_name.as_str() };
+    let mut command_line = format!("\"{}\"", binary.display());
+    if !options.arguments.trim().is_empty() {
+        command_line.push(' ');
+        command_line.push_str(options.arguments.trim());
+    }
+
+    unsafe {
+        let manager = OpenSCManagerW(None, None, SC_MANAGER_CONNECT | SC_MANAGER_CREATE_SERVICE)
+            .map_err(|e| format!("Failed to open the Service Control Manager: {}", e))?;
+        let service = match CreateServiceW(
+            manager,
+            &HSTRING::from(name),
+            &HSTRING::from(display_name),
+            SERVICE_ALL_ACCESS,
+            SERVICE_WIN32_OWN_PROCESS,
+            start_type,
+            SERVICE_ERROR_NORMAL,
+            &HSTRING::from(command_line.as_str()),
+            None,
+            None,
+            None,
+            None,
+            None,
+        ) {
+            Ok(service) => {
+                log.info(&format!("Installed service {} ({})", name, command_line));
+                Ok(service)
+            }
+            // Installed by an earlier run, possibly with another extraction path or options
+            Err(e) if e.code() == ERROR_SERVICE_EXISTS.to_hresult() => {
+                OpenServiceW(manager, &HSTRING::from(name), SERVICE_ALL_ACCESS).and_then(|service| {
+                    let updated = ChangeServiceConfigW(
+                        service,
+                        SERVICE_WIN32_OWN_PROCESS,
+                        start_type,
+                        SERVICE_ERROR_NORMAL,
+                        &HSTRING::from(command_line.as_str()),
+                        None,
+                        None,
+                        None,
+                        None,
+                        None,
+                        &HSTRING::from(display_name),
+                    );
+                    match updated {
+                        Ok(()) => {
+                            log.info(&format!("Updated the existing service {} ({})", name, command_line));
+                            Ok(service)
+                        }
+                        Err(e) => {
+                            let _ = CloseServiceHandle(service);
+                            Err(e)
+                        }
+                    }
+                })
+            }
+            Err(e) => Err(e),
+        };
+        if let Ok(service) = &service {
+            set_service_description(*service, &options.description, log);
+        }
+        let result = match service {
+            Ok(service) => {
+                let started = if options.start_now && start_type != SERVICE_DISABLED {
+                    match StartServiceW(service, None) {
+                        Ok(()) => {
+                            log.info(&format!("Started service {}", name));
+                            Ok(())
+                        }
+                        Err(e) if e.code() == ERROR_SERVICE_ALREADY_RUNNING.to_hresult() => Ok(()),
+                        Err(e) => Err(format!("Service {} was installed but failed to start: {}", name, e)),
+                    }
+                } else {
+                    Ok(())
+                };
+                let _ = CloseServiceHandle(service);
+                started
+            }
+            Err(e) => Err(format!("Failed to install service {}: {}", name, e)),
+        };
+        let _ = CloseServiceHandle(manager);
+        result
+    }
+}
+
+/// Stop and remove the service `install_service` registered. A service that is
+/// not installed counts as removed.
+fn remove_service(name: &str, log: &mut StubLog) -> Result<(), String> {
+    unsafe {
+        let manager = OpenSCManagerW(None, None, SC_MANAGER_CONNECT)
+            .map_err(|e| format!("Failed to open the Service Control Manager: {}", e))?;
+        let result = match OpenServiceW(manager, &HSTRING::from(name), SERVICE_ALL_ACCESS) {
+            Ok(service) => {
+                let mut status = SERVICE_STATUS::default();
+                // Deleting (and removing the files) only works once it has let go of them
+                let stopped = if ControlService(service, SERVICE_CONTROL_STOP, &mut status).is_ok() {
+                    wait_for_service_stop(service, name, log)
+                } else {
+                    Ok(())
+                };
+                let deleted = stopped.and_then(|()| DeleteService(service).map_err(|e| format!("Failed to remove service {}: {}", name, e)));
+                let _ = CloseServiceHandle(service);
+                deleted
+            }
+            Err(e) if e.code() == ERROR_SERVICE_DOES_NOT_EXIST.to_hresult() => Ok(()),
+            Err(e) => Err(format!("Failed to open service {}: {}", name, e)),
+        };
+        let _ = CloseServiceHandle(manager);
+        result
+    }
+}
+
+/// Set the description shown in the Services console; empty leaves it unset.
+fn set_service_description(service: SC_HANDLE, description: &str, log: &mut StubLog) {
+    if description.is_empty() {
         return;
     }
+    let mut description: Vec<u16> = description.encode_utf16().chain(Some(0)).collect();
+    let info = SERVICE_DESCRIPTIONW { lpDescription: PWSTR(description.as_mut_ptr()) };
+    if let Err(e) = unsafe { ChangeServiceConfig2W(service, SERVICE_CONFIG_DESCRIPTION, Some(&info as *const _ as _)) } {
+        log.error(&format!("Failed to set the service description: {}", e));
+    }
+}
+
+/// Poll a service that was asked to stop until it has, for at most
+/// SERVICE_STOP_TIMEOUT_MS.
+fn wait_for_service_stop(service: SC_HANDLE, name: &str, log: &mut StubLog) -> Result<(), String> {
+    let started = std::time::Instant::now();
+    loop {
+        let mut status = SERVICE_STATUS::default();
+        unsafe { QueryServiceStatus(service, &mut status) }
+            .map_err(|e| format!("Failed to query service {}: {}", name, e))?;
+        if status.dwCurrentState == SERVICE_STOPPED {
+            log.info(&format!("Stopped service {}", name));
+            return Ok(());
+        }
+        if started.elapsed() >= std::time::Duration::from_millis(SERVICE_STOP_TIMEOUT_MS) {
+            return Err(format!("Service {} did not stop within {} seconds", name, SERVICE_STOP_TIMEOUT_MS / 1000));
+        }
+        std::thread::sleep(std::time::Duration::from_millis(SERVICE_STOP_POLL_MS));
+    }
+}
+
+/// Name of the mutex guarding a single-instance app. Derived from the extraction
+/// path and main file (FNV-1a), so every copy of the same packed EXE shares it.
+fn single_instance_mutex_name(header: &ArchiveHeader) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in format!("{}|{}", header.extraction_path, header.main_file).to_lowercase().bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("Local\\ResourceCompiler_{:016x}", hash)
+}
+
+/// Bring a visible top-level window of the process running `exe_path` to the front.
+/// Returns false if no such window was found (e.g. the app runs hidden).
+fn focus_running_instance(exe_path: &Path) -> bool {
+    struct Search {
+        exe_path: PathBuf,
+        found: Option<HWND>,
+    }
+
+    unsafe extern "system" fn check_window(hwnd: HWND, lparam: LPARAM) -> BOOL {
+        let search = unsafe { &mut *(lparam.0 as *mut Search) };
+        if !unsafe { IsWindowVisible(hwnd) }.as_bool() {
+            return BOOL(1);
+        }
+        let mut process_id = 0u32;
+        unsafe { GetWindowThreadProcessId(hwnd, Some(&mut process_id)) };
+        let Ok(process) = (unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, process_id) }) else {
+            return BOOL(1);
+        };
+        let mut buffer = [0u16; 1024];
+        let mut length = buffer.len() as u32;
+        let queried = unsafe { QueryFullProcessImageNameW(process, PROCESS_NAME_WIN32, PWSTR(buffer.as_mut_ptr()), &mut length) };
+        unsafe {
+            let _ = CloseHandle(process);
+        }
+        if queried.is_ok() {
+            let image = String::from_utf16_lossy(&buffer[..length as usize]);
+            if image.eq_ignore_ascii_case(&search.exe_path.to_string_lossy()) {
+                search.found = Some(hwnd);
+                return BOOL(0); // stop enumerating
+            }
+        }
+        BOOL(1)
+    }
+
+    let mut search = Search {
+        exe_path: fs::canonicalize(exe_path).unwrap_or_else(|_| exe_path.to_path_buf()),
+        found: None,
+    };
+    // canonicalize adds a \\?\ prefix that process image names don't have
+    if let Some(stripped) = search.exe_path.to_str().and_then(|p| p.strip_prefix(r"\\?\")) {
+        search.exe_path = PathBuf::from(stripped);
+    }
+    unsafe {
+        // Returns an error when the callback stops early, which is what we want
+        let _ = EnumWindows(Some(check_window), LPARAM(&mut search as *mut Search as isize));
+    }
+
+    match search.found {
+        Some(hwnd) => unsafe {
+            if IsIconic(hwnd).as_bool() {
+                let _ = ShowWindow(hwnd, SW_RESTORE);
+            }
+            let _ = SetForegroundWindow(hwnd);
+            true
+        },
+        None => false,
+    }
+}
+
+/// Borderless topmost window built on the system STATIC class, used to show
+/// extraction progress when the stub has no console to print to.
+struct ProgressWindow {
+    hwnd: HWND,
+}
+
+impl ProgressWindow {
+    fn new() -> Option<Self> {
+        let (width, height) = (360, 60);
+        let (screen_w, screen_h) = unsafe { (GetSystemMetrics(SM_CXSCREEN), GetSystemMetrics(SM_CYSCREEN)) };
+        let hwnd = unsafe {
+            CreateWindowExW(
+                WS_EX_TOPMOST | WS_EX_TOOLWINDOW,
+                w!("STATIC"),
+                w!("Extracting..."),
+                WS_POPUP | WS_VISIBLE | WS_BORDER | WINDOW_STYLE(SS_CENTER.0 | SS_CENTERIMAGE.0),
+                (screen_w - width) / 2,
+                (screen_h - height) / 2,
+                width,
+                height,
+                None,
+                None,
+                None,
+                None,
+            )
+        }.ok()?;
+        let window = Self { hwnd };
+        window.pump_messages();
+        Some(window)
+    }
+
+    fn set_text(&self, text: &str) {
+        use std::ffi::OsStr;
+        use std::iter;
+        use std::os::windows::ffi::OsStrExt;
+
+        let wide_text: Vec<u16> = OsStr::new(text)
+            .encode_wide()
+            .chain(iter::once(0))
+            .collect();
+        unsafe { let _ = SetWindowTextW(self.hwnd, PCWSTR(wide_text.as_ptr())); }
+        self.pump_messages();
+    }
+
+    // We never block in a message loop, so drain pending messages to keep the window painted.
+    fn pump_messages(&self) {
+        let mut msg = MSG::default();
+        unsafe {
+            while PeekMessageW(&mut msg, None, 0, 0, PM_REMOVE).as_bool() {
+                let _ = TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+            }
+        }
+    }
+}
+
+impl Drop for ProgressWindow {
+    fn drop(&mut self) {
+        unsafe { let _ = DestroyWindow(self.hwnd); }
+    }
+}
+
+/// Read-only, console-like window showing the output of a launched CLI tool.
+/// It's a top-level EDIT control, so closing it needs no window procedure of our own.
+struct OutputWindow {
+    hwnd: HWND,
+}
+
+impl OutputWindow {
+    fn new(title: &str) -> Option<Self> {
+        let style = WS_OVERLAPPEDWINDOW | WS_VISIBLE | WS_VSCROLL
+            | WINDOW_STYLE((ES_MULTILINE | ES_READONLY | ES_AUTOVSCROLL) as u32);
+        let hwnd = unsafe {
+            CreateWindowExW(
+                WINDOW_EX_STYLE(0),
+                w!("EDIT"),
+                &HSTRING::from(title),
+                style,
+                CW_USEDEFAULT,
+                CW_USEDEFAULT,
+                720,
+                480,
+                None,
+                None,
+                None,
+                None,
+            )
+        }.ok()?;
+        unsafe {
+            let font = GetStockObject(ANSI_FIXED_FONT);
+            SendMessageW(hwnd, WM_SETFONT, Some(WPARAM(font.0 as usize)), Some(LPARAM(1)));
+        }
+        Some(Self { hwnd })
+    }
+
+    fn append(&self, text: &str) {
+        // EDIT controls need CRLF line breaks
+        let text = text.replace("\r\n", "\n").replace('\n', "\r\n");
+        let wide_text = HSTRING::from(text);
+        unsafe {
+            let end = GetWindowTextLengthW(self.hwnd) as usize;
+            SendMessageW(self.hwnd, EM_SETSEL, Some(WPARAM(end)), Some(LPARAM(end as isize)));
+            SendMessageW(self.hwnd, EM_REPLACESEL, Some(WPARAM(0)), Some(LPARAM(wide_text.as_ptr() as isize)));
+        }
+    }
+
+    fn set_title(&self, title: &str) {
+        unsafe { let _ = SetWindowTextW(self.hwnd, &HSTRING::from(title)); }
+    }
+
+    fn is_open(&self) -> bool {
+        unsafe { IsWindow(Some(self.hwnd)).as_bool() }
+    }
+}
+
+/// Run `program` without a console, relaying its stdout and stderr into an
+/// OutputWindow until the user closes the window. `raw_arguments` is appended
+/// to the command line unquoted, as typed by the packer.
+fn run_with_output_window(program: &Path, arguments: &[String], raw_arguments: &str, working_dir: &Path) -> Result<(), String> {
+    use std::os::windows::process::CommandExt;
+    use std::process::{Command, Stdio};
+    use std::sync::mpsc;
+
+    const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+    let mut command = Command::new(program);
+    command.args(arguments);
+    if !raw_arguments.is_empty() {
+        command.raw_arg(raw_arguments);
+    }
+    let mut child = command
+        .current_dir(working_dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .creation_flags(CREATE_NO_WINDOW)
+        .spawn()
+        .map_err(|e| format!("Failed to start {:?}: {}", program, e))?;
+
+    // One reader thread per pipe, both feeding the window through a channel
+    let (sender, receiver) = mpsc::channel::<String>();
+    let pipes: [Option<Box<dyn Read + Send>>; 2] = [
+        child.stdout.take().map(|pipe| Box::new(pipe) as Box<dyn Read + Send>),
+        child.stderr.take().map(|pipe| Box::new(pipe) as Box<dyn Read + Send>),
+    ];
+    for mut pipe in pipes.into_iter().flatten() {
+        let sender = sender.clone();
+        std::thread::spawn(move || {
+            let mut buffer = [0u8; 4096];
+            while let Ok(read) = pipe.read(&mut buffer) {
+                if read == 0 || sender.send(String::from_utf8_lossy(&buffer[..read]).to_string()).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    drop(sender);
+
+    let title = program.file_name().map_or_else(|| "Output".to_string(), |name| name.to_string_lossy().to_string());
+    let window = OutputWindow::new(&title).ok_or("Failed to create the output window")?;
+    let mut exited = false;
+    while window.is_open() {
+        let mut msg = MSG::default();
+        unsafe {
+            while PeekMessageW(&mut msg, None, 0, 0, PM_REMOVE).as_bool() {
+                let _ = TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+            }
+        }
+        while let Ok(chunk) = receiver.try_recv() {
+            window.append(&chunk);
+        }
+        if !exited
+            && let Ok(Some(status)) = child.try_wait()
+        {
+            exited = true;
+            window.set_title(&format!("{} (exited with code {})", title, status.code().unwrap_or(-1)));
+        }
+        std::thread::sleep(std::time::Duration::from_millis(15));
+    }
+    Ok(())
+}
+
+/// Archive bytes (header JSON + resource data): mapped straight from a file,
+/// the EXE's own resource section, or read into memory.
+enum ArchiveData {
+    Mapped(memmap2::Mmap, std::ops::Range<usize>),
+    Static(&'static [u8]),
+    Owned(Vec<u8>),
+}
+
+impl std::ops::Deref for ArchiveData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            ArchiveData::Mapped(map, range) => &map[range.clone()],
+            ArchiveData::Static(bytes) => bytes,
+            ArchiveData::Owned(bytes) => bytes,
+        }
+    }
+}
+
+/// Read the archive (header JSON + resource bytes) terminated by our footer at the
+/// end of `path`. Returns the header length and the archive data.
+fn read_archive(path: &Path) -> Result<(usize, ArchiveData), String> {
+    let mut file = fs::File::open(path)
+        .map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+
+    // Map the file so only the pages we actually touch get read; fall back to
+    // reading the archive into memory where mapping fails (e.g. empty files)
+    // SAFETY: the map is read-only, and a running EXE (or its sidecar, which
+    // we hold open) can't be truncated underneath us
+    if let Ok(map) = unsafe { memmap2::Mmap::map(&file) } {
+        let (header_length, range) = archive_range(&map)?;
+        return Ok((header_length, ArchiveData::Mapped(map, range)));
+    }
+    let file_size = file.metadata()
+        .map_err(|e| format!("Failed to get metadata: {}", e))?
+        .len();
+
+    if file_size < FOOTER_SIZE as u64 {
+        return Err("No appended resource archive found.".to_string());
+    }
 
     // Read the footer (last FOOTER_SIZE bytes)
     file.seek(SeekFrom::End(-(FOOTER_SIZE as i64)))
-        .expect("Failed to seek to footer");
+        .map_err(|e| format!("Failed to seek to footer: {}", e))?;
     let mut footer_buf = [0u8; FOOTER_SIZE];
     file.read_exact(&mut footer_buf)
-        .expect("Failed to read footer");
+        .map_err(|e| format!("Failed to read footer: {}", e))?;
+    let (header_length, archive_data_length) = parse_footer(&footer_buf)?;
 
-    let header_length = u32::from_le_bytes(footer_buf[0..4].try_into().unwrap()) as usize;
-    let archive_data_length = u32::from_le_bytes(footer_buf[4..8].try_into().unwrap()) as usize;
-    let marker = &footer_buf[8..24];
+    // Locate and read the appended archive data, making sure the declared
+    // length actually fits in the file before allocating a buffer for it
+    let declared_size = archive_data_length as u64 + FOOTER_SIZE as u64;
+    if declared_size > file_size {
+        return Err(format!("Declared archive size {} exceeds file size {}.", declared_size, file_size));
+    }
+    let archive_start = file_size - declared_size;
+    file.seek(SeekFrom::Start(archive_start))
+        .map_err(|e| format!("Failed to seek to archive start: {}", e))?;
 
-    if marker != FOOTER_MARKER {
-        eprintln!("Invalid resource archive marker.");
-        return;
+    let mut archive_data = vec![0u8; archive_data_length];
+    file.read_exact(&mut archive_data)
+        .map_err(|e| format!("Failed to read archive data: {}", e))?;
+
+    Ok((header_length, ArchiveData::Owned(archive_data)))
+}
+
+/// Reassemble a split build from our own EXE and the part files next to it.
+/// Returns `None` when the EXE doesn't end with a split trailer.
+fn read_split_archive(exe_path: &Path) -> Option<Result<(usize, ArchiveData), String>> {
+    let mut file = fs::File::open(exe_path).ok()?;
+    file.seek(SeekFrom::End(-(SPLIT_TRAILER_SIZE as i64))).ok()?;
+    let mut trailer = [0u8; SPLIT_TRAILER_SIZE];
+    file.read_exact(&mut trailer).ok()?;
+    if &trailer[4..] != SPLIT_MARKER {
+        return None;
     }
+    let volumes = read_u32_le(&trailer, 0, "split trailer").ok()?;
 
-    // Locate and read the appended archive data
-    let archive_start = file_size as i64 - (archive_data_length as i64 + FOOTER_SIZE as i64);
-    if archive_start < 0 {
-        eprintln!("Invalid archive start.");
-        return;
+    let mut bytes = match fs::read(exe_path) {
+        Ok(bytes) => bytes,
+        Err(e) => return Some(Err(format!("Failed to read {:?}: {}", exe_path, e))),
+    };
+    bytes.truncate(bytes.len() - SPLIT_TRAILER_SIZE);
+    // Parts only hold archive data, so they can't add up to more than the archive limit
+    let size_limit = bytes.len() + MAX_ARCHIVE_SIZE + FOOTER_SIZE;
+    for volume in 2..=volumes {
+        let part_path = exe_path.with_extension(format!("part{}", volume));
+        match fs::read(&part_path) {
+            Ok(part) => bytes.extend_from_slice(&part),
+            Err(e) => return Some(Err(format!("Missing volume {:?} (part {} of {}): {}", part_path, volume, volumes, e))),
+        }
+        if bytes.len() > size_limit {
+            return Some(Err("Split archive exceeds the archive size limit.".to_string()));
+        }
     }
-    file.seek(SeekFrom::Start(archive_start as u64))
-        .expect("Failed to seek to archive start");
+    Some(archive_range(&bytes).map_err(String::from).map(|(header_length, range)| {
+        bytes.truncate(range.end);
+        bytes.drain(..range.start);
+        (header_length, ArchiveData::Owned(bytes))
+    }))
+}
 
-    let mut archive_data = vec![0u8; archive_data_length];
-    file.read_exact(&mut archive_data)
-        .expect("Failed to read archive data");
+/// Read the archive from the RCDATA resource embedded in our own EXE (PE resource
+/// build mode). Returns `None` when the EXE carries no such resource.
+fn read_resource_archive() -> Option<Result<(usize, ArchiveData), String>> {
+    let bytes = unsafe {
+        let resource = FindResourceW(None, ARCHIVE_RESOURCE_NAME, RT_RCDATA);
+        if resource.is_invalid() {
+            return None;
+        }
+        let size = SizeofResource(None, resource) as usize;
+        let loaded = LoadResource(None, resource).ok()?;
+        let data = LockResource(loaded);
+        if data.is_null() {
+            return None;
+        }
+        std::slice::from_raw_parts(data as *const u8, size)
+    };
 
-    if header_length > archive_data.len() {
-        eprintln!("Invalid header length.");
-        return;
+    // Resource memory stays valid for the life of the process, no copy needed
+    Some(archive_range(bytes).map_err(String::from).map(|(header_length, range)| (header_length, ArchiveData::Static(&bytes[range]))))
+}
+
+/// Find our archive: a PE resource, a split build, data appended to the EXE or,
+/// failing all of those, a sidecar pack next to it.
+fn load_archive(exe_path: &Path, log: &mut StubLog) -> Result<(usize, ArchiveData), String> {
+    read_resource_archive()
+        .or_else(|| read_split_archive(exe_path))
+        .unwrap_or_else(|| read_archive(exe_path))
+        .or_else(|exe_err| {
+            let sidecar_path = exe_path.with_extension("rscpack");
+            if !sidecar_path.exists() {
+                return Err(exe_err);
+            }
+            log.debug(&format!("No embedded archive ({}), reading sidecar {:?}", exe_err, sidecar_path));
+            read_archive(&sidecar_path)
+        })
+}
+
+/// Windows errors for a file another process has open without sharing it
+const ERROR_SHARING_VIOLATION_CODE: i32 = 32;
+const ERROR_LOCK_VIOLATION_CODE: i32 = 33;
+/// How often, and how far apart, writing a locked file is retried
+const LOCKED_FILE_RETRIES: u32 = 5;
+const LOCKED_FILE_RETRY_DELAY_MS: u64 = 200;
+
+/// First delay between the header's io_retries, doubled after each failed attempt
+const IO_RETRY_DELAY_MS: u64 = 250;
+const IO_RETRY_MAX_DELAY_MS: u64 = 4000;
+/// Windows errors of a network share or device that is briefly unavailable:
+/// not ready, remote computer gone, bad/busy/unreachable network path or name,
+/// network error, name deleted, semaphore timeout
+const TRANSIENT_IO_ERROR_CODES: &[i32] = &[21, 51, 53, 54, 55, 59, 64, 67, 121, 1231];
+
+fn is_transient_io_error(e: &std::io::Error) -> bool {
+    e.raw_os_error().is_some_and(|code| TRANSIENT_IO_ERROR_CODES.contains(&code))
+        || matches!(e.kind(), std::io::ErrorKind::TimedOut | std::io::ErrorKind::Interrupted)
+}
+
+/// Run `op` until it succeeds, at most `retries` more times while it fails with
+/// a transient error, waiting longer after each failure. Extraction paths on
+/// network shares can fail to be created or written for a moment; anything
+/// else (access denied, a bad name, a locked file) is returned straight away.
+fn with_retries<T>(retries: u32, log: &mut StubLog, mut op: impl FnMut() -> std::io::Result<T>) -> std::io::Result<T> {
+    let mut delay = IO_RETRY_DELAY_MS;
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        match op() {
+            Err(e) if is_transient_io_error(&e) && attempt <= retries => {
+                log.debug(&format!("Attempt {} of {} failed: {}, retrying in {} ms", attempt, retries + 1, e, delay));
+                std::thread::sleep(std::time::Duration::from_millis(delay));
+                delay = (delay * 2).min(IO_RETRY_MAX_DELAY_MS);
+            }
+            Err(e) if attempt > 1 => {
+                log.error(&format!("Gave up after {} attempts: {}", attempt, e));
+                return Err(e);
+            }
+            result => return result,
+        }
+    }
+}
+
+fn is_file_locked_error(e: &std::io::Error) -> bool {
+    matches!(e.raw_os_error(), Some(ERROR_SHARING_VIOLATION_CODE | ERROR_LOCK_VIOLATION_CODE))
+        || e.kind() == std::io::ErrorKind::PermissionDenied
+}
+
+/// Write one extracted file, returning false if it was left as it was.
+/// A file held open by another process (usually an earlier copy of the app
+/// that is still running) is skipped when it already has the right contents,
+/// otherwise writing it is retried for a moment before giving up. Transient
+/// network errors are retried up to `io_retries` times on their own.
+fn write_extracted_file(path: &Path, data: &[u8], io_retries: u32, log: &mut StubLog) -> Result<bool, String> {
+    let mut attempt = 0;
+    loop {
+        let e = match with_retries(io_retries, log, || fs::write(path, data)) {
+            Ok(()) => return Ok(true),
+            Err(e) if is_file_locked_error(&e) => e,
+            Err(e) => return Err(format!("Failed to write file {:?}: {}", path, e)),
+        };
+        let up_to_date = fs::metadata(path).is_ok_and(|m| m.len() == data.len() as u64)
+            && fs::read(path).is_ok_and(|existing| existing == data);
+        if up_to_date {
+            log.info(&format!("{:?} is in use but already up to date, skipped", path));
+            return Ok(false);
+        }
+        if attempt == LOCKED_FILE_RETRIES {
+            return Err(format!(
+                "{:?} is in use by another program, probably a copy of this application that is still running. Close it and try again.\n\n({})",
+                path, e
+            ));
+        }
+        attempt += 1;
+        log.debug(&format!("{:?} is locked ({}), retry {} of {}", path, e, attempt, LOCKED_FILE_RETRIES));
+        std::thread::sleep(std::time::Duration::from_millis(LOCKED_FILE_RETRY_DELAY_MS));
+    }
+}
+
+const REGISTRY_TOKEN_START: &str = "{reg:";
+/// Used for a registry token whose value is missing and that has no fallback
+const DEFAULT_EXTRACTION_DIR: &str = "rc_extracted";
+
+/// Replace `{reg:ROOT\Key\Path\Value|fallback}` tokens in an extraction path
+/// with the string value read from the registry. A missing key or value is
+/// replaced by the fallback after `|`, or DEFAULT_EXTRACTION_DIR without one.
+fn resolve_registry_tokens(path: &str, log: &mut StubLog) -> String {
+    let mut resolved = String::new();
+    let mut rest = path;
+    while let Some(start) = rest.find(REGISTRY_TOKEN_START) {
+        let Some(end) = rest[start..].find('}').map(|end| start + end) else {
+            break;
+        };
+        resolved.push_str(&rest[..start]);
+        let token = &rest[start + REGISTRY_TOKEN_START.len()..end];
+        let (registry_path, fallback) = match token.split_once('|') {
+            Some((registry_path, fallback)) => (registry_path, Some(fallback)),
+            None => (token, None),
+        };
+        match read_registry_string(registry_path) {
+            Ok(value) => {
+                log.debug(&format!("Registry value {} = {}", registry_path, value));
+                resolved.push_str(&value);
+            }
+            Err(e) => {
+                let fallback = fallback.unwrap_or(DEFAULT_EXTRACTION_DIR);
+                log.info(&format!("Registry value {} unavailable ({}), using {}", registry_path, e, fallback));
+                resolved.push_str(fallback);
+            }
+        }
+        rest = &rest[end + 1..];
+    }
+    resolved.push_str(rest);
+    resolved
+}
+
+/// Expand %VARIABLE% references, leaving unknown ones as they are
+fn expand_env_vars(text: &str) -> String {
+    let mut expanded = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('%') {
+        let Some(len) = rest[start + 1..].find('%') else {
+            break;
+        };
+        let reference = &rest[start..start + len + 2];
+        expanded.push_str(&rest[..start]);
+        match env::var(&reference[1..reference.len() - 1]) {
+            Ok(value) if len > 0 => expanded.push_str(&value),
+            _ => expanded.push_str(reference),
+        }
+        rest = &rest[start + len + 2..];
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+/// Registry root named at the start of a registry path (HKLM, HKCU, ...)
+fn registry_root(name: &str) -> Result<HKEY, String> {
+    match name.to_ascii_uppercase().as_str() {
+        "HKLM" | "HKEY_LOCAL_MACHINE" => Ok(HKEY_LOCAL_MACHINE),
+        "HKCU" | "HKEY_CURRENT_USER" => Ok(HKEY_CURRENT_USER),
+        "HKCR" | "HKEY_CLASSES_ROOT" => Ok(HKEY_CLASSES_ROOT),
+        "HKU" | "HKEY_USERS" => Ok(HKEY_USERS),
+        other => Err(format!("unknown registry root {}", other)),
+    }
+}
+
+/// Whether a key given as `ROOT\Key\Path` exists and can be read
+fn registry_key_exists(registry_path: &str) -> bool {
+    let (root, key) = registry_path.split_once('\\').unwrap_or((registry_path, ""));
+    let Ok(root) = registry_root(root) else {
+        return false;
+    };
+    let mut handle = HKEY::default();
+    let opened = unsafe { RegOpenKeyExW(root, &HSTRING::from(key), None, KEY_READ, &mut handle) }.is_ok();
+    if opened {
+        unsafe {
+            let _ = RegCloseKey(handle);
+        }
     }
+    opened
+}
+
+/// Read a string value given as `ROOT\Key\Path\Value`. A trailing backslash
+/// (empty value name) reads the key's default value. REG_EXPAND_SZ values
+/// come back with their environment variables expanded.
+fn read_registry_string(registry_path: &str) -> Result<String, String> {
+    let (root, key_and_value) = registry_path.split_once('\\').ok_or("no registry key given")?;
+    let root = registry_root(root)?;
+    let (key, value) = key_and_value.rsplit_once('\\').unwrap_or(("", key_and_value));
+    let wide_key = HSTRING::from(key);
+    let wide_value = HSTRING::from(value);
+    let value_name = if value.is_empty() { PCWSTR::null() } else { PCWSTR(wide_value.as_ptr()) };
+
+    // First call asks for the size in bytes, second reads the value
+    let mut size = 0u32;
+    unsafe { RegGetValueW(root, &wide_key, value_name, RRF_RT_REG_SZ, None, None, Some(&mut size)) }
+        .ok()
+        .map_err(|e| e.to_string())?;
+    let mut buffer = vec![0u16; (size as usize).div_ceil(2)];
+    unsafe { RegGetValueW(root, &wide_key, value_name, RRF_RT_REG_SZ, None, Some(buffer.as_mut_ptr().cast()), Some(&mut size)) }
+        .ok()
+        .map_err(|e| e.to_string())?;
+    let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+    Ok(String::from_utf16_lossy(&buffer[..len]))
+}
+
+/// `--verify`: check the archive without extracting or launching anything:
+/// header, checksum, decompression and that every resource fits the data.
+fn verify_archive(header_length: usize, archive_data: &[u8]) -> Result<String, String> {
+    let header = parse_header(&archive_data[..header_length])?;
+    let resource_bytes = &archive_data[header_length..];
+    header.check_data_crc(resource_bytes)?;
+
+    let max_extract_size = header.extract_limit();
+    let data_len = header.open_resource_data(resource_bytes, max_extract_size)?.uncompressed_len()?;
+    if data_len as u64 > max_extract_size {
+        return Err(format!("Decompressed data exceeds the {} byte extraction limit.", max_extract_size));
+    }
+    header.check_layout(data_len)?;
+
+    Ok(format!(
+        "{} resource(s), {} bytes{}",
+        header.resources.len(),
+        data_len,
+        if header.data_crc32.is_some() { ", checksum OK" } else { ", no checksum recorded" }
+    ))
+}
+
+/// Print the `--verify` result to the console we were started from (the stub
+/// normally has none of its own), or show it in a message box. Returns the exit code.
+fn report_verification(result: Result<String, String>) -> i32 {
+    let (text, code) = match result {
+        Ok(summary) => (format!("PASS: {}", summary), 0),
+        Err(e) => (format!("FAIL: {}", e), 1),
+    };
+    let has_console = unsafe { !GetConsoleWindow().is_invalid() || AttachConsole(ATTACH_PARENT_PROCESS).is_ok() };
+    if has_console {
+        println!("{}", text);
+    } else {
+        let icon = if code == 0 { MB_ICONINFORMATION } else { MB_ICONERROR };
+        message_box("Verify", &text, MB_OK | icon);
+    }
+    code
+}
+
+fn main() {
+    // Everything the stub holds (log file, mapped archive, instance mutex, progress
+    // window) is owned by run() and dropped when it returns, so the exit code is only
+    // applied afterwards. ExitCode can't carry a full 32-bit Windows exit code.
+    if let Some(code) = run() {
+        std::process::exit(code);
+    }
+}
+
+fn run() -> Option<i32> {
+    let mut log = StubLog::new();
+
+    // Open our own executable to read appended data
+    let exe_path = match env::current_exe() {
+        Ok(exe_path) => exe_path,
+        Err(e) => {
+            let detail = format!("Failed to locate the running EXE: {}", e);
+            if env::args().any(|arg| arg == VERIFY_ARG) {
+                return Some(report_verification(Err(detail)));
+            }
+            report_failure(&mut log, &StubMessages::default(), StubFailure::Extraction, &detail);
+            return None;
+        }
+    };
+    log.debug(&format!("Stub started from {:?}", exe_path));
+    log.debug(&String::from_utf8_lossy(CAPABILITIES));
+    let load_started = std::time::Instant::now();
+    let archive = load_archive(&exe_path, &mut log);
+    if env::args().any(|arg| arg == VERIFY_ARG) {
+        let result = archive.and_then(|(header_length, archive_data)| verify_archive(header_length, &archive_data));
+        return Some(report_verification(result));
+    }
+    let (header_length, archive_data) = match archive {
+        Ok(archive) => archive,
+        Err(e) => {
+            // No header yet, so only the default wording is available
+            report_failure(&mut log, &StubMessages::default(), StubFailure::CorruptArchive, &e);
+            return None;
+        }
+    };
     let header_json = &archive_data[0..header_length];
     let resource_bytes = &archive_data[header_length..];
 
     // Deserialize the header JSON
-    let header: ArchiveHeader = serde_json::from_slice(header_json)
-        .expect("Failed to parse header JSON");
+    let mut header = match parse_header(header_json) {
+        Ok(header) => header,
+        Err(e) => {
+            report_failure(&mut log, &StubMessages::default(), StubFailure::CorruptArchive, &e.to_string());
+            return None;
+        }
+    };
+
+    // Now that we know the configured verbosity, start the log file
+    let log_stem = exe_path.file_stem().map_or_else(|| "stub".into(), |s| s.to_string_lossy().to_string());
+    let log_path = env::temp_dir().join(format!("{}_extract.log", log_stem));
+    log.set_level(LogLevel::from_header(&header.log_level), &log_path);
+    log.info(&format!(
+        "Archive loaded: {} resource(s), compressed: {}, archive size: {} bytes",
+        header.resources.len(), header.is_compressed, archive_data.len()
+    ));
+    log.debug(&format!(
+        "Archive located in {:?} ({})",
+        load_started.elapsed(),
+        match archive_data {
+            ArchiveData::Mapped(..) => "memory-mapped",
+            ArchiveData::Static(_) => "PE resource",
+            ArchiveData::Owned(_) => "read into memory",
+        }
+    ));
+    log.info(&format!(
+        "Package version: {}, built: {}",
+        if header.package_version.is_empty() { "unset" } else { &header.package_version },
+        if header.build_time.is_empty() { "unknown" } else { &header.build_time }
+    ));
+
+    // Registry tokens and %VARIABLES% are resolved on this machine before anything
+    // (cleaning, uninstalling, extracting) uses the path
+    let mut resolved = header.extraction_path.clone();
+    if resolved.contains(REGISTRY_TOKEN_START) {
+        resolved = resolve_registry_tokens(&resolved, &mut log);
+    }
+    let resolved = expand_env_vars(&resolved);
+    if resolved != header.extraction_path {
+        log.info(&format!("Extraction path {} resolved to {}", header.extraction_path, resolved));
+        header.extraction_path = resolved;
+    }
 
     // Check if admin rights are required and if we have them
     if header.run_as_admin {
         match is_elevated() {
             Ok(elevated) => {
+                log.debug(&format!("Admin rights required, elevated: {}", elevated));
                 if !elevated {
-                    show_message_box("Please run as administrator.");
-                    return;
+                    // Relaunch ourselves with the "runas" verb so UAC prompts automatically.
+                    // The marker argument stops us looping if elevation silently fails.
+                    let already_relaunched = env::args().any(|arg| arg == ELEVATED_RELAUNCH_ARG);
+                    if !already_relaunched {
+                        let mut parameters: Vec<String> = env::args()
+                            .skip(1)
+                            .map(|arg| format!("\"{}\"", arg))
+                            .collect();
+                        parameters.push(ELEVATED_RELAUNCH_ARG.to_string());
+                        // With wait_for_exit the elevated copy's exit code is the main file's, pass it on
+                        match launch_process("runas", &exe_path.to_string_lossy(), &parameters.join(" "), None, SW_SHOWNORMAL, header.wait_for_exit) {
+                            Ok(exit_code) => {
+                                log.info("Relaunched elevated, exiting non-elevated instance");
+                                return exit_code.map(|exit_code| exit_code as i32);
+                            }
+                            // Most likely the user declined the UAC prompt
+                            Err(e) => log.error(&format!("Elevated relaunch failed: {}", e.message)),
+                        }
+                    }
+                    log.error("Not running as administrator");
+                    show_admin_message(&header, "Please run as administrator.");
+                    return None;
                 }
             }
             Err(err) => {
-                eprintln!("Failed to check admin rights: {}", err);
-                show_message_box("Failed to check admin rights. Please run as administrator.");
-                return;
+                log.error(&format!("Failed to check admin rights: {}", err));
+                show_admin_message(&header, "Failed to check admin rights. Please run as administrator.");
+                return None;
             }
         }
     }
 
+    if header.allow_uninstall && env::args().any(|arg| arg == UNINSTALL_ARG) {
+        uninstall(&header, &exe_path, &mut log);
+        return None;
+    }
+
+    // Single instance: the first copy holds a named mutex until the main file
+    // exits; later copies focus the running app instead of re-extracting
+    let mut _instance_mutex = None;
+    if header.single_instance {
+        let name = HSTRING::from(single_instance_mutex_name(&header));
+        match unsafe { CreateMutexW(None, true, &name) } {
+            Ok(mutex) if unsafe { GetLastError() } == ERROR_ALREADY_EXISTS => {
+                unsafe {
+                    let _ = CloseHandle(mutex);
+                }
+                let main_file_path = Path::new(&header.extraction_path).join(&header.main_file);
+                let focused = !header.main_is_external && focus_running_instance(&main_file_path);
+                log.info(&format!("Another instance is already running (focused window: {}), exiting", focused));
+                return None;
+            }
+            Ok(mutex) => _instance_mutex = Some(mutex),
+            Err(e) => log.error(&format!("Failed to create single instance mutex: {}", e)),
+        }
+    }
+
+    // Stop with a helpful message if a required runtime is missing, rather than
+    // extracting an app that would only crash on launch
+    if let Some(missing) = header.dependencies.iter().find(|dependency| !is_dependency_present(dependency)) {
+        let name = if missing.name.is_empty() { &missing.path } else { &missing.name };
+        log.error(&format!("Missing dependency {} ({} {})", name, missing.kind, missing.path));
+        let text = if missing.message.is_empty() {
+            format!("This application requires {}, which was not found on this computer. Please install it and try again.", name)
+        } else {
+            missing.message.clone()
+        };
+        let title = if header.messages.title.is_empty() { "Missing Requirement" } else { &header.messages.title };
+        message_box(title, &text, MB_OK | MB_ICONERROR);
+        return None;
+    }
+
+    // First run: the user accepts the license before anything is written.
+    // Declining exits without extracting or launching.
+    let eula_marker = Path::new(&header.extraction_path).join(EULA_MARKER_FILE);
+    let show_eula = !header.eula_text.is_empty() && !eula_marker.exists();
+    if show_eula {
+        let title = if header.eula_title.is_empty() { "License Agreement" } else { &header.eula_title };
+        let prompt = format!("{}\n\nDo you accept these terms? Yes = Accept, No = Decline", header.eula_text);
+        if message_box(title, &prompt, MB_YESNO | MB_ICONQUESTION | MB_DEFBUTTON2) != IDYES {
+            log.info("License declined, exiting");
+            return None;
+        }
+        log.info("License accepted");
+    }
+
+    // Don't clobber (or clean) a folder of the user's own files without consent
+    if !confirm_overwrite(&header, &mut log) {
+        log.info("Extraction cancelled, the extraction directory holds other files");
+        return None;
+    }
+
+    // Start from an empty extraction directory if requested, but never remove
+    // anything that looks like a system, profile or the stub's own folder
+    if header.clean_before_extract && Path::new(&header.extraction_path).exists() {
+        if is_dangerous_to_delete(Path::new(&header.extraction_path), &exe_path) {
+            report_failure(&mut log, &header.messages, StubFailure::Extraction, &format!("Refusing to clean {:?}, it looks like a protected folder", header.extraction_path));
+            return None;
+        }
+        log.info(&format!("Removing existing extraction directory {}", header.extraction_path));
+        if let Err(e) = fs::remove_dir_all(&header.extraction_path) {
+            report_failure(&mut log, &header.messages, StubFailure::Extraction, &format!("Failed to clean extraction directory {}: {}", header.extraction_path, e));
+            return None;
+        }
+    }
+
     // Create the extraction directory
-    fs::create_dir_all(&header.extraction_path)
-        .expect("Failed to create extraction directory");
+    if let Err(e) = with_retries(header.io_retries, &mut log, || fs::create_dir_all(&header.extraction_path)) {
+        report_failure(&mut log, &header.messages, StubFailure::Extraction, &format!("Failed to create extraction directory {}: {}", header.extraction_path, e));
+        return None;
+    }
+    let resolved_extraction_path = fs::canonicalize(&header.extraction_path)
+        .unwrap_or_else(|_| Path::new(&header.extraction_path).to_path_buf());
+    log.info(&format!("Extraction path: {} (resolved: {:?})", header.extraction_path, resolved_extraction_path));
+    if let Err(e) = fs::write(Path::new(&header.extraction_path).join(EXTRACTED_MARKER_FILE), "") {
+        // Only means the next run asks again
+        log.error(&format!("Failed to write {}: {}", EXTRACTED_MARKER_FILE, e));
+    }
+    // Written after any clean step so cleaning doesn't bring the prompt back
+    if !header.eula_text.is_empty()
+        && let Err(e) = fs::write(&eula_marker, "")
+    {
+        // Only means the next run asks again
+        log.error(&format!("Failed to write {}: {}", EULA_MARKER_FILE, e));
+    }
+
+    // Refuse to write more than the configured limit, whatever the header claims
+    let max_extract_size = header.extract_limit();
+    let declared_total: u64 = header.resources.iter().map(|r| r.size as u64).sum();
+    if declared_total > max_extract_size {
+        report_failure(&mut log, &header.messages, StubFailure::Extraction, &format!("Resources total {} bytes, over the {} byte extraction limit.", declared_total, max_extract_size));
+        return None;
+    }
 
     // Extract each resource
-    let mut offset = 0;
     
-    // Decompress the resource data if needed
-    let mut decompressed_resource_bytes: Vec<u8>;  // Added 'mut' keyword here
-    let final_resource_bytes = if header.is_compressed {
-        let mut decompressor = GzDecoder::new(Cursor::new(resource_bytes));
-        decompressed_resource_bytes = Vec::new();
-        decompressor.read_to_end(&mut decompressed_resource_bytes)
-            .expect("Failed to decompress resource data");
-        &decompressed_resource_bytes
+    // Decompress the resource data if needed. Block-compressed (seekable) data is
+    // inflated a block at a time while extracting instead of all up front.
+    let mut resource_data = match header.open_resource_data(resource_bytes, max_extract_size) {
+        Ok(data) => data,
+        Err(e) => {
+            report_failure(&mut log, &header.messages, StubFailure::CorruptArchive, &e.to_string());
+            return None;
+        }
+    };
+    match &resource_data {
+        ResourceData::Blocks(_) => log.debug(&format!("Seekable compression: {} block(s) of {} bytes", header.compressed_blocks.len(), header.compression_block_size)),
+        ResourceData::Inflated(bytes) => log.debug(&format!("Decompressed {} bytes to {} bytes", resource_bytes.len(), bytes.len())),
+        ResourceData::Stored(_) => {}
+    }
+
+    // Resources are extracted in header order, which is the order they were
+    // listed in the packer, apart from main_file_last
+    let plan = match header.extraction_plan(Path::new(&header.extraction_path)) {
+        Ok(plan) => plan,
+        Err(e) => {
+            report_failure(&mut log, &header.messages, StubFailure::CorruptArchive, &e.to_string());
+            return None;
+        }
+    };
+
+    let total = plan.len();
+
+    // Progress is opt-in so small bundles extract silently. A window is only
+    // shown when there is no console attached (GUI-subsystem stubs).
+    let progress_window = if header.show_progress && unsafe { GetConsoleWindow() }.is_invalid() {
+        ProgressWindow::new()
     } else {
-        resource_bytes
+        None
     };
-    
-    for resource in &header.resources {
-        let file_path = Path::new(&header.extraction_path).join(&resource.filename);
+
+    let mut wrote_any = false;
+    for (index, planned) in plan.iter().enumerate() {
+        let (resource, file_path) = (planned.resource, &planned.path);
+        if header.show_progress {
+            let status = format!("Extracting file {} of {}", index + 1, total);
+            eprintln!("{}", status);
+            if let Some(window) = &progress_window {
+                window.set_text(&format!("{}: {}", status, resource.filename));
+            }
+        }
+        if keep_existing_file(&header.update_mode, file_path, resource) {
+            log.debug(&format!("Kept existing {} (update mode: {})", resource.filename, header.update_mode));
+            continue;
+        }
         let size = resource.size as usize;
-        if offset + size > final_resource_bytes.len() {
-            eprintln!("Resource data is incomplete.");
-            return;
+        let data = match planned.read(&mut resource_data) {
+            Ok(data) => data,
+            Err(e) => {
+                report_failure(&mut log, &header.messages, StubFailure::CorruptArchive, &e.to_string());
+                return None;
+            }
+        };
+        if let Some(parent) = file_path.parent() {
+            if let Err(e) = with_retries(header.io_retries, &mut log, || fs::create_dir_all(parent)) {
+                report_failure(&mut log, &header.messages, StubFailure::Extraction, &format!("Failed to create directory {:?}: {}", parent, e));
+                return None;
+            }
+            // The name is clean, but a junction or symlink already in the extraction
+            // directory could still point the write somewhere else
+            if let Ok(resolved_parent) = fs::canonicalize(parent)
+                && !resolved_parent.starts_with(&resolved_extraction_path)
+            {
+                report_failure(&mut log, &header.messages, StubFailure::Extraction, &format!("Refusing to extract {:?}: {:?} resolves outside the extraction directory", resource.filename, parent));
+                return None;
+            }
+        }
+        if file_path.exists() {
+            clear_file_attributes(file_path);
+        }
+        // Once one file is written the share is reachable, so only the first write is retried
+        let retries = if wrote_any { 0 } else { header.io_retries };
+        let written = write_extracted_file(file_path, &data, retries, &mut log);
+        wrote_any = true;
+        match written {
+            Ok(true) => {}
+            // Locked but already up to date, its metadata can't be changed either
+            Ok(false) => continue,
+            Err(e) => {
+                report_failure(&mut log, &header.messages, StubFailure::Extraction, &e);
+                return None;
+            }
+        }
+        if resource.executable
+            && let Err(e) = make_executable(file_path)
+        {
+            log.error(&e);
         }
-        let data = &final_resource_bytes[offset..offset + size];
-        fs::write(&file_path, data)
-            .expect(&format!("Failed to write file {:?}", file_path));
-        offset += size;
+        if let Err(e) = restore_file_metadata(file_path, resource) {
+            // Not fatal, the file contents are what matter
+            log.error(&e);
+        }
+        log.debug(&format!("Extracted {} ({} bytes)", resource.filename, size));
     }
+    log.info(&format!("Extracted {} resource(s)", total));
 
-    // Determine the SHOW_WINDOW_CMD value
-    let show_cmd = match header.execution_style.to_lowercase().as_str() {
-        "no-window"   => SW_HIDE,
-        "minimized"   => SW_SHOWMINIMIZED,
-        "normal"      => SW_SHOWNORMAL,
-        "maximized"   => SW_SHOWMAXIMIZED,
-        _             => SW_SHOWNORMAL,
+    // Optional setup step (e.g. registering a DLL), waited on before launching
+    if !header.post_extract_command.is_empty() {
+        if let Some(window) = &progress_window {
+            window.set_text("Setting up...");
+        }
+        let extraction_dir = std::path::absolute(&header.extraction_path)
+            .unwrap_or_else(|_| PathBuf::from(&header.extraction_path));
+        log.info(&format!("Running post-extract command: {}", header.post_extract_command));
+        let parameters = format!("/c {}", header.post_extract_command);
+        let failure = match run_and_wait("cmd", &parameters, &extraction_dir, show_window_cmd(&header.post_extract_style)) {
+            Ok(0) => None,
+            Ok(code) => Some(format!("Post-extract command {:?} exited with code {}", header.post_extract_command, code)),
+            Err(e) => Some(format!("Post-extract command {:?} could not be started: {}", header.post_extract_command, e)),
+        };
+        match failure {
+            Some(e) if header.post_extract_abort_on_failure => {
+                report_failure(&mut log, &header.messages, StubFailure::Launch, &e);
+                return None;
+            }
+            Some(e) => log.error(&format!("{}, launching anyway", e)),
+            None => log.info("Post-extract command finished"),
+        }
+    }
+
+    // Optional pause before launching (splash screens); keep the progress window
+    // up as a "Loading..." indicator while we wait
+    if header.launch_delay_ms > 0 {
+        log.debug(&format!("Waiting {} ms before launch", header.launch_delay_ms));
+        if let Some(window) = &progress_window {
+            window.set_text("Loading...");
+        }
+        std::thread::sleep(std::time::Duration::from_millis(header.launch_delay_ms as u64));
+    }
+    drop(progress_window);
+
+    let show_cmd = show_window_cmd(&header.execution_style);
+
+    // Launch the "main" file. External commands are passed through as-is so
+    // ShellExecute can resolve them (absolute path or something on PATH).
+    let main_file_path = if header.main_is_external {
+        PathBuf::from(&header.main_file)
+    } else {
+        Path::new(&header.extraction_path).join(&header.main_file)
     };
+    if let Some(shortcut) = &header.shortcut {
+        let working_dir = fs::canonicalize(&header.extraction_path)
+            .unwrap_or_else(|_| PathBuf::from(&header.extraction_path));
+        // Shortcuts need absolute paths, so resolve a relative extraction path first
+        let target = if header.main_is_external {
+            main_file_path.clone()
+        } else {
+            working_dir.join(&header.main_file)
+        };
+        create_shortcuts(shortcut, &target, &working_dir, &mut log);
+    }
+
+    // A service is started by the Service Control Manager, never launched directly
+    if let Some(service) = &header.service {
+        // The SCM wants a plain absolute path, not canonicalize's \\?\ form
+        let binary = Path::new(&header.extraction_path).join(&header.main_file);
+        let binary = std::path::absolute(&binary).unwrap_or(binary);
+        let name = service_name(service, &header.main_file);
+        if let Err(e) = install_service(service, &name, &binary, &mut log) {
+            report_failure(&mut log, &header.messages, StubFailure::Launch, &e);
+        }
+        return None;
+    }
 
-    // Launch the "main" file
-    let main_file_path = Path::new(&header.extraction_path).join(&header.main_file);
     println!("Launching main file: {:?}", main_file_path);
+    log.info(&format!("Launching main file {:?} ({})", main_file_path, header.execution_style));
 
-    // Choose the operation verb: "runas" if elevation is requested, otherwise "open"
-    let operation = if header.run_as_admin { "open" } else { "open" };
+    // Elevation is handled by relaunching the stub, so "open" is the default even
+    // with run_as_admin. Other verbs (print, edit, ...) go to the file's association.
+    let operation = if header.launch_verb.is_empty() { "open" } else { header.launch_verb.as_str() };
+    log.debug(&format!("Launch verb: {}", operation));
 
-    // If the file is a batch file, run it via cmd /c
+    // If the file is a batch file being run, run it via cmd /c
     let file_extension = main_file_path
         .extension()
         .and_then(|ext| ext.to_str())
         .unwrap_or("");
-    if file_extension.eq_ignore_ascii_case("bat") || file_extension.eq_ignore_ascii_case("cmd") {
-        let cmd = "cmd";
-        let parameters = format!("/c \"{}\"", main_file_path.to_str().unwrap());
-        launch_process(operation, cmd, &parameters, show_cmd);
+    let is_batch = file_extension.eq_ignore_ascii_case("bat") || file_extension.eq_ignore_ascii_case("cmd");
+    // A relative working directory is inside the extraction path; an absolute one replaces it
+    let start_dir = (!header.working_dir.is_empty()).then(|| {
+        let dir = Path::new(&header.extraction_path).join(&header.working_dir);
+        std::path::absolute(&dir).unwrap_or(dir)
+    });
+    if !header.launch_args.is_empty() {
+        log.debug(&format!("Launch arguments: {}", header.launch_args));
+    }
+    // Single instance holds its mutex until the main file exits, so it waits too
+    let wait_for_exit = header.wait_for_exit || header.single_instance;
+    let shell_launch = |verb: &str| if is_batch && (verb == "open" || verb == "runas") {
+        let mut parameters = format!("/c \"{}\"", main_file_path.to_string_lossy());
+        if !header.launch_args.is_empty() {
+            parameters.push(' ');
+            parameters.push_str(&header.launch_args);
+        }
+        launch_process(verb, "cmd", &parameters, start_dir.as_deref(), show_cmd, wait_for_exit)
+    } else {
+        launch_process(verb, &main_file_path.to_string_lossy(), &header.launch_args, start_dir.as_deref(), show_cmd, wait_for_exit)
+    };
+    let launched = if header.capture_output {
+        // Absolute paths, since the child runs in the extraction directory
+        let extraction_dir = std::path::absolute(&header.extraction_path)
+            .unwrap_or_else(|_| PathBuf::from(&header.extraction_path));
+        let program = if header.main_is_external {
+            main_file_path.clone()
+        } else {
+            extraction_dir.join(&header.main_file)
+        };
+        let working_dir = start_dir.as_deref().unwrap_or(&extraction_dir);
+        if is_batch {
+            let arguments = ["/c".to_string(), program.to_string_lossy().to_string()];
+            run_with_output_window(Path::new("cmd"), &arguments, &header.launch_args, working_dir)
+        } else {
+            run_with_output_window(&program, &[], &header.launch_args, working_dir)
+        }.map(|()| None)
     } else {
-        launch_process(operation, main_file_path.to_str().unwrap(), "", show_cmd);
+        let mut result = shell_launch(operation);
+        // Access denied is often just missing rights, e.g. an installer without a
+        // manifest, so offer to try again elevated
+        if let Err(e) = &result {
+            log.error(&format!("Launch failed with error {}: {}", e.code, e.message));
+            let needs_elevation = matches!(e.code, ERROR_ACCESS_DENIED_CODE | ERROR_ELEVATION_REQUIRED_CODE);
+            if needs_elevation && operation != "runas" {
+                let title = if header.messages.title.is_empty() { "Administrator Rights" } else { &header.messages.title };
+                let prompt = format!("{}\n\nTry again as administrator?", e.message);
+                if message_box(title, &prompt, MB_YESNO | MB_ICONQUESTION) == IDYES {
+                    log.info("Retrying the launch elevated");
+                    result = shell_launch("runas");
+                }
+            }
+        }
+        result.map_err(|e| e.message)
+    };
+    let exit_code = match launched {
+        Ok(exit_code) => exit_code,
+        Err(e) => {
+            report_failure(&mut log, &header.messages, StubFailure::Launch, &e);
+            None
+        }
+    };
+    if let Some(mutex) = _instance_mutex {
+        unsafe {
+            let _ = CloseHandle(mutex);
+        }
     }
+    // Scripts running the packed EXE see the main file's result
+    if let (true, Some(exit_code)) = (header.wait_for_exit, exit_code) {
+        log.info(&format!("Main file exited with code {}", exit_code));
+        return Some(exit_code as i32);
+    }
+    None
 }
 
-/// Launch a process using ShellExecuteW
-/// The `show_cmd` parameter is of type SHOW_WINDOW_CMD
-fn launch_process(operation: &str, file: &str, parameters: &str, show_cmd: windows::Win32::UI::WindowsAndMessaging::SHOW_WINDOW_CMD) {
+/// SHOW_WINDOW_CMD for an execution style from the header
+fn show_window_cmd(style: &str) -> windows::Win32::UI::WindowsAndMessaging::SHOW_WINDOW_CMD {
+    match style.to_lowercase().as_str() {
+        "no-window"   => SW_HIDE,
+        "minimized"   => SW_SHOWMINIMIZED,
+        "normal"      => SW_SHOWNORMAL,
+        "maximized"   => SW_SHOWMAXIMIZED,
+        _             => SW_SHOWNORMAL,
+    }
+}
+
+/// Start `file` with ShellExecuteExW in `directory`, wait for it to exit and
+/// return its exit code.
+fn run_and_wait(file: &str, parameters: &str, directory: &Path, show_cmd: windows::Win32::UI::WindowsAndMessaging::SHOW_WINDOW_CMD) -> Result<u32, String> {
+    let wide_operation = HSTRING::from("open");
+    let wide_file = HSTRING::from(file);
+    let wide_parameters = HSTRING::from(parameters);
+    let wide_directory = HSTRING::from(directory.as_os_str());
+    let mut info = SHELLEXECUTEINFOW {
+        cbSize: size_of::<SHELLEXECUTEINFOW>() as u32,
+        fMask: SEE_MASK_NOCLOSEPROCESS,
+        lpVerb: PCWSTR(wide_operation.as_ptr()),
+        lpFile: PCWSTR(wide_file.as_ptr()),
+        lpParameters: PCWSTR(wide_parameters.as_ptr()),
+        lpDirectory: PCWSTR(wide_directory.as_ptr()),
+        nShow: show_cmd.0,
+        ..Default::default()
+    };
+    if let Err(e) = unsafe { ShellExecuteExW(&mut info) } {
+        return Err(format!("ShellExecuteExW failed: {}", e));
+    }
+    // Copied out first since the struct is packed on x86
+    let process = info.hProcess;
+    if process.is_invalid() {
+        return Err("no process was started".to_string());
+    }
+    let mut exit_code = 0u32;
+    let result = unsafe {
+        WaitForSingleObject(process, INFINITE);
+        let result = GetExitCodeProcess(process, &mut exit_code);
+        let _ = CloseHandle(process);
+        result
+    };
+    result.map_err(|e| format!("Failed to read the exit code: {}", e))?;
+    Ok(exit_code)
+}
+
+/// Windows errors ShellExecuteExW commonly fails with, see launch_error_message
+const ERROR_FILE_NOT_FOUND_CODE: u32 = 2;
+const ERROR_PATH_NOT_FOUND_CODE: u32 = 3;
+const ERROR_ACCESS_DENIED_CODE: u32 = 5;
+const ERROR_BAD_FORMAT_CODE: u32 = 11;
+const ERROR_BAD_EXE_FORMAT_CODE: u32 = 193;
+const ERROR_ELEVATION_REQUIRED_CODE: u32 = 740;
+const ERROR_NO_ASSOCIATION_CODE: u32 = 1155;
+const ERROR_DLL_NOT_FOUND_CODE: u32 = 1157;
+const ERROR_CANCELLED_CODE: u32 = 1223;
+
+/// Why ShellExecuteExW couldn't start a file: the Win32 error code and a
+/// message the user can act on
+struct LaunchError {
+    code: u32,
+    message: String,
+}
+
+/// Turn a ShellExecuteExW failure for `file` into guidance rather than an error code.
+fn launch_error_message(code: u32, file: &str) -> String {
+    let name = Path::new(file).file_name().map_or_else(|| file.to_string(), |n| n.to_string_lossy().to_string());
+    match code {
+        ERROR_FILE_NOT_FOUND_CODE | ERROR_PATH_NOT_FOUND_CODE => format!("{} was not found. Security software may have removed it; try running this file again.", name),
+        ERROR_ACCESS_DENIED_CODE => format!("Windows denied access to {}. It may be blocked by security software or need administrator rights.", name),
+        ERROR_ELEVATION_REQUIRED_CODE => format!("{} needs administrator rights to start.", name),
+        ERROR_BAD_FORMAT_CODE | ERROR_BAD_EXE_FORMAT_CODE => format!("{} is not a valid application for this version of Windows (e.g. a 64-bit program on 32-bit Windows).", name),
+        ERROR_NO_ASSOCIATION_CODE => {
+            let extension = Path::new(file).extension().map(|e| e.to_string_lossy().to_string()).unwrap_or_default();
+            format!("No program is set up to open .{} files such as {}. Install one and try again.", extension, name)
+        }
+        ERROR_DLL_NOT_FOUND_CODE => format!("{} could not start because a DLL it needs is missing. Installing its runtime (e.g. the Visual C++ Redistributable) usually fixes this.", name),
+        ERROR_CANCELLED_CODE => format!("Starting {} was cancelled.", name),
+        _ => format!("Windows could not start {} (error {}).", name, code),
+    }
+}
+
+/// Launch a process using ShellExecuteExW
+/// The `show_cmd` parameter is of type SHOW_WINDOW_CMD. `directory` is the
+/// start folder, None = inherit ours. With `wait_for_exit` this only returns
+/// once the launched process has exited, with its exit code (None if Windows
+/// handed the file to an already running program, so there was nothing to wait on).
+fn launch_process(operation: &str, file: &str, parameters: &str, directory: Option<&Path>, show_cmd: windows::Win32::UI::WindowsAndMessaging::SHOW_WINDOW_CMD, wait_for_exit: bool) -> Result<Option<u32>, LaunchError> {
     use std::ffi::OsStr;
     use std::iter;
     use std::os::windows::ffi::OsStrExt;
@@ -247,23 +1943,42 @@ fn launch_process(operation: &str, file: &str, parameters: &str, show_cmd: windo
         .encode_wide()
         .chain(iter::once(0))
         .collect();
+    let wide_directory: Option<Vec<u16>> = directory.map(|dir| dir.as_os_str()
+        .encode_wide()
+        .chain(iter::once(0))
+        .collect());
 
-    let result = unsafe {
-        ShellExecuteW(
-            Some(HWND(std::ptr::null_mut())),
-            PCWSTR(wide_operation.as_ptr()),
-            PCWSTR(wide_file.as_ptr()),
-            if parameters.is_empty() {
-                PCWSTR(std::ptr::null())
-            } else {
-                PCWSTR(wide_parameters.as_ptr())
-            },
-            PCWSTR(std::ptr::null()),
-            show_cmd,
-        )
+    let mut info = SHELLEXECUTEINFOW {
+        cbSize: size_of::<SHELLEXECUTEINFOW>() as u32,
+        fMask: if wait_for_exit { SEE_MASK_NOCLOSEPROCESS } else { 0 },
+        lpVerb: PCWSTR(wide_operation.as_ptr()),
+        lpFile: PCWSTR(wide_file.as_ptr()),
+        lpParameters: if parameters.is_empty() {
+            PCWSTR(std::ptr::null())
+        } else {
+            PCWSTR(wide_parameters.as_ptr())
+        },
+        lpDirectory: wide_directory.as_ref().map_or(PCWSTR(std::ptr::null()), |dir| PCWSTR(dir.as_ptr())),
+        nShow: show_cmd.0,
+        ..Default::default()
     };
 
-    if result.0 as isize <= 32 {
-        eprintln!("ShellExecuteW failed with code: {:?}", result.0);
+    if let Err(e) = unsafe { ShellExecuteExW(&mut info) } {
+        // Win32 errors come wrapped as HRESULT_FROM_WIN32 (facility 7)
+        let hresult = e.code().0 as u32;
+        let code = if (hresult >> 16) & 0x1fff == 7 { hresult & 0xffff } else { hresult };
+        return Err(LaunchError { code, message: launch_error_message(code, file) });
+    }
+    // Copied out first since the struct is packed on x86
+    let process = info.hProcess;
+    if !wait_for_exit || process.is_invalid() {
+        return Ok(None);
     }
-}
\ No newline at end of file
+    let mut exit_code = 0u32;
+    unsafe {
+        WaitForSingleObject(process, INFINITE);
+        let read = GetExitCodeProcess(process, &mut exit_code);
+        let _ = CloseHandle(process);
+        Ok(read.ok().map(|_| exit_code))
+    }
+}